@@ -14,10 +14,90 @@ struct Args {
     #[arg(long)]
     reset: bool,
 
+    /// Specifies the path of the settings file used to restore previous devices.
+    #[arg(long, default_value = "./settings.json")]
+    settings_path: Option<String>,
+
+    /// Starts a simulated device, can be used multiple times, e.g: "ping1d", "ping360:udp:12345" or "ping1d:pty".
+    #[arg(long, value_name = "DEVICE[:udp[:PORT]|:pty]")]
+    simulator: Vec<crate::device::simulator::SimulatorConfig>,
+
+    /// Sets the mean seabed depth in meters used by simulated devices.
+    #[arg(long, default_value = "10.0")]
+    simulator_depth: f32,
+
+    /// Turns off the Zenoh bridge used to receive the vehicle pose and publish sonar data.
+    #[arg(long)]
+    disable_zenoh: bool,
+
+    /// Sets the Zenoh mode, overrides the config file, "client" when there is no file.
+    #[arg(long, value_name = "client|peer|router")]
+    zenoh_mode: Option<crate::vehicle::ZenohMode>,
+
+    /// Sets a Zenoh endpoint to connect to, can be used multiple times, overrides the config file, e.g: "tcp/192.168.2.2:7447".
+    #[arg(long, value_name = "ENDPOINT")]
+    zenoh_endpoint: Vec<String>,
+
+    /// Specifies the path of a Zenoh configuration file used as base for the session.
+    #[arg(long)]
+    zenoh_config: Option<String>,
+
+    /// Sets the MAVLink system id of the vehicle followed by the Zenoh bridge.
+    #[arg(long, default_value = "1")]
+    zenoh_system_id: u8,
+
+    /// Sets the root of the MAVLink keys on Zenoh, messages are received from "<PREFIX>/**/<SYSTEM_ID>/<MESSAGE>".
+    #[arg(long, value_name = "PREFIX", default_value = "mavlink")]
+    zenoh_key_prefix: String,
+
+    /// Sets where Ping1D DISTANCE_SENSOR messages are sent to, "zenoh" or a MAVLink connection like "udpout:127.0.0.1:14550".
+    #[arg(long, value_name = "zenoh|MAVLINK_ADDRESS", default_value = "zenoh")]
+    rangefinder_output: crate::device::rangefinder::RangefinderTransportSelection,
+
+    /// Specifies the path of the directory where recordings are stored.
+    #[arg(long, default_value = "./recordings")]
+    recordings_path: String,
+
+    /// Starts a new recording file once the current one reaches this size in megabytes.
+    #[arg(long, value_name = "MB")]
+    recording_max_file_size: Option<u64>,
+
+    /// Starts a new recording file once the current one covers this duration in seconds.
+    #[arg(long, value_name = "SECONDS")]
+    recording_max_duration: Option<u64>,
+
+    /// Deletes the oldest recordings once all of them take more than this size in gigabytes.
+    #[arg(long, value_name = "GB")]
+    recording_retention_size: Option<f64>,
+
+    /// Deletes recordings older than this number of days.
+    #[arg(long, value_name = "DAYS")]
+    recording_retention_days: Option<u32>,
+
+    /// Stops recording when the free disk space falls below this size in megabytes.
+    #[arg(long, value_name = "MB", default_value = "500")]
+    recording_min_free_disk: u64,
+
+    /// Records every Ping protocol message byte for byte, besides the decoded channels.
+    #[arg(long)]
+    recording_raw_messages: bool,
+
     /// Sets the address for the REST API server
     #[arg(long, value_name = "IP>:<PORT", default_value = "0.0.0.0:8080")]
     rest_server: String,
 
+    /// Sets a bearer token accepted by the server, can be used multiple times, turns on authentication, e.g: "operator:secret".
+    #[arg(long, value_name = "viewer|operator|admin:TOKEN")]
+    auth_token: Vec<crate::server::auth::AuthToken>,
+
+    /// Sets a basic authentication user, can be used multiple times, turns on authentication, e.g: "admin:pilot:secret".
+    #[arg(long, value_name = "viewer|operator|admin:USER:PASSWORD")]
+    auth_user: Vec<crate::server::auth::AuthUser>,
+
+    /// Sets an origin allowed to access the server from browsers, can be used multiple times, any origin is allowed without it.
+    #[arg(long, value_name = "ORIGIN")]
+    cors_origin: Vec<String>,
+
     /// Turns all log categories up to Debug, for more information check RUST_LOG env variable.
     #[arg(short, long)]
     verbose: bool,
@@ -82,6 +162,56 @@ pub fn is_enable_auto_create() -> bool {
     MANAGER.clap_matches.enable_auto_create
 }
 
+pub fn is_reset() -> bool {
+    MANAGER.clap_matches.reset
+}
+
+pub fn settings_path() -> String {
+    let settings_path = MANAGER.clap_matches.settings_path.clone().expect(
+        "Clap arg \"settings-path\" should always be \"Some(_)\" because of the default value.",
+    );
+
+    shellexpand::full(&settings_path)
+        .expect("Failed to expand path")
+        .to_string()
+}
+
+// Return the simulated devices requested on the command line
+pub fn simulators() -> Vec<crate::device::simulator::SimulatorConfig> {
+    MANAGER
+        .clap_matches
+        .simulator
+        .iter()
+        .cloned()
+        .map(|mut config| {
+            config.seabed.depth = MANAGER.clap_matches.simulator_depth;
+            config
+        })
+        .collect()
+}
+
+// Return where rangefinder data should be sent to
+pub fn rangefinder_output() -> crate::device::rangefinder::RangefinderTransportSelection {
+    MANAGER.clap_matches.rangefinder_output.clone()
+}
+
+// Return the Zenoh bridge settings requested on the command line
+pub fn zenoh_bridge_config() -> crate::vehicle::ZenohBridgeConfig {
+    let args = &MANAGER.clap_matches;
+    crate::vehicle::ZenohBridgeConfig {
+        enabled: !args.disable_zenoh,
+        mode: args.zenoh_mode,
+        endpoints: args.zenoh_endpoint.clone(),
+        config_file: args.zenoh_config.as_ref().map(|path| {
+            shellexpand::full(path)
+                .expect("Failed to expand path")
+                .to_string()
+        }),
+        system_id: args.zenoh_system_id,
+        key_prefix: args.zenoh_key_prefix.clone(),
+    }
+}
+
 pub fn log_path() -> String {
     let log_path =
         MANAGER.clap_matches.log_path.clone().expect(
@@ -98,6 +228,35 @@ pub fn server_address() -> String {
     MANAGER.clap_matches.rest_server.clone()
 }
 
+pub fn recordings_path() -> String {
+    shellexpand::full(&MANAGER.clap_matches.recordings_path)
+        .expect("Failed to expand path")
+        .to_string()
+}
+
+pub fn recording_policy() -> crate::device::recording::rolling::RecordingPolicy {
+    let args = &MANAGER.clap_matches;
+    crate::device::recording::rolling::RecordingPolicy {
+        max_file_size_mb: args.recording_max_file_size,
+        max_duration_s: args.recording_max_duration,
+        retention_gb: args.recording_retention_size,
+        retention_days: args.recording_retention_days,
+        min_free_disk_mb: args.recording_min_free_disk,
+        raw_messages: args.recording_raw_messages,
+    }
+}
+
+pub fn auth_config() -> crate::server::auth::AuthConfig {
+    crate::server::auth::AuthConfig {
+        tokens: MANAGER.clap_matches.auth_token.clone(),
+        users: MANAGER.clap_matches.auth_user.clone(),
+    }
+}
+
+pub fn cors_origins() -> Vec<String> {
+    MANAGER.clap_matches.cors_origin.clone()
+}
+
 // Return the command line used to start this application
 pub fn command_line_string() -> String {
     std::env::args().collect::<Vec<String>>().join(" ")
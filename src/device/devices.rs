@@ -1,7 +1,7 @@
 use bluerobotics_ping::device::PingDevice;
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tracing::{debug, error, trace, warn};
 
 #[derive(Debug)]
@@ -208,19 +208,58 @@ impl DeviceActor {
             receiver,
             device_type: device,
         };
-        let actor_handler = DeviceActorHandler { sender };
+        let (control, _) = broadcast::channel(100);
+        let actor_handler = DeviceActorHandler { sender, control };
 
         trace!("Device and handler successfully created: Success");
         (actor, actor_handler)
     }
 }
 
+/// Request sent to a device and its outcome, published so recordings can keep track of them
+#[derive(Debug, Clone, Serialize)]
+pub struct PingControlMessage {
+    pub request: PingRequest,
+    /// Error returned while handling the request, empty when it succeeded
+    pub error: Option<String>,
+}
+
 #[derive(Clone, Debug)]
 pub struct DeviceActorHandler {
     pub sender: mpsc::Sender<DeviceActorRequest>,
+    control: broadcast::Sender<PingControlMessage>,
 }
 impl DeviceActorHandler {
+    pub fn subscribe_control(&self) -> broadcast::Receiver<PingControlMessage> {
+        self.control.subscribe()
+    }
+
     pub async fn send(&self, device_request: PingRequest) -> Result<PingAnswer, DeviceError> {
+        // Only requests reaching the device are published, and only when someone is listening
+        let control_request = match &device_request {
+            PingRequest::Ping1D(_)
+            | PingRequest::Ping360(_)
+            | PingRequest::Common(_)
+            | PingRequest::Upgrade
+                if self.control.receiver_count() > 0 =>
+            {
+                Some(device_request.clone())
+            }
+            _ => None,
+        };
+
+        let result = self.forward(device_request).await;
+
+        if let Some(request) = control_request {
+            let _ = self.control.send(PingControlMessage {
+                request,
+                error: result.as_ref().err().map(|err| format!("{err:?}")),
+            });
+        }
+        result
+    }
+
+    async fn forward(&self, device_request: PingRequest) -> Result<PingAnswer, DeviceError> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         let device_request = DeviceActorRequest {
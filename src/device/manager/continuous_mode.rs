@@ -1,3 +1,5 @@
+use std::sync::{Arc, RwLock};
+
 use serde_json::json;
 use tracing::{debug, error, trace};
 use uuid::Uuid;
@@ -7,7 +9,10 @@ use crate::device::{
     manager::{Answer, DeviceAnswer, DeviceManager, DeviceSelection, ManagerError},
 };
 
-use super::{DeviceProperties, ManagerActorHandler, Ping360Properties, SourceSelection};
+use super::{
+    sonar_image::GRADIANS, DeviceProperties, ManagerActorHandler, Ping360Config, Ping360Properties,
+    SourceSelection,
+};
 
 impl DeviceManager {
     // Call the helpers specifically for each device type
@@ -36,23 +41,41 @@ impl DeviceManager {
         };
 
         match device_type {
-            DeviceSelection::Ping1D => Some(tokio::spawn(async move {
-                loop {
-                    match subscriber.recv().await {
-                        Ok(msg) => {
-                            Self::ping1d_continuous_mode_helper(msg, device_id);
-                        }
-                        Err(err @ tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
-                            error!("Device subscriber channel issue {err:?}, device: {device_id}");
-                            Self::handle_error_continuous_mode(err, device_id);
-                        }
-                        Err(err) => {
-                            Self::handle_error_continuous_mode(err, device_id);
-                            break;
+            DeviceSelection::Ping1D => {
+                let (filter_config, rangefinder) = match self.get_device_properties(device_id).await
+                {
+                    Ok(Some(DeviceProperties::Ping1D(properties))) => {
+                        (properties.distance_filter, properties.rangefinder)
+                    }
+                    _ => Default::default(),
+                };
+                let mut filter = super::distance_filter::DistanceFilter::new(filter_config);
+
+                Some(tokio::spawn(async move {
+                    loop {
+                        match subscriber.recv().await {
+                            Ok(msg) => {
+                                Self::ping1d_continuous_mode_helper(
+                                    msg,
+                                    device_id,
+                                    &mut filter,
+                                    &rangefinder,
+                                );
+                            }
+                            Err(err @ tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                                error!(
+                                    "Device subscriber channel issue {err:?}, device: {device_id}"
+                                );
+                                Self::handle_error_continuous_mode(err, device_id);
+                            }
+                            Err(err) => {
+                                Self::handle_error_continuous_mode(err, device_id);
+                                break;
+                            }
                         }
                     }
-                }
-            })),
+                }))
+            }
             DeviceSelection::Ping360 => {
                 let device_properties = self.get_device_properties(device_id).await.ok()?;
                 let Some(DeviceProperties::Ping360(properties)) = device_properties else {
@@ -68,7 +91,8 @@ impl DeviceManager {
                 if supports_auto_transmit {
                     match self.get_device_source(device_id) {
                         Ok(source) => match source {
-                            super::SourceSelection::UdpStream(_) => {
+                            super::SourceSelection::UdpStream(_)
+                            | super::SourceSelection::Replay(_) => {
                                 Some(Self::start_ping360_firmware_mode(
                                     self.get_device_manager_handler(),
                                     handler,
@@ -77,11 +101,13 @@ impl DeviceManager {
                                     subscriber,
                                 ))
                             }
-                            super::SourceSelection::SerialStream(_) => {
+                            // TCP sources are usually serial bridges, keep the serial behavior
+                            super::SourceSelection::SerialStream(_)
+                            | super::SourceSelection::TcpStream(_) => {
                                 Some(Self::start_ping360_software_mode(
                                     handler,
                                     device_id,
-                                    properties.clone(),
+                                    properties.continuous_mode_settings.clone(),
                                 ))
                             }
                         },
@@ -94,7 +120,7 @@ impl DeviceManager {
                     Some(Self::start_ping360_software_mode(
                         handler,
                         device_id,
-                        properties.clone(),
+                        properties.continuous_mode_settings.clone(),
                     ))
                 }
             }
@@ -151,7 +177,7 @@ impl DeviceManager {
             DeviceSelection::Ping360 => {
                 if matches!(
                     self.get_device_source(device_id)?,
-                    SourceSelection::UdpStream(_)
+                    SourceSelection::UdpStream(_) | SourceSelection::Replay(_)
                 ) {
                     if let Err(err) = self.turnoff_device_on_continuous_mode(device_id).await {
                         error!("Something went wrong while executing continuous_mode_shutdown_routine, details: {err:?}, device: {device_id}");
@@ -171,13 +197,25 @@ impl DeviceManager {
         Ok(())
     }
 
-    // An inner helper focused on Ping1D, which uses Profile message to plot graphs
+    // An inner helper focused on Ping1D, which uses Profile message to plot graphs,
+    // the filtered distance follows the raw profile and is forwarded to the autopilot when enabled
     pub fn ping1d_continuous_mode_helper(
         msg: bluerobotics_ping::message::ProtocolMessage,
         device_id: Uuid,
+        filter: &mut super::distance_filter::DistanceFilter,
+        rangefinder: &std::sync::RwLock<crate::device::rangefinder::RangefinderConfig>,
     ) {
         if msg.message_id == <bluerobotics_ping::ping1d::ProfileStruct as bluerobotics_ping::message::MessageInfo>::id() {
-            if let Ok(bluerobotics_ping::Messages::Ping1D(bluerobotics_ping::ping1d::Messages::Profile(_answer))) = bluerobotics_ping::Messages::try_from(&msg) {
+            if let Ok(bluerobotics_ping::Messages::Ping1D(bluerobotics_ping::ping1d::Messages::Profile(profile))) = bluerobotics_ping::Messages::try_from(&msg) {
+                let filtered_distance = filter.process(&profile, tokio::time::Instant::now());
+
+                if let Ok(rangefinder) = rangefinder.read() {
+                    match &filtered_distance {
+                        Some(filtered) if rangefinder.use_filtered => crate::device::rangefinder::send(&rangefinder, filtered.distance, filtered.confidence),
+                        _ => crate::device::rangefinder::send(&rangefinder, profile.distance, profile.confidence),
+                    }
+                }
+
                 let answer = Answer::DeviceMessage(DeviceAnswer {
                     answer: crate::device::devices::PingAnswer::PingMessage(
                         match  bluerobotics_ping::Messages::try_from(&msg){
@@ -190,6 +228,15 @@ impl DeviceManager {
                     device_id,
                 });
                 crate::server::protocols::v1::websocket::send_to_websockets(json!(answer), Some(device_id));
+
+                if let Some(filtered_distance) = filtered_distance {
+                    super::distance_filter::publish(device_id, &filtered_distance);
+                    let answer = Answer::FilteredDistance(super::distance_filter::FilteredDistanceAnswer {
+                        filtered_distance,
+                        device_id,
+                    });
+                    crate::server::protocols::v1::websocket::send_to_websockets(json!(answer), Some(device_id));
+                }
             }
         }
     }
@@ -200,7 +247,8 @@ impl DeviceManager {
         device_id: Uuid,
     ) {
         if msg.message_id == <bluerobotics_ping::ping360::AutoDeviceDataStruct as bluerobotics_ping::message::MessageInfo>::id() {
-                if let Ok(bluerobotics_ping::Messages::Ping360(bluerobotics_ping::ping360::Messages::AutoDeviceData(_answer))) = bluerobotics_ping::Messages::try_from(&msg) {
+                if let Ok(bluerobotics_ping::Messages::Ping360(bluerobotics_ping::ping360::Messages::AutoDeviceData(answer))) = bluerobotics_ping::Messages::try_from(&msg) {
+                    super::sonar_image::update(device_id, answer.angle, answer.num_steps, &answer.data);
                     let answer = Answer::DeviceMessage(DeviceAnswer {
                         answer: crate::device::devices::PingAnswer::PingMessage(
                             match  bluerobotics_ping::Messages::try_from(&msg){
@@ -218,7 +266,18 @@ impl DeviceManager {
     }
 
     // An inner helper focused on Ping360, which uses DeviceData message to plot graphs
-    pub fn ping360_continuous_mode_helper(msg: bluerobotics_ping::Messages, device_id: Uuid) {
+    pub fn ping360_continuous_mode_helper(
+        msg: bluerobotics_ping::Messages,
+        device_id: Uuid,
+        num_steps: u8,
+    ) {
+        if let bluerobotics_ping::Messages::Ping360(
+            bluerobotics_ping::ping360::Messages::DeviceData(data),
+        ) = &msg
+        {
+            super::sonar_image::update(device_id, data.angle, num_steps, &data.data);
+        }
+
         let answer = Answer::DeviceMessage(DeviceAnswer {
             answer: crate::device::devices::PingAnswer::PingMessage(msg),
             device_id,
@@ -339,11 +398,10 @@ impl DeviceManager {
     fn start_ping360_software_mode(
         handler: DeviceActorHandler,
         device_id: Uuid,
-        properties: Ping360Properties,
+        config: Arc<RwLock<Ping360Config>>,
     ) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
             loop {
-                let config = properties.continuous_mode_settings.clone();
                 let initial_settings = match config.read() {
                     Ok(settings) => *settings,
                     Err(err) => {
@@ -390,7 +448,11 @@ impl DeviceManager {
                     {
                         Ok(answer) => match answer {
                             crate::device::devices::PingAnswer::PingMessage(msg) => {
-                                Self::ping360_continuous_mode_helper(msg, device_id)
+                                Self::ping360_continuous_mode_helper(
+                                    msg,
+                                    device_id,
+                                    initial_settings.num_steps,
+                                )
                             }
                             msg => {
                                 error!("Unexpected message during scan: {msg:?}");
@@ -416,6 +478,8 @@ impl DeviceManager {
         })
     }
 
+    // Angles are stepped as an offset from the start angle, so sectors with a stop angle lower
+    // than the start angle sweep across zero
     fn calculate_next_angle(
         current_angle: u16,
         step_size: u16,
@@ -424,24 +488,97 @@ impl DeviceManager {
         start_angle: u16,
         stop_angle: u16,
     ) -> u16 {
+        let gradians = GRADIANS as i32;
         if is_full_circle {
-            if current_angle + step_size >= 400 {
+            return if current_angle as i32 + step_size as i32 >= gradians {
                 0
             } else {
                 current_angle + step_size
-            }
-        } else if *direction > 0 {
-            if current_angle + step_size > stop_angle {
+            };
+        }
+
+        let span = (stop_angle as i32 - start_angle as i32).rem_euclid(gradians);
+        let offset = (current_angle as i32 - start_angle as i32).rem_euclid(gradians);
+        let step = step_size as i32;
+        let offset = if *direction > 0 {
+            if offset + step > span {
                 *direction = -1;
-                stop_angle
+                span
             } else {
-                current_angle + step_size
+                offset + step
             }
-        } else if (current_angle as i32 - step_size as i32) <= start_angle as i32 {
+        } else if offset - step <= 0 {
             *direction = 1;
-            start_angle
+            0
         } else {
-            current_angle.wrapping_sub(step_size)
+            offset - step
+        };
+        ((start_angle as i32 + offset) % gradians) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{
+        devices::{DeviceActor, DeviceType, PingAnswer, PingRequest},
+        simulator::{SimulatedDevice, SyntheticSeabed},
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[tokio::test]
+    async fn test_ping360_software_mode_crosses_zero() {
+        // Simulated Ping360 answering the transducer requests of the sweep
+        let (device_port, mut simulator_port) = tokio::io::duplex(64 * 1024);
+        let mut simulator =
+            SimulatedDevice::new(DeviceSelection::Ping360, SyntheticSeabed::default());
+        tokio::spawn(async move {
+            let mut buffer = [0u8; 1024];
+            while let Ok(size @ 1..) = simulator_port.read(&mut buffer).await {
+                for answer in simulator.handle_bytes(&buffer[..size]) {
+                    if simulator_port.write_all(&answer).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let (actor, handler) = DeviceActor::new(
+            DeviceType::Ping360(bluerobotics_ping::device::Ping360::new(device_port)),
+            10,
+        );
+        tokio::spawn(actor.run());
+        let mut subscriber = match handler.send(PingRequest::GetSubscriber).await.unwrap() {
+            PingAnswer::Subscriber(subscriber) => subscriber,
+            answer => panic!("Unexpected answer: {answer:?}"),
+        };
+
+        let config = Arc::new(RwLock::new(Ping360Config {
+            mode: 1,
+            gain_setting: 0,
+            transmit_duration: 32,
+            sample_period: 80,
+            transmit_frequency: 740,
+            number_of_samples: 200,
+            start_angle: 390,
+            stop_angle: 10,
+            num_steps: 5,
+            delay: 0,
+        }));
+        let sweep = DeviceManager::start_ping360_software_mode(handler, Uuid::new_v4(), config);
+
+        let mut angles = Vec::new();
+        while angles.len() < 11 {
+            let message = subscriber.recv().await.unwrap();
+            if let Ok(bluerobotics_ping::Messages::Ping360(
+                bluerobotics_ping::ping360::Messages::DeviceData(data),
+            )) = bluerobotics_ping::Messages::try_from(&message)
+            {
+                angles.push(data.angle);
+            }
         }
+        sweep.abort();
+
+        assert_eq!(angles, [390, 395, 0, 5, 10, 10, 5, 0, 395, 390, 395]);
     }
 }
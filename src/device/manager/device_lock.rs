@@ -0,0 +1,218 @@
+use std::time::Duration;
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use tracing::info;
+use uuid::Uuid;
+
+use super::{Answer, DeviceManager, ManagerError, ModifyDeviceCommand, Request};
+use crate::device::devices::{Ping1DRequest, Ping360Request, PingCommonRequest, PingRequest};
+
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(60);
+pub const MAX_LOCK_TIMEOUT: Duration = Duration::from_secs(3600);
+
+// Control of a device by a single client, until released or expired
+#[derive(Debug, Clone)]
+pub struct DeviceLock {
+    pub owner: String,
+    pub expires_at: Instant,
+}
+
+impl DeviceLock {
+    pub fn is_expired(&self) -> bool {
+        Instant::now() >= self.expires_at
+    }
+
+    pub fn info(&self) -> DeviceLockInfo {
+        DeviceLockInfo {
+            owner: self.owner.clone(),
+            expires_in_ms: self
+                .expires_at
+                .saturating_duration_since(Instant::now())
+                .as_millis() as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceLockInfo {
+    /// Client holding the device
+    pub owner: String,
+    /// Time until the lock expires, unless renewed
+    pub expires_in_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct LockRequest {
+    pub uuid: Uuid,
+    /// Lock duration in seconds, acquiring it again renews it
+    pub timeout_s: Option<u64>,
+}
+
+impl ModifyDeviceCommand {
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            ModifyDeviceCommand::GetPing360Config
+                | ModifyDeviceCommand::GetPing1DDistanceFilter
+                | ModifyDeviceCommand::GetPing1DRangefinder
+                | ModifyDeviceCommand::GetPing360ScanPlans
+        )
+    }
+}
+
+impl PingRequest {
+    // Requests changing the device configuration or state, anything else only reads data
+    pub fn is_mutating(&self) -> bool {
+        match self {
+            PingRequest::Ping1D(request) => matches!(
+                request,
+                Ping1DRequest::SetDeviceId(_)
+                    | Ping1DRequest::GotoBootloader
+                    | Ping1DRequest::SetModeAuto(_)
+                    | Ping1DRequest::SetPingInterval(_)
+                    | Ping1DRequest::SetPingEnable(_)
+                    | Ping1DRequest::SetSpeedOfSound(_)
+                    | Ping1DRequest::SetRange(_)
+                    | Ping1DRequest::SetGainSetting(_)
+                    | Ping1DRequest::ContinuousStart(_)
+                    | Ping1DRequest::ContinuousStop(_)
+            ),
+            PingRequest::Ping360(request) => !matches!(request, Ping360Request::DeviceData),
+            PingRequest::Common(request) => matches!(request, PingCommonRequest::SetDeviceId(_)),
+            PingRequest::Upgrade | PingRequest::Stop => true,
+            PingRequest::GetSubscriber => false,
+        }
+    }
+}
+
+impl Request {
+    // Device changed by the request, the ones that must be arbitrated between clients.
+    // Device requests only reading data, like the ones allowed to viewers, aren't arbitrated.
+    pub fn controlled_device(&self) -> Option<Uuid> {
+        match self {
+            Request::ModifyDevice(modify) if !modify.modify.is_read_only() => Some(modify.uuid),
+            Request::Ping(ping) if ping.device_request.is_mutating() => Some(ping.uuid),
+            Request::Delete(uuid)
+            | Request::EnableContinuousMode(uuid)
+            | Request::DisableContinuousMode(uuid)
+            | Request::ReleaseLock(uuid) => Some(**uuid),
+            Request::AcquireLock(lock) => Some(lock.uuid),
+            Request::ReplayControl(replay) => Some(replay.uuid),
+            _ => None,
+        }
+    }
+}
+
+impl DeviceManager {
+    // Requests without a client come from the service itself and are never blocked
+    pub fn check_device_lock(
+        &self,
+        request: &Request,
+        client: Option<&str>,
+    ) -> Result<(), ManagerError> {
+        let (Some(device_id), Some(client)) = (request.controlled_device(), client) else {
+            return Ok(());
+        };
+        let Some(device) = self.device.get(&device_id) else {
+            return Ok(());
+        };
+        match &device.lock {
+            Some(lock) if !lock.is_expired() && lock.owner != client => {
+                Err(ManagerError::DeviceLocked(device_id, lock.info()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn acquire_device_lock(
+        &mut self,
+        request: LockRequest,
+        client: Option<String>,
+    ) -> Result<Answer, ManagerError> {
+        let Some(owner) = client else {
+            return Err(ManagerError::Other(
+                "acquire_device_lock: A client id is needed to lock a device".to_string(),
+            ));
+        };
+        let timeout = request
+            .timeout_s
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_LOCK_TIMEOUT)
+            .min(MAX_LOCK_TIMEOUT);
+
+        let device = self
+            .device
+            .get_mut(&request.uuid)
+            .ok_or(ManagerError::DeviceNotExist(request.uuid))?;
+        if device.lock.as_ref().is_none_or(|lock| lock.owner != owner) {
+            info!("Device {} locked by {owner} for {timeout:?}", request.uuid);
+        }
+        device.lock = Some(DeviceLock {
+            owner,
+            expires_at: Instant::now() + timeout,
+        });
+        Ok(Answer::DeviceInfo(vec![device.info()]))
+    }
+
+    // Also used by admins to take a device back from another client
+    pub fn release_device_lock(&mut self, device_id: Uuid) -> Result<Answer, ManagerError> {
+        let device = self
+            .device
+            .get_mut(&device_id)
+            .ok_or(ManagerError::DeviceNotExist(device_id))?;
+        if let Some(lock) = device.lock.take() {
+            info!("Device {device_id} released by {}", lock.owner);
+        }
+        Ok(Answer::DeviceInfo(vec![device.info()]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{
+        devices::{Ping360Request, PingRequest},
+        manager::{DeviceRequestStruct, ModifyDevice, UuidWrapper},
+    };
+
+    #[test]
+    fn test_controlled_device() {
+        let uuid = Uuid::new_v4();
+        let modify = |modify| Request::ModifyDevice(ModifyDevice { uuid, modify });
+
+        assert_eq!(
+            modify(ModifyDeviceCommand::StopPing360ScanPlan).controlled_device(),
+            Some(uuid)
+        );
+        assert_eq!(
+            modify(ModifyDeviceCommand::GetPing360Config).controlled_device(),
+            None
+        );
+        assert_eq!(
+            Request::Delete(UuidWrapper { uuid }).controlled_device(),
+            Some(uuid)
+        );
+        assert_eq!(
+            Request::Info(UuidWrapper { uuid }).controlled_device(),
+            None
+        );
+        assert_eq!(Request::List.controlled_device(), None);
+
+        let ping = |device_request| {
+            Request::Ping(DeviceRequestStruct {
+                uuid,
+                device_request,
+            })
+        };
+        assert_eq!(
+            ping(PingRequest::Ping360(Ping360Request::MotorOff)).controlled_device(),
+            Some(uuid)
+        );
+        assert_eq!(
+            ping(PingRequest::Ping360(Ping360Request::DeviceData)).controlled_device(),
+            None
+        );
+    }
+}
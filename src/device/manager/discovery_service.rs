@@ -1,21 +1,16 @@
 use std::collections::HashSet;
-use std::net::SocketAddrV4;
 use std::time::Duration;
 
-use bluerobotics_ping::ping1d::Device as Ping1D;
-use bluerobotics_ping::ping360::Device as Ping360;
 use tokio::sync::broadcast;
 use tokio::time::sleep;
-use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
 use tracing::{debug, error, info, trace, warn};
-use udp_stream::UdpStream;
 use uuid::Uuid;
 
-use crate::device::devices::{DeviceActor, DeviceType, PingAnswer, UpgradeResult};
+use crate::device::devices::{DeviceActor, PingAnswer, UpgradeResult};
 use crate::device::manager::ManagerError;
 
 use super::{
-    device_discovery, DeviceInfo, DeviceSelection, DeviceStatus, SourceSelection, SourceType,
+    device_discovery, DeviceInfo, DeviceManager, DeviceSelection, DeviceStatus, SourceSelection,
 };
 
 use std::collections::hash_map::DefaultHasher;
@@ -28,51 +23,7 @@ impl DeviceFactory {
         source: SourceSelection,
         mut device_type: DeviceSelection,
     ) -> Result<DeviceInfo, ManagerError> {
-        let port = match &source {
-            SourceSelection::UdpStream(source_udp_struct) => {
-                let socket_addr = SocketAddrV4::new(source_udp_struct.ip, source_udp_struct.port);
-
-                let udp_stream = UdpStream::connect(socket_addr.into())
-                    .await
-                    .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
-                SourceType::Udp(udp_stream)
-            }
-            SourceSelection::SerialStream(source_serial_struct) => {
-                let mut serial_stream: SerialStream =
-                    tokio_serial::new(&source_serial_struct.path, source_serial_struct.baudrate)
-                        .open_native_async()
-                        .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
-
-                device_discovery::set_baudrate_pre_routine(
-                    &mut serial_stream,
-                    source_serial_struct.baudrate,
-                )
-                .await?;
-
-                serial_stream
-                    .clear(tokio_serial::ClearBuffer::All)
-                    .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
-
-                SourceType::Serial(serial_stream)
-            }
-        };
-
-        let device = match port {
-            SourceType::Udp(udp_port) => match device_type {
-                DeviceSelection::Common | DeviceSelection::Auto => {
-                    DeviceType::Common(bluerobotics_ping::common::Device::new(udp_port))
-                }
-                DeviceSelection::Ping1D => DeviceType::Ping1D(Ping1D::new(udp_port)),
-                DeviceSelection::Ping360 => DeviceType::Ping360(Ping360::new(udp_port)),
-            },
-            SourceType::Serial(serial_port) => match device_type {
-                DeviceSelection::Common | DeviceSelection::Auto => {
-                    DeviceType::Common(bluerobotics_ping::common::Device::new(serial_port))
-                }
-                DeviceSelection::Ping1D => DeviceType::Ping1D(Ping1D::new(serial_port)),
-                DeviceSelection::Ping360 => DeviceType::Ping360(Ping360::new(serial_port)),
-            },
-        };
+        let (device, _replay) = DeviceManager::open_device(&source, &device_type).await?;
 
         let (mut device, _handler) = DeviceActor::new(device, 1);
 
@@ -137,6 +88,7 @@ impl DeviceFactory {
             status: DeviceStatus::Available,
             device_type,
             properties: None,
+            lock: None,
         };
 
         Ok(device)
@@ -268,6 +220,8 @@ fn get_device_key(source: &SourceSelection) -> String {
     match source {
         SourceSelection::SerialStream(serial) => serial.path.clone(),
         SourceSelection::UdpStream(udp) => format!("{}:{}", udp.ip, udp.port),
+        SourceSelection::TcpStream(tcp) => format!("tcp://{}:{}", tcp.ip, tcp.port),
+        SourceSelection::Replay(replay) => replay.path.clone(),
     }
 }
 
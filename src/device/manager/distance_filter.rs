@@ -0,0 +1,306 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, PoisonError, RwLock},
+};
+
+use bluerobotics_ping::ping1d::ProfileStruct;
+use lazy_static::lazy_static;
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast, time::Instant};
+use tracing::error;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+#[serde(default)]
+pub struct DistanceFilterConfig {
+    /// Off by default, only the raw distance is reported until it is enabled
+    pub enabled: bool,
+    /// Number of readings used by the median filter, 0 or 1 disables it
+    pub median_window: u8,
+    pub kalman: bool,
+    /// Kalman process noise, how much the distance is expected to change, in mm²/s
+    pub process_noise: f32,
+    /// Kalman measurement noise, variance of a single reading, in mm²
+    pub measurement_noise: f32,
+    /// Readings with lower confidence are rejected, from 0 to 100
+    pub min_confidence: u16,
+    /// Maximum change of the filtered distance, in millimeters per second, 0 disables it
+    pub max_rate: f32,
+}
+
+impl Default for DistanceFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            median_window: 5,
+            kalman: true,
+            process_noise: 250_000.0,
+            measurement_noise: 10_000.0,
+            min_confidence: 50,
+            max_rate: 2_000.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct FilteredDistance {
+    #[schemars(description = "Filtered distance in millimeters")]
+    pub distance: u32,
+    #[schemars(description = "Distance reported by the device in millimeters")]
+    pub raw_distance: u32,
+    #[schemars(description = "Confidence reported by the device, from 0 to 100")]
+    pub confidence: u16,
+    #[schemars(description = "False when the reading was rejected and the last distance is held")]
+    pub accepted: bool,
+    #[schemars(description = "Ping number of the source profile")]
+    pub ping_number: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilteredDistanceAnswer {
+    #[serde(flatten)]
+    pub filtered_distance: FilteredDistance,
+    pub device_id: Uuid,
+}
+
+// Smoothing state of a single device, restarted every time its configuration changes
+#[derive(Debug)]
+pub struct DistanceFilter {
+    config: Arc<RwLock<DistanceFilterConfig>>,
+    current: DistanceFilterConfig,
+    window: VecDeque<u32>,
+    // Kalman estimate and its variance
+    estimate: Option<(f32, f32)>,
+    output: Option<f32>,
+    last_update: Option<Instant>,
+}
+
+impl DistanceFilter {
+    pub fn new(config: Arc<RwLock<DistanceFilterConfig>>) -> Self {
+        let current = config.read().map(|config| *config).unwrap_or_default();
+        Self {
+            config,
+            current,
+            window: VecDeque::new(),
+            estimate: None,
+            output: None,
+            last_update: None,
+        }
+    }
+
+    fn reset(&mut self, config: DistanceFilterConfig) {
+        self.current = config;
+        self.window.clear();
+        self.estimate = None;
+        self.output = None;
+        self.last_update = None;
+    }
+
+    fn median(&mut self, distance: u32) -> f32 {
+        let size = self.current.median_window.max(1) as usize;
+        self.window.push_back(distance);
+        while self.window.len() > size {
+            self.window.pop_front();
+        }
+
+        let mut sorted: Vec<u32> = self.window.iter().copied().collect();
+        sorted.sort_unstable();
+        sorted[sorted.len() / 2] as f32
+    }
+
+    fn kalman(&mut self, measurement: f32, elapsed: f32) -> f32 {
+        let measurement_noise = self.current.measurement_noise.max(f32::EPSILON);
+        let (estimate, variance) = match self.estimate {
+            None => (measurement, measurement_noise),
+            Some((estimate, variance)) => {
+                let variance = variance + self.current.process_noise.max(0.0) * elapsed;
+                let gain = variance / (variance + measurement_noise);
+                (
+                    estimate + gain * (measurement - estimate),
+                    (1.0 - gain) * variance,
+                )
+            }
+        };
+        self.estimate = Some((estimate, variance));
+        estimate
+    }
+
+    // Run a new profile through the pipeline, returns nothing while disabled or before the first
+    // accepted reading
+    pub fn process(&mut self, profile: &ProfileStruct, now: Instant) -> Option<FilteredDistance> {
+        let config = match self.config.read() {
+            Ok(config) => *config,
+            Err(err) => {
+                error!("Failed to read DistanceFilterConfig: {err:?}");
+                return None;
+            }
+        };
+        if config != self.current {
+            self.reset(config);
+        }
+        if !config.enabled {
+            return None;
+        }
+
+        let accepted = profile.confidence >= config.min_confidence;
+        if accepted {
+            let elapsed = self
+                .last_update
+                .map(|last| now.duration_since(last).as_secs_f32())
+                .unwrap_or_default();
+
+            let mut distance = self.median(profile.distance);
+            if config.kalman {
+                distance = self.kalman(distance, elapsed);
+            }
+            if let Some(previous) = self.output.filter(|_| config.max_rate > 0.0) {
+                let limit = config.max_rate * elapsed;
+                distance = distance.clamp(previous - limit, previous + limit);
+            }
+
+            self.output = Some(distance);
+            self.last_update = Some(now);
+        }
+
+        Some(FilteredDistance {
+            distance: self.output?.max(0.0).round() as u32,
+            raw_distance: profile.distance,
+            confidence: profile.confidence,
+            accepted,
+            ping_number: profile.ping_number,
+        })
+    }
+}
+
+lazy_static! {
+    // Filtered distances are consumed by websockets and recordings, which only know the device id
+    static ref FILTERED_DISTANCES: Mutex<HashMap<Uuid, broadcast::Sender<FilteredDistance>>> =
+        Mutex::new(HashMap::new());
+}
+
+pub fn subscribe(device_id: Uuid) -> broadcast::Receiver<FilteredDistance> {
+    FILTERED_DISTANCES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(device_id)
+        .or_insert_with(|| broadcast::channel(100).0)
+        .subscribe()
+}
+
+pub fn publish(device_id: Uuid, filtered_distance: &FilteredDistance) {
+    if let Ok(senders) = FILTERED_DISTANCES.lock() {
+        if let Some(sender) = senders.get(&device_id) {
+            let _ = sender.send(filtered_distance.clone());
+        }
+    }
+}
+
+pub fn remove(device_id: Uuid) {
+    if let Ok(mut senders) = FILTERED_DISTANCES.lock() {
+        senders.remove(&device_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn profile(distance: u32, confidence: u16) -> ProfileStruct {
+        ProfileStruct {
+            distance,
+            confidence,
+            transmit_duration: 0,
+            ping_number: 0,
+            scan_start: 0,
+            scan_length: 0,
+            gain_setting: 0,
+            profile_data_length: 0,
+            profile_data: vec![],
+        }
+    }
+
+    fn filter(config: DistanceFilterConfig) -> DistanceFilter {
+        DistanceFilter::new(Arc::new(RwLock::new(config)))
+    }
+
+    #[test]
+    fn test_distance_filter_median_and_confidence() {
+        // Disabled filters report nothing
+        assert_eq!(
+            filter(DistanceFilterConfig::default()).process(&profile(1000, 100), Instant::now()),
+            None
+        );
+
+        let mut filter = filter(DistanceFilterConfig {
+            enabled: true,
+            median_window: 3,
+            kalman: false,
+            max_rate: 0.0,
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        // Nothing to report before the first accepted reading
+        assert_eq!(filter.process(&profile(1000, 10), now), None);
+
+        assert_eq!(
+            filter.process(&profile(1000, 100), now).unwrap().distance,
+            1000
+        );
+        assert_eq!(
+            filter.process(&profile(1020, 100), now).unwrap().distance,
+            1020
+        );
+        // A single spike is removed by the median
+        assert_eq!(
+            filter.process(&profile(9000, 100), now).unwrap().distance,
+            1020
+        );
+
+        // Low confidence readings hold the last value
+        let rejected = filter.process(&profile(5000, 10), now).unwrap();
+        assert!(!rejected.accepted);
+        assert_eq!(rejected.distance, 1020);
+        assert_eq!(rejected.raw_distance, 5000);
+    }
+
+    #[test]
+    fn test_distance_filter_rate_limit_and_kalman() {
+        let config = Arc::new(RwLock::new(DistanceFilterConfig {
+            enabled: true,
+            median_window: 1,
+            kalman: false,
+            max_rate: 1000.0,
+            ..Default::default()
+        }));
+        let mut filter = DistanceFilter::new(config.clone());
+        let now = Instant::now();
+
+        filter.process(&profile(1000, 100), now);
+        let limited = filter
+            .process(&profile(5000, 100), now + Duration::from_millis(500))
+            .unwrap();
+        assert_eq!(limited.distance, 1500);
+
+        // Changing the configuration restarts the filter
+        *config.write().unwrap() = DistanceFilterConfig {
+            enabled: true,
+            median_window: 1,
+            max_rate: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            filter.process(&profile(2000, 100), now).unwrap().distance,
+            2000
+        );
+        let smoothed = filter
+            .process(&profile(3000, 100), now + Duration::from_millis(100))
+            .unwrap();
+        assert!(smoothed.distance > 2000 && smoothed.distance < 3000);
+
+        *config.write().unwrap() = DistanceFilterConfig::default();
+        assert_eq!(filter.process(&profile(2000, 100), now), None);
+    }
+}
@@ -4,8 +4,26 @@ pub mod continuous_mode;
 pub mod device_discovery;
 /// Specially for continuous_mode methods, startup, shutdown, handle and errors routines for each device type
 pub mod device_handle;
+/// Specially for DeviceManager, give a single client control of a device until released or expired
+pub mod device_lock;
 /// Specially for DeviceManager, allow discovery service to run on background
 pub mod discovery_service;
+/// Specially for Ping1D, smooth distance readings and reject outliers before broadcasting them
+pub mod distance_filter;
+/// Specially for Ping360, compute the sampling configuration reaching a range in meters
+pub mod ping360_range;
+/// Specially for DeviceManager, bring devices in error state back with exponential backoff
+pub mod reconnect;
+/// Specially for Ping360, named sequences of sectors stepped through while on continuous mode
+pub mod scan_plan;
+/// Specially for DeviceManager, persist created devices and their settings across restarts
+pub mod settings;
+/// Specially for Ping360, keep the latest scan of each device and render it as a Cartesian image
+pub mod sonar_image;
+/// Specially for Ping1D and Ping360, compute the speed of sound from the water properties and apply it
+pub mod speed_of_sound;
+/// Specially for TCP sources, keep the device connection alive reconnecting when it drops
+pub mod tcp_stream;
 
 use paperclip::actix::Apiv2Schema;
 use serde::{Deserialize, Serialize};
@@ -14,10 +32,12 @@ use std::{
     hash::{Hash, Hasher},
     net::{Ipv4Addr, SocketAddrV4, UdpSocket},
     ops::Deref,
+    path::{Path, PathBuf},
     sync::{Arc, RwLock},
     time::Duration,
 };
 use tokio::{
+    io::DuplexStream,
     sync::{broadcast::Receiver, mpsc, oneshot},
     time::sleep,
 };
@@ -26,6 +46,7 @@ use tokio_serial::{SerialPort, SerialPortBuilderExt, SerialStream};
 use tracing::{debug, error, info, trace, warn};
 use udp_stream::UdpStream;
 use uuid::Uuid;
+use validator::{Validate, ValidationError, ValidationErrors, ValidationErrorsKind};
 
 use super::devices::{DeviceActor, DeviceActorHandler, DeviceType, PingAnswer};
 use bluerobotics_ping::{
@@ -44,6 +65,8 @@ pub struct Device {
     pub status: DeviceStatus,
     pub device_type: DeviceSelection,
     pub properties: Option<DeviceProperties>,
+    pub replay: Option<super::replay::ReplayHandler>,
+    pub lock: Option<device_lock::DeviceLock>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -53,16 +76,39 @@ pub enum DeviceProperties {
     Ping360(Ping360Properties),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Apiv2Schema)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Apiv2Schema, Validate)]
 pub struct Ping360Config {
+    #[validate(range(max = 1))]
     pub mode: u8,
+    #[validate(range(max = 2))]
     pub gain_setting: u8,
+    /// Transmit duration in microseconds
+    #[validate(range(
+        min = ping360_range::MIN_TRANSMIT_DURATION,
+        max = ping360_range::MAX_TRANSMIT_DURATION
+    ))]
     pub transmit_duration: u16,
+    /// Time between samples, in 25 ns ticks
+    #[validate(range(min = ping360_range::MIN_SAMPLE_PERIOD))]
     pub sample_period: u16,
+    /// Transmit frequency in kHz
+    #[validate(range(
+        min = ping360_range::MIN_TRANSMIT_FREQUENCY,
+        max = ping360_range::MAX_TRANSMIT_FREQUENCY
+    ))]
     pub transmit_frequency: u16,
+    #[validate(range(
+        min = ping360_range::MIN_NUMBER_OF_SAMPLES,
+        max = ping360_range::MAX_NUMBER_OF_SAMPLES
+    ))]
     pub number_of_samples: u16,
+    /// Angles in gradians, from 0 to 399
+    #[validate(range(max = 399))]
     pub start_angle: u16,
+    #[validate(range(max = 399))]
     pub stop_angle: u16,
+    /// Gradians between consecutive lines, 0 would never move the head
+    #[validate(range(min = 1))]
     pub num_steps: u8,
     pub delay: u8,
 }
@@ -76,12 +122,17 @@ pub struct CommonProperties {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Ping1DProperties {
     pub common: CommonProperties,
+    pub distance_filter: Arc<RwLock<distance_filter::DistanceFilterConfig>>,
+    pub rangefinder: Arc<RwLock<crate::device::rangefinder::RangefinderConfig>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Ping360Properties {
     pub common: CommonProperties,
     pub continuous_mode_settings: Arc<RwLock<Ping360Config>>,
+    pub scan_plans: Arc<RwLock<scan_plan::ScanPlans>>,
+    /// Speed of sound used to convert the sampling configuration to a range, in meters per second
+    pub speed_of_sound: Arc<RwLock<f32>>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -91,6 +142,9 @@ pub struct DeviceInfo {
     pub status: DeviceStatus,
     pub device_type: DeviceSelection,
     pub properties: Option<DeviceProperties>,
+    /// Client currently controlling the device
+    #[serde(default)]
+    pub lock: Option<device_lock::DeviceLockInfo>,
 }
 impl Device {
     pub fn info(&self) -> DeviceInfo {
@@ -100,6 +154,11 @@ impl Device {
             status: self.status.clone(),
             device_type: self.device_type.clone(),
             properties: self.properties.clone(),
+            lock: self
+                .lock
+                .as_ref()
+                .filter(|lock| !lock.is_expired())
+                .map(|lock| lock.info()),
         }
     }
 }
@@ -120,7 +179,7 @@ impl Drop for Device {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Apiv2Schema, schemars::JsonSchema)]
 pub enum DeviceSelection {
     Common,
     Ping1D,
@@ -132,11 +191,15 @@ pub enum DeviceSelection {
 pub enum SourceSelection {
     UdpStream(SourceUdpStruct),
     SerialStream(SourceSerialStruct),
+    TcpStream(SourceTcpStruct),
+    Replay(SourceReplayStruct),
 }
 
 enum SourceType {
     Udp(UdpStream),
     Serial(SerialStream),
+    Tcp(DuplexStream),
+    Replay(DuplexStream),
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize, Hash, Apiv2Schema, PartialEq)]
@@ -151,7 +214,30 @@ pub struct SourceSerialStruct {
     pub baudrate: u32,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, Hash, Apiv2Schema, PartialEq)]
+pub struct SourceTcpStruct {
+    pub ip: Ipv4Addr,
+    pub port: u16,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, Apiv2Schema, PartialEq)]
+pub struct SourceReplayStruct {
+    pub path: String,
+    pub speed: f32,
+    #[serde(rename = "loop")]
+    pub looping: bool,
+}
+
+// f32 doesn't implement Hash, use its bit representation to keep the device id stable
+impl Hash for SourceReplayStruct {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.path.hash(state);
+        self.speed.to_bits().hash(state);
+        self.looping.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
 pub enum DeviceStatus {
     Available,
     Running,
@@ -164,11 +250,22 @@ pub struct DeviceManager {
     pub device: HashMap<Uuid, Device>,
     discovery_service: DiscoveryComponent,
     pub manager_handler: ManagerActorHandler,
+    settings_path: Option<PathBuf>,
+    pending_settings: Vec<settings::DeviceSettings>,
+    reconnect: HashMap<Uuid, reconnect::ReconnectState>,
+    reconnect_sender: mpsc::Sender<reconnect::ReconnectAttempt>,
+    reconnect_receiver: mpsc::Receiver<reconnect::ReconnectAttempt>,
+    scan_plan_runners: HashMap<Uuid, scan_plan::ScanPlanRunner>,
+    speed_of_sound: speed_of_sound::SpeedOfSoundService,
+    speed_of_sound_sender: mpsc::Sender<speed_of_sound::SpeedOfSoundUpdate>,
+    speed_of_sound_receiver: mpsc::Receiver<speed_of_sound::SpeedOfSoundUpdate>,
 }
 
 #[derive(Debug)]
 pub struct ManagerActorRequest {
     pub request: Request,
+    /// Client sending the request, empty for requests from the service itself
+    pub client: Option<String>,
     pub respond_to: oneshot::Sender<Result<Answer, ManagerError>>,
 }
 #[derive(Clone)]
@@ -183,6 +280,11 @@ pub enum Answer {
     InnerDeviceHandler(DeviceActorHandler),
     DeviceInfo(Vec<DeviceInfo>),
     DeviceConfig(ModifyDeviceResult),
+    ReplayStatus(super::replay::ReplayStatus),
+    DeviceStatusTransition(reconnect::DeviceStatusTransition),
+    SonarImageTile(sonar_image::SonarImageTile),
+    FilteredDistance(distance_filter::FilteredDistanceAnswer),
+    SpeedOfSound(speed_of_sound::SpeedOfSoundStatus),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -195,9 +297,76 @@ pub enum ManagerError {
     NoDevices,
     TokioMpsc(String),
     NotImplemented(Request),
+    /// Device controlled by another client
+    DeviceLocked(Uuid, device_lock::DeviceLockInfo),
+    /// Configuration rejected before reaching the device, with every offending field
+    InvalidConfig(Vec<InvalidField>),
     Other(String),
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct InvalidField {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl InvalidField {
+    fn new(field: &str, error: &ValidationError) -> Self {
+        let message = match &error.message {
+            Some(message) => message.to_string(),
+            None => {
+                let param = |name: &str| error.params.get(name).map(|value| value.to_string());
+                let value = param("value").unwrap_or_default();
+                match (param("min"), param("max")) {
+                    (Some(min), Some(max)) => {
+                        format!("{field} is {value}, expected between {min} and {max}")
+                    }
+                    (Some(min), None) => format!("{field} is {value}, expected at least {min}"),
+                    (None, Some(max)) => format!("{field} is {value}, expected at most {max}"),
+                    (None, None) => format!("{field} is invalid: {}", error.code),
+                }
+            }
+        };
+        Self {
+            field: field.to_string(),
+            code: error.code.to_string(),
+            message,
+        }
+    }
+}
+
+// Errors of nested structures are named after their path, like `sectors[1].stop_angle`
+fn collect_invalid_fields(prefix: &str, errors: &ValidationErrors, fields: &mut Vec<InvalidField>) {
+    for (field, kind) in errors.errors() {
+        let field = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{prefix}.{field}")
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                fields.extend(errors.iter().map(|error| InvalidField::new(&field, error)))
+            }
+            ValidationErrorsKind::Struct(errors) => collect_invalid_fields(&field, errors, fields),
+            ValidationErrorsKind::List(list) => {
+                for (index, errors) in list {
+                    collect_invalid_fields(&format!("{field}[{index}]"), errors, fields);
+                }
+            }
+        }
+    }
+}
+
+impl From<ValidationErrors> for ManagerError {
+    fn from(errors: ValidationErrors) -> Self {
+        let mut fields = Vec::new();
+        collect_invalid_fields("", &errors, &mut fields);
+        fields.sort_by(|a, b| a.field.cmp(&b.field));
+        ManagerError::InvalidConfig(fields)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DeviceAnswer {
     #[serde(flatten)]
@@ -219,6 +388,14 @@ pub enum Request {
     ModifyDevice(ModifyDevice),
     EnableContinuousMode(UuidWrapper),
     DisableContinuousMode(UuidWrapper),
+    ReplayControl(ReplayControl),
+    GetSpeedOfSound,
+    SetSpeedOfSoundConfig(speed_of_sound::SpeedOfSoundConfig),
+    /// Take control of a device, other clients can't change it until released or expired
+    AcquireLock(device_lock::LockRequest),
+    ReleaseLock(UuidWrapper),
+    /// Release a device locked by any client
+    ForceReleaseLock(UuidWrapper),
     #[serde(skip)]
     SpecialTurnOffContinuousMode(UuidWrapper),
 }
@@ -228,12 +405,28 @@ pub enum ModifyDeviceCommand {
     SetIp(Ipv4Addr),
     SetPing360Config(Ping360Config),
     GetPing360Config,
+    /// Set the sampling configuration from a range in meters
+    SetPing360Range(ping360_range::Ping360Range),
+    SetPing1DDistanceFilter(distance_filter::DistanceFilterConfig),
+    GetPing1DDistanceFilter,
+    SetPing1DRangefinder(crate::device::rangefinder::RangefinderConfig),
+    GetPing1DRangefinder,
+    /// Add a scan plan, or replace the one with the same name
+    SetPing360ScanPlan(scan_plan::ScanPlan),
+    DeletePing360ScanPlan(String),
+    GetPing360ScanPlans,
+    /// Run a scan plan by name, enabling continuous mode when needed
+    StartPing360ScanPlan(String),
+    StopPing360ScanPlan,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ModifyDeviceResult {
     ConfigAcknowledge(ModifyDevice),
-    Ping360Config(Ping360Config),
+    Ping360Config(ping360_range::Ping360ConfigInfo),
+    Ping1DDistanceFilter(distance_filter::DistanceFilterConfig),
+    Ping1DRangefinder(crate::device::rangefinder::RangefinderConfig),
+    Ping360ScanPlans(scan_plan::ScanPlans),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
@@ -242,6 +435,12 @@ pub struct ModifyDevice {
     pub modify: ModifyDeviceCommand,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct ReplayControl {
+    pub uuid: Uuid,
+    pub command: super::replay::ReplayCommand,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
 pub struct UuidWrapper {
     pub uuid: Uuid,
@@ -269,6 +468,27 @@ pub struct DeviceRequestStruct {
 impl DeviceManager {
     async fn handle_message(&mut self, actor_request: ManagerActorRequest) {
         trace!("DeviceManager: Received a request, details: {actor_request:?}");
+
+        let should_save_settings = matches!(
+            actor_request.request,
+            Request::AutoCreate
+                | Request::Create(_)
+                | Request::Delete(_)
+                | Request::EnableContinuousMode(_)
+                | Request::DisableContinuousMode(_)
+                | Request::ModifyDevice(_)
+                | Request::SetSpeedOfSoundConfig(_)
+        );
+
+        if let Err(err) =
+            self.check_device_lock(&actor_request.request, actor_request.client.as_deref())
+        {
+            if let Err(e) = actor_request.respond_to.send(Err(err)) {
+                error!("DeviceManager: Failed to return DeviceLocked response: {e:?}");
+            }
+            return;
+        }
+
         match actor_request.request {
             Request::AutoCreate => {
                 let result = self.auto_create().await;
@@ -324,12 +544,51 @@ impl DeviceManager {
                     error!("DeviceManager: Failed to return GetDeviceHandler response: {e:?}");
                 }
             }
+            // Only the lock is checked here, the request is sent by the handler to the device
+            Request::Ping(request) => {
+                let answer = self.get_device_handler(request.uuid).await;
+                if let Err(e) = actor_request.respond_to.send(answer) {
+                    error!("DeviceManager: Failed to return Ping device handler: {e:?}");
+                }
+            }
             Request::ModifyDevice(request) => {
                 let answer = self.modify_device(request).await;
                 if let Err(err) = actor_request.respond_to.send(answer) {
                     error!("DeviceManager: Failed to return ModifyDevice response: {err:?}");
                 }
             }
+            Request::ReplayControl(request) => {
+                let answer = self.replay_control(request).await;
+                if let Err(err) = actor_request.respond_to.send(answer) {
+                    error!("DeviceManager: Failed to return ReplayControl response: {err:?}");
+                }
+            }
+            Request::AcquireLock(request) => {
+                let answer = self.acquire_device_lock(request, actor_request.client);
+                if let Err(err) = actor_request.respond_to.send(answer) {
+                    error!("DeviceManager: Failed to return AcquireLock response: {err:?}");
+                }
+            }
+            Request::ReleaseLock(uuid) | Request::ForceReleaseLock(uuid) => {
+                let answer = self.release_device_lock(*uuid);
+                if let Err(err) = actor_request.respond_to.send(answer) {
+                    error!("DeviceManager: Failed to return ReleaseLock response: {err:?}");
+                }
+            }
+            Request::GetSpeedOfSound => {
+                let answer = self.get_speed_of_sound();
+                if let Err(err) = actor_request.respond_to.send(answer) {
+                    error!("DeviceManager: Failed to return GetSpeedOfSound response: {err:?}");
+                }
+            }
+            Request::SetSpeedOfSoundConfig(config) => {
+                let answer = self.set_speed_of_sound_config(config).await;
+                if let Err(err) = actor_request.respond_to.send(answer) {
+                    error!(
+                        "DeviceManager: Failed to return SetSpeedOfSoundConfig response: {err:?}"
+                    );
+                }
+            }
             _ => {
                 if let Err(e) = actor_request
                     .respond_to
@@ -339,10 +598,16 @@ impl DeviceManager {
                 }
             }
         }
+
+        if should_save_settings {
+            self.save_settings();
+        }
     }
 
     pub fn new(size: usize) -> (Self, ManagerActorHandler) {
         let (sender, receiver) = mpsc::channel(size);
+        let (reconnect_sender, reconnect_receiver) = mpsc::channel(size);
+        let (speed_of_sound_sender, speed_of_sound_receiver) = mpsc::channel(size);
 
         let actor_handler = ManagerActorHandler { sender };
         let actor = DeviceManager {
@@ -350,12 +615,30 @@ impl DeviceManager {
             device: HashMap::new(),
             discovery_service: DiscoveryComponent::new(),
             manager_handler: actor_handler.clone(),
+            settings_path: None,
+            pending_settings: Vec::new(),
+            reconnect: HashMap::new(),
+            reconnect_sender,
+            reconnect_receiver,
+            scan_plan_runners: HashMap::new(),
+            speed_of_sound: speed_of_sound::SpeedOfSoundService::default(),
+            speed_of_sound_sender,
+            speed_of_sound_receiver,
         };
 
         trace!("DeviceManager and handler successfully created: Success");
         (actor, actor_handler)
     }
 
+    pub fn new_with_settings(
+        size: usize,
+        settings_path: impl AsRef<Path>,
+    ) -> (Self, ManagerActorHandler) {
+        let (mut actor, actor_handler) = Self::new(size);
+        actor.settings_path = Some(settings_path.as_ref().to_path_buf());
+        (actor, actor_handler)
+    }
+
     pub fn get_device_manager_handler(&self) -> ManagerActorHandler {
         self.manager_handler.clone()
     }
@@ -372,6 +655,9 @@ impl DeviceManager {
         let mut discovery_rx = self.discovery_service.get_discovery_rx();
 
         let mut status_check_interval = tokio::time::interval(std::time::Duration::from_secs(30));
+        let mut reconnect_interval = tokio::time::interval(std::time::Duration::from_secs(1));
+        let mut speed_of_sound_interval =
+            tokio::time::interval(speed_of_sound::SPEED_OF_SOUND_UPDATE_INTERVAL);
 
         loop {
             tokio::select! {
@@ -392,6 +678,18 @@ impl DeviceManager {
                     debug!("Running scheduled device status check");
                     self.update_devices_status().await;
                 }
+                _ = reconnect_interval.tick() => {
+                    self.reconnect_devices();
+                }
+                Some(attempt) = self.reconnect_receiver.recv() => {
+                    self.finish_reconnection(attempt).await;
+                }
+                _ = speed_of_sound_interval.tick() => {
+                    self.update_speed_of_sound().await;
+                }
+                Some(update) = self.speed_of_sound_receiver.recv() => {
+                    self.finish_speed_of_sound(update);
+                }
                 else => break,
             }
         }
@@ -415,7 +713,12 @@ impl DeviceManager {
             let receiver = match self.get_subscriber(device.id).await {
                 Ok(receiver) => receiver,
                 Err(err) => {
-                    error!("Device connection error, cant take subscriber. Device id: {:?}, Error: {err:?}", device.id);
+                    error!("Device connection error, cant take subscriber, marking device with error. Device id: {:?}, Error: {err:?}", device.id);
+                    if let Some(device_entry) = self.device.get_mut(&device.id) {
+                        let previous_status =
+                            std::mem::replace(&mut device_entry.status, DeviceStatus::Error);
+                        self.schedule_reconnection(device.id, previous_status);
+                    }
                     continue;
                 }
             };
@@ -427,6 +730,7 @@ impl DeviceManager {
                     continue;
                 }
             };
+            let previous_status = device_entry.status.clone();
 
             if let Some(handle) = &device_entry.actor {
                 if handle.is_finished() {
@@ -435,6 +739,7 @@ impl DeviceManager {
                         device.id
                     );
                     device_entry.status = DeviceStatus::Error;
+                    self.schedule_reconnection(device.id, previous_status);
                     continue;
                 }
             }
@@ -452,6 +757,10 @@ impl DeviceManager {
                     continue;
                 }
             }
+
+            if device_entry.status == DeviceStatus::Error {
+                self.schedule_reconnection(device.id, previous_status);
+            }
         }
     }
 
@@ -552,68 +861,7 @@ impl DeviceManager {
             return Err(ManagerError::DeviceAlreadyExist(hash));
         }
 
-        let port = match &source {
-            SourceSelection::UdpStream(source_udp_struct) => {
-                let socket_addr = SocketAddrV4::new(source_udp_struct.ip, source_udp_struct.port);
-
-                let udp_stream = UdpStream::connect(socket_addr.into())
-                    .await
-                    .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
-                SourceType::Udp(udp_stream)
-            }
-            SourceSelection::SerialStream(source_serial_struct) => {
-                let mut serial_stream: SerialStream =
-                    tokio_serial::new(&source_serial_struct.path, source_serial_struct.baudrate)
-                        .open_native_async()
-                        .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
-
-                device_discovery::set_baudrate_pre_routine(
-                    &mut serial_stream,
-                    source_serial_struct.baudrate,
-                )
-                .await?;
-
-                serial_stream
-                    .clear(tokio_serial::ClearBuffer::All)
-                    .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
-
-                #[cfg(unix)]
-                serial_stream
-                    .set_exclusive(true)
-                    .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
-
-                SourceType::Serial(serial_stream)
-            }
-        };
-
-        let device = match port {
-            SourceType::Udp(udp_port) => match device_selection {
-                DeviceSelection::Common | DeviceSelection::Auto => {
-                    crate::device::devices::DeviceType::Common(
-                        bluerobotics_ping::common::Device::new(udp_port),
-                    )
-                }
-                DeviceSelection::Ping1D => {
-                    crate::device::devices::DeviceType::Ping1D(Ping1D::new(udp_port))
-                }
-                DeviceSelection::Ping360 => {
-                    crate::device::devices::DeviceType::Ping360(Ping360::new(udp_port))
-                }
-            },
-            SourceType::Serial(serial_port) => match device_selection {
-                DeviceSelection::Common | DeviceSelection::Auto => {
-                    crate::device::devices::DeviceType::Common(
-                        bluerobotics_ping::common::Device::new(serial_port),
-                    )
-                }
-                DeviceSelection::Ping1D => {
-                    crate::device::devices::DeviceType::Ping1D(Ping1D::new(serial_port))
-                }
-                DeviceSelection::Ping360 => {
-                    crate::device::devices::DeviceType::Ping360(Ping360::new(serial_port))
-                }
-            },
-        };
+        let (device, replay) = Self::open_device(&source, &device_selection).await?;
 
         let (mut device, handler) = super::devices::DeviceActor::new(device, 10);
 
@@ -672,6 +920,8 @@ impl DeviceManager {
             broadcast: None,
             device_type: device_selection,
             properties: None,
+            replay,
+            lock: None,
         };
 
         self.device.insert(hash, device);
@@ -749,7 +999,44 @@ impl DeviceManager {
         source: SourceSelection,
         device_type: DeviceSelection,
     ) -> Result<DeviceInfo, ManagerError> {
-        let port = match &source {
+        let (device_type_inner, replay) = Self::open_device(&source, &device_type).await?;
+
+        let (device_actor, handler) = super::devices::DeviceActor::new(device_type_inner, 10);
+        let actor = tokio::spawn(async move { device_actor.run().await });
+
+        self.install_device(device_id, handler, actor, replay)?;
+
+        match self.continuous_mode(device_id).await {
+            Ok(_) => {
+                trace!(
+                    "Successfully enabled continuous mode for device {}",
+                    device_id
+                );
+            }
+            Err(err) => {
+                error!(
+                    "Failed to enable continuous mode for device {}: {:?}",
+                    device_id, err
+                );
+            }
+        }
+
+        match self.get_device(device_id) {
+            Ok(device) => Ok(device.info()),
+            Err(err) => {
+                error!("Failed to get device info for {}: {:?}", device_id, err);
+                Err(err)
+            }
+        }
+    }
+
+    // Open the source of a device and wrap it with its driver, without touching the manager
+    async fn open_device(
+        source: &SourceSelection,
+        device_type: &DeviceSelection,
+    ) -> Result<(DeviceType, Option<super::replay::ReplayHandler>), ManagerError> {
+        let mut replay = None;
+        let port = match source {
             SourceSelection::UdpStream(source_udp_struct) => {
                 let socket_addr = SocketAddrV4::new(source_udp_struct.ip, source_udp_struct.port);
 
@@ -774,8 +1061,23 @@ impl DeviceManager {
                     .clear(tokio_serial::ClearBuffer::All)
                     .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
 
+                #[cfg(unix)]
+                serial_stream
+                    .set_exclusive(true)
+                    .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
+
                 SourceType::Serial(serial_stream)
             }
+            SourceSelection::TcpStream(source_tcp_struct) => {
+                let tcp_stream = tcp_stream::connect(source_tcp_struct).await?;
+                SourceType::Tcp(tcp_stream)
+            }
+            SourceSelection::Replay(source_replay_struct) => {
+                let (replay_stream, replay_handler) =
+                    super::replay::open(source_replay_struct).await?;
+                replay = Some(replay_handler);
+                SourceType::Replay(replay_stream)
+            }
         };
 
         let device_type_inner = match port {
@@ -793,40 +1095,40 @@ impl DeviceManager {
                 DeviceSelection::Ping1D => DeviceType::Ping1D(Ping1D::new(serial_port)),
                 DeviceSelection::Ping360 => DeviceType::Ping360(Ping360::new(serial_port)),
             },
+            SourceType::Tcp(tcp_port) => match device_type {
+                DeviceSelection::Common | DeviceSelection::Auto => {
+                    DeviceType::Common(bluerobotics_ping::common::Device::new(tcp_port))
+                }
+                DeviceSelection::Ping1D => DeviceType::Ping1D(Ping1D::new(tcp_port)),
+                DeviceSelection::Ping360 => DeviceType::Ping360(Ping360::new(tcp_port)),
+            },
+            SourceType::Replay(replay_port) => match device_type {
+                DeviceSelection::Common | DeviceSelection::Auto => {
+                    DeviceType::Common(bluerobotics_ping::common::Device::new(replay_port))
+                }
+                DeviceSelection::Ping1D => DeviceType::Ping1D(Ping1D::new(replay_port)),
+                DeviceSelection::Ping360 => DeviceType::Ping360(Ping360::new(replay_port)),
+            },
         };
 
-        let (device_actor, handler) = super::devices::DeviceActor::new(device_type_inner, 10);
-        let actor = tokio::spawn(async move { device_actor.run().await });
+        Ok((device_type_inner, replay))
+    }
 
+    fn install_device(
+        &mut self,
+        device_id: Uuid,
+        handler: DeviceActorHandler,
+        actor: tokio::task::JoinHandle<DeviceActor>,
+        replay: Option<super::replay::ReplayHandler>,
+    ) -> Result<(), ManagerError> {
         if let Some(device) = self.device.get_mut(&device_id) {
-            device.handler = Some(handler.clone());
+            device.handler = Some(handler);
             device.actor = Some(actor);
+            device.replay = replay;
             device.status = DeviceStatus::Running;
+            Ok(())
         } else {
-            return Err(ManagerError::DeviceNotExist(device_id));
-        }
-
-        match self.continuous_mode(device_id).await {
-            Ok(_) => {
-                trace!(
-                    "Successfully enabled continuous mode for device {}",
-                    device_id
-                );
-            }
-            Err(err) => {
-                error!(
-                    "Failed to enable continuous mode for device {}: {:?}",
-                    device_id, err
-                );
-            }
-        }
-
-        match self.get_device(device_id) {
-            Ok(device) => Ok(device.info()),
-            Err(err) => {
-                error!("Failed to get device info for {}: {:?}", device_id, err);
-                Err(err)
-            }
+            Err(ManagerError::DeviceNotExist(device_id))
         }
     }
 
@@ -866,6 +1168,8 @@ impl DeviceManager {
             broadcast: None,
             device_type: device_info.device_type,
             properties: device_info.properties,
+            replay: None,
+            lock: None,
         };
 
         let info = device.info();
@@ -899,6 +1203,12 @@ impl DeviceManager {
     }
 
     pub async fn delete(&mut self, id: Uuid) -> Result<Answer, ManagerError> {
+        self.forget_settings(id);
+        self.forget_reconnection(id);
+        self.forget_scan_plan(id);
+        sonar_image::remove(id);
+        distance_filter::remove(id);
+
         let device = self
             .device
             .remove(&id)
@@ -1052,8 +1362,24 @@ impl DeviceManager {
                 device.properties = Some(DeviceProperties::Common(common_properties))
             }
             DeviceSelection::Ping1D => {
+                // Keep the filter and rangefinder settings across properties updates, like reconnections
+                let (distance_filter, rangefinder) = match &device.properties {
+                    Some(DeviceProperties::Ping1D(properties)) => (
+                        properties.distance_filter.clone(),
+                        properties.rangefinder.clone(),
+                    ),
+                    _ => (
+                        Arc::new(RwLock::new(distance_filter::DistanceFilterConfig::default())),
+                        Arc::new(RwLock::new(
+                            crate::device::rangefinder::RangefinderConfig::default(),
+                        )),
+                    ),
+                };
+
                 let ping_1d_properties = Ping1DProperties {
                     common: common_properties,
+                    distance_filter,
+                    rangefinder,
                 };
 
                 device.properties = Some(DeviceProperties::Ping1D(ping_1d_properties))
@@ -1091,9 +1417,23 @@ impl DeviceManager {
                     delay: 0,
                 };
 
+                // Scan plans and speed of sound are kept across properties updates, like reconnections
+                let (scan_plans, speed_of_sound) = match &device.properties {
+                    Some(DeviceProperties::Ping360(properties)) => (
+                        properties.scan_plans.clone(),
+                        properties.speed_of_sound.clone(),
+                    ),
+                    _ => (
+                        Arc::new(RwLock::new(scan_plan::ScanPlans::default())),
+                        Arc::new(RwLock::new(ping360_range::DEFAULT_SPEED_OF_SOUND)),
+                    ),
+                };
+
                 let ping_360_properties = Ping360Properties {
                     common: common_properties,
                     continuous_mode_settings: Arc::new(RwLock::new(auto_transmit)),
+                    scan_plans,
+                    speed_of_sound,
                 };
 
                 device.properties = Some(DeviceProperties::Ping360(ping_360_properties))
@@ -1121,6 +1461,16 @@ impl DeviceManager {
         &self,
         device_id: Uuid,
         new_config: Ping360Config,
+    ) -> Result<(), ManagerError> {
+        new_config.validate()?;
+        self.write_ping360_config(device_id, new_config)
+    }
+
+    // Without validation, for configurations that were already running on the device
+    fn write_ping360_config(
+        &self,
+        device_id: Uuid,
+        new_config: Ping360Config,
     ) -> Result<(), ManagerError> {
         let device = self.get_device(device_id)?;
         if let Some(DeviceProperties::Ping360(properties)) = &device.properties {
@@ -1139,10 +1489,19 @@ impl DeviceManager {
     pub async fn get_ping360_config(&self, device_id: Uuid) -> Result<Answer, ManagerError> {
         let device = self.get_device(device_id)?;
         if let Some(DeviceProperties::Ping360(properties)) = &device.properties {
+            let read_error = |err: String| {
+                ManagerError::Other(format!("get_ping360_config: {err}, device: {device_id}"))
+            };
+            let config = *properties
+                .continuous_mode_settings
+                .read()
+                .map_err(|err| read_error(err.to_string()))?;
+            let speed_of_sound = *properties
+                .speed_of_sound
+                .read()
+                .map_err(|err| read_error(err.to_string()))?;
             return Ok(Answer::DeviceConfig(ModifyDeviceResult::Ping360Config(
-                *properties.continuous_mode_settings.read().map_err(|err| {
-                    ManagerError::Other(format!("get_ping360_config: {err}, device: {device_id}"))
-                })?,
+                ping360_range::Ping360ConfigInfo::new(config, speed_of_sound),
             )));
         }
         Err(ManagerError::DeviceSourceError(
@@ -1150,6 +1509,86 @@ impl DeviceManager {
         ))
     }
 
+    pub fn update_ping1d_distance_filter(
+        &self,
+        device_id: Uuid,
+        new_config: distance_filter::DistanceFilterConfig,
+    ) -> Result<(), ManagerError> {
+        let device = self.get_device(device_id)?;
+        if let Some(DeviceProperties::Ping1D(properties)) = &device.properties {
+            let mut config = properties
+                .distance_filter
+                .write()
+                .map_err(|err| ManagerError::Other(err.to_string()))?;
+            *config = new_config;
+            return Ok(());
+        }
+        Err(ManagerError::DeviceSourceError(
+            "update_ping1d_distance_filter: Can't set DistanceFilterConfig".to_string(),
+        ))
+    }
+
+    pub fn get_ping1d_distance_filter(
+        &self,
+        device_id: Uuid,
+    ) -> Result<distance_filter::DistanceFilterConfig, ManagerError> {
+        let device = self.get_device(device_id)?;
+        if let Some(DeviceProperties::Ping1D(properties)) = &device.properties {
+            return properties
+                .distance_filter
+                .read()
+                .map(|config| *config)
+                .map_err(|err| {
+                    ManagerError::Other(format!(
+                        "get_ping1d_distance_filter: {err}, device: {device_id}"
+                    ))
+                });
+        }
+        Err(ManagerError::DeviceSourceError(
+            "get_ping1d_distance_filter: Can't return DistanceFilterConfig".to_string(),
+        ))
+    }
+
+    pub fn update_ping1d_rangefinder(
+        &self,
+        device_id: Uuid,
+        new_config: crate::device::rangefinder::RangefinderConfig,
+    ) -> Result<(), ManagerError> {
+        let device = self.get_device(device_id)?;
+        if let Some(DeviceProperties::Ping1D(properties)) = &device.properties {
+            let mut config = properties
+                .rangefinder
+                .write()
+                .map_err(|err| ManagerError::Other(err.to_string()))?;
+            *config = new_config;
+            return Ok(());
+        }
+        Err(ManagerError::DeviceSourceError(
+            "update_ping1d_rangefinder: Can't set RangefinderConfig".to_string(),
+        ))
+    }
+
+    pub fn get_ping1d_rangefinder(
+        &self,
+        device_id: Uuid,
+    ) -> Result<crate::device::rangefinder::RangefinderConfig, ManagerError> {
+        let device = self.get_device(device_id)?;
+        if let Some(DeviceProperties::Ping1D(properties)) = &device.properties {
+            return properties
+                .rangefinder
+                .read()
+                .map(|config| *config)
+                .map_err(|err| {
+                    ManagerError::Other(format!(
+                        "get_ping1d_rangefinder: {err}, device: {device_id}"
+                    ))
+                });
+        }
+        Err(ManagerError::DeviceSourceError(
+            "get_ping1d_rangefinder: Can't return RangefinderConfig".to_string(),
+        ))
+    }
+
     pub async fn modify_device(&mut self, request: ModifyDevice) -> Result<Answer, ManagerError> {
         match request.modify {
             ModifyDeviceCommand::SetIp(ip) => {
@@ -1175,15 +1614,85 @@ impl DeviceManager {
                 )))
             }
             ModifyDeviceCommand::SetPing360Config(config) => {
+                // A configuration set by the user takes over any running scan plan
+                self.stop_ping360_scan_plan(request.uuid).await?;
                 self.update_ping360_config(request.uuid, config).await?;
                 Ok(Answer::DeviceConfig(ModifyDeviceResult::ConfigAcknowledge(
                     request,
                 )))
             }
             ModifyDeviceCommand::GetPing360Config => self.get_ping360_config(request.uuid).await,
+            ModifyDeviceCommand::SetPing360Range(range) => {
+                Ok(Answer::DeviceConfig(ModifyDeviceResult::Ping360Config(
+                    self.set_ping360_range(request.uuid, range).await?,
+                )))
+            }
+            ModifyDeviceCommand::SetPing1DDistanceFilter(config) => {
+                self.update_ping1d_distance_filter(request.uuid, config)?;
+                Ok(Answer::DeviceConfig(ModifyDeviceResult::ConfigAcknowledge(
+                    request,
+                )))
+            }
+            ModifyDeviceCommand::GetPing1DDistanceFilter => Ok(Answer::DeviceConfig(
+                ModifyDeviceResult::Ping1DDistanceFilter(
+                    self.get_ping1d_distance_filter(request.uuid)?,
+                ),
+            )),
+            ModifyDeviceCommand::SetPing1DRangefinder(config) => {
+                self.update_ping1d_rangefinder(request.uuid, config)?;
+                Ok(Answer::DeviceConfig(ModifyDeviceResult::ConfigAcknowledge(
+                    request,
+                )))
+            }
+            ModifyDeviceCommand::GetPing1DRangefinder => Ok(Answer::DeviceConfig(
+                ModifyDeviceResult::Ping1DRangefinder(self.get_ping1d_rangefinder(request.uuid)?),
+            )),
+            ModifyDeviceCommand::SetPing360ScanPlan(ref plan) => {
+                self.set_ping360_scan_plan(request.uuid, plan.clone())
+                    .await?;
+                Ok(Answer::DeviceConfig(ModifyDeviceResult::ConfigAcknowledge(
+                    request,
+                )))
+            }
+            ModifyDeviceCommand::DeletePing360ScanPlan(ref name) => {
+                self.delete_ping360_scan_plan(request.uuid, name.clone())
+                    .await?;
+                Ok(Answer::DeviceConfig(ModifyDeviceResult::ConfigAcknowledge(
+                    request,
+                )))
+            }
+            ModifyDeviceCommand::GetPing360ScanPlans => Ok(Answer::DeviceConfig(
+                ModifyDeviceResult::Ping360ScanPlans(self.get_ping360_scan_plans(request.uuid)?),
+            )),
+            ModifyDeviceCommand::StartPing360ScanPlan(ref name) => {
+                self.start_ping360_scan_plan(request.uuid, name.clone())
+                    .await?;
+                Ok(Answer::DeviceConfig(ModifyDeviceResult::ConfigAcknowledge(
+                    request,
+                )))
+            }
+            ModifyDeviceCommand::StopPing360ScanPlan => {
+                self.stop_ping360_scan_plan(request.uuid).await?;
+                Ok(Answer::DeviceConfig(ModifyDeviceResult::ConfigAcknowledge(
+                    request,
+                )))
+            }
         }
     }
 
+    pub async fn replay_control(&self, request: ReplayControl) -> Result<Answer, ManagerError> {
+        let device = self.get_device(request.uuid)?;
+        let Some(replay) = &device.replay else {
+            return Err(ManagerError::Other(format!(
+                "replay_control : device {} is not a replay source",
+                request.uuid
+            )));
+        };
+
+        let status = replay.send(request.command).await?;
+        Ok(Answer::ReplayStatus(status))
+    }
+
     pub async fn modify_device_ip(
         &mut self,
         ip: Ipv4Addr,
@@ -1206,19 +1715,25 @@ impl DeviceManager {
 
 impl ManagerActorHandler {
     pub async fn send(&self, request: Request) -> Result<Answer, ManagerError> {
+        self.send_from(None, request).await
+    }
+
+    // Requests from clients are checked against the device locks
+    pub async fn send_from(
+        &self,
+        client: Option<String>,
+        request: Request,
+    ) -> Result<Answer, ManagerError> {
         let (result_sender, result_receiver) = oneshot::channel();
 
         match &request {
             // Devices requests are forwarded directly to device and let manager handle other incoming request.
             Request::Ping(request) => {
                 trace!("Handling Ping request: {request:?}: Forwarding request to device handler");
-                let get_handler_target = request.uuid;
-                let handler_request =
-                    Request::GetDeviceHandler(crate::device::manager::UuidWrapper {
-                        uuid: get_handler_target,
-                    });
+                // The manager checks the device lock before returning the device handler
                 let manager_request = ManagerActorRequest {
-                    request: handler_request,
+                    request: Request::Ping(request.clone()),
+                    client,
                     respond_to: result_sender,
                 };
                 self.sender
@@ -1264,6 +1779,7 @@ impl ManagerActorHandler {
                 trace!("Handling DeviceManager request: {request:?}: Forwarding request.");
                 let device_request = ManagerActorRequest {
                     request: request.clone(),
+                    client,
                     respond_to: result_sender,
                 };
 
@@ -1292,6 +1808,12 @@ impl ManagerActorHandler {
     }
 }
 
+fn serialize_message(message: &impl bluerobotics_ping::message::PingMessage) -> Vec<u8> {
+    let mut package = ProtocolMessage::new();
+    package.set_message(message);
+    package.serialized()
+}
+
 pub async fn turnoff_device_continuous_mode(source: &SourceSelection) -> Result<(), ManagerError> {
     match source {
         SourceSelection::SerialStream(serial_config) => {
@@ -1332,6 +1854,30 @@ pub async fn turnoff_device_continuous_mode(source: &SourceSelection) -> Result<
                     ))
                 })?;
         }
+        SourceSelection::TcpStream(tcp_config) => {
+            debug!(
+                "Sending stop messages to TCP device at {}:{}",
+                tcp_config.ip, tcp_config.port
+            );
+            // There is no break signal over TCP, stop both Ping360 auto-transmit and Ping1D profiles
+            let profile_id = <bluerobotics_ping::ping1d::ProfileStruct as bluerobotics_ping::message::MessageInfo>::id();
+            for message in [
+                serialize_message(&bluerobotics_ping::ping360::Messages::MotorOff(
+                    bluerobotics_ping::ping360::MotorOffStruct {},
+                )),
+                serialize_message(&bluerobotics_ping::ping1d::Messages::ContinuousStop(
+                    bluerobotics_ping::ping1d::ContinuousStopStruct { id: profile_id },
+                )),
+            ] {
+                tcp_stream::write(tcp_config, message).await?;
+            }
+        }
+        SourceSelection::Replay(replay_config) => {
+            debug!(
+                "Replay device at {} has no continuous mode to turn off",
+                replay_config.path
+            );
+        }
     }
 
     Ok(())
@@ -0,0 +1,331 @@
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+use uuid::Uuid;
+
+use super::{
+    Answer, DeviceManager, DeviceProperties, ManagerError, ModifyDeviceResult, Ping360Config,
+};
+
+/// Duration of a sample period tick, in seconds
+pub const SAMPLE_PERIOD_TICK_DURATION: f32 = 25e-9;
+pub const MIN_SAMPLE_PERIOD: u16 = 80;
+/// Transmit duration limits of the firmware, in microseconds
+pub const MIN_TRANSMIT_DURATION: u16 = 5;
+pub const MAX_TRANSMIT_DURATION: u16 = 500;
+pub const MIN_NUMBER_OF_SAMPLES: u16 = 200;
+/// Transmit frequency limits of the transducer, in kHz
+pub const MIN_TRANSMIT_FREQUENCY: u16 = 500;
+pub const MAX_TRANSMIT_FREQUENCY: u16 = 1000;
+pub const MAX_NUMBER_OF_SAMPLES: u16 = 1200;
+/// Speed of sound in water used until one is provided, in meters per second
+pub const DEFAULT_SPEED_OF_SOUND: f32 = 1500.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub struct Ping360Range {
+    /// Desired one-way range, in meters
+    pub range_m: f32,
+    /// Speed of sound in meters per second, the device's current one is used when missing
+    pub speed_of_sound: Option<f32>,
+    /// Desired distance between samples, in meters, the finest possible is used when missing
+    pub resolution: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Ping360ConfigInfo {
+    #[serde(flatten)]
+    pub config: Ping360Config,
+    /// Speed of sound used to compute the range, in meters per second
+    pub speed_of_sound: f32,
+    /// Effective one-way range, in meters
+    pub range_m: f32,
+    /// Effective distance between samples, in meters
+    pub resolution_m: f32,
+}
+
+impl Ping360ConfigInfo {
+    pub fn new(config: Ping360Config, speed_of_sound: f32) -> Self {
+        Self {
+            config,
+            speed_of_sound,
+            range_m: range_m(&config, speed_of_sound),
+            resolution_m: resolution_m(&config, speed_of_sound),
+        }
+    }
+}
+
+pub fn range_m(config: &Ping360Config, speed_of_sound: f32) -> f32 {
+    config.sample_period as f32
+        * SAMPLE_PERIOD_TICK_DURATION
+        * config.number_of_samples as f32
+        * speed_of_sound
+        / 2.0
+}
+
+pub fn resolution_m(config: &Ping360Config, speed_of_sound: f32) -> f32 {
+    if config.number_of_samples == 0 {
+        return 0.0;
+    }
+    range_m(config, speed_of_sound) / config.number_of_samples as f32
+}
+
+fn sample_period(range_m: f32, number_of_samples: u16, speed_of_sound: f32) -> f32 {
+    2.0 * range_m / (number_of_samples as f32 * speed_of_sound * SAMPLE_PERIOD_TICK_DURATION)
+}
+
+// Same rules as Ping Viewer's automatic transmit duration:
+// start from the range, keep it wide enough for the sample period, then apply the firmware limits
+fn transmit_duration(range_m: f32, sample_period: u16, speed_of_sound: f32) -> u16 {
+    // A tick is 1/40 of a microsecond, kept as a division to avoid rounding up a whole microsecond
+    let sample_period_us = sample_period as f32 / 40.0;
+    let duration = (8000.0 * range_m / speed_of_sound)
+        .round()
+        .max((2.5 * sample_period_us).ceil());
+    let max_duration = (64.0 * sample_period_us).min(MAX_TRANSMIT_DURATION as f32);
+    duration.min(max_duration).max(MIN_TRANSMIT_DURATION as f32) as u16
+}
+
+impl Ping360Range {
+    /// Range and resolution reached by a configuration
+    pub fn current(config: &Ping360Config, speed_of_sound: f32) -> Self {
+        Self {
+            range_m: range_m(config, speed_of_sound),
+            speed_of_sound: Some(speed_of_sound),
+            resolution: Some(resolution_m(config, speed_of_sound)),
+        }
+    }
+
+    /// Sampling configuration reaching the range, keeping everything else from `base`
+    pub fn apply(
+        &self,
+        base: &Ping360Config,
+        speed_of_sound: f32,
+    ) -> Result<Ping360Config, String> {
+        let range_m = self.range_m;
+        if !range_m.is_finite() || range_m <= 0.0 {
+            return Err(format!("Invalid range of {range_m} m"));
+        }
+        if !speed_of_sound.is_finite() || speed_of_sound <= 0.0 {
+            return Err(format!("Invalid speed of sound of {speed_of_sound} m/s"));
+        }
+
+        let mut number_of_samples = match self.resolution {
+            Some(resolution) if !resolution.is_finite() || resolution <= 0.0 => {
+                return Err(format!("Invalid resolution of {resolution} m"))
+            }
+            Some(resolution) => (range_m / resolution)
+                .ceil()
+                .clamp(MIN_NUMBER_OF_SAMPLES as f32, MAX_NUMBER_OF_SAMPLES as f32)
+                as u16,
+            None => MAX_NUMBER_OF_SAMPLES,
+        };
+
+        // Short ranges need faster sampling than the firmware allows, so fewer samples are used
+        let mut period = sample_period(range_m, number_of_samples, speed_of_sound).round();
+        while period < MIN_SAMPLE_PERIOD as f32 && number_of_samples > MIN_NUMBER_OF_SAMPLES {
+            number_of_samples -= 1;
+            period = sample_period(range_m, number_of_samples, speed_of_sound).round();
+        }
+        if period < MIN_SAMPLE_PERIOD as f32 {
+            return Err(format!("Range of {range_m} m is too short"));
+        }
+        if period > u16::MAX as f32 {
+            return Err(format!("Range of {range_m} m is too long"));
+        }
+        let period = period as u16;
+
+        Ok(Ping360Config {
+            sample_period: period,
+            number_of_samples,
+            transmit_duration: transmit_duration(range_m, period, speed_of_sound),
+            ..*base
+        })
+    }
+}
+
+impl DeviceManager {
+    pub fn ping360_speed_of_sound(
+        &self,
+        device_id: Uuid,
+    ) -> Result<Arc<RwLock<f32>>, ManagerError> {
+        match &self.get_device(device_id)?.properties {
+            Some(DeviceProperties::Ping360(properties)) => Ok(properties.speed_of_sound.clone()),
+            _ => Err(ManagerError::DeviceSourceError(
+                "ping360_speed_of_sound: Can't use speed of sound".to_string(),
+            )),
+        }
+    }
+
+    pub fn update_ping360_speed_of_sound(
+        &self,
+        device_id: Uuid,
+        speed_of_sound: f32,
+    ) -> Result<(), ManagerError> {
+        let current = self.ping360_speed_of_sound(device_id)?;
+        let mut current = current
+            .write()
+            .map_err(|err| ManagerError::Other(err.to_string()))?;
+        *current = speed_of_sound;
+        Ok(())
+    }
+
+    pub async fn set_ping360_range(
+        &mut self,
+        device_id: Uuid,
+        range: Ping360Range,
+    ) -> Result<Ping360ConfigInfo, ManagerError> {
+        let speed_of_sound = match range.speed_of_sound {
+            Some(speed_of_sound) => speed_of_sound,
+            None => *self
+                .ping360_speed_of_sound(device_id)?
+                .read()
+                .map_err(|err| ManagerError::Other(err.to_string()))?,
+        };
+
+        // The range takes over any running scan plan, like a configuration set by the user
+        self.stop_ping360_scan_plan(device_id).await?;
+        let base = match self.get_ping360_config(device_id).await? {
+            Answer::DeviceConfig(ModifyDeviceResult::Ping360Config(info)) => info.config,
+            answer => {
+                return Err(ManagerError::Other(format!(
+                    "Unexpected answer while reading Ping360Config: {answer:?}"
+                )))
+            }
+        };
+        let config = range
+            .apply(&base, speed_of_sound)
+            .map_err(ManagerError::Other)?;
+
+        self.update_ping360_speed_of_sound(device_id, speed_of_sound)?;
+        self.update_ping360_config(device_id, config).await?;
+        Ok(Ping360ConfigInfo::new(config, speed_of_sound))
+    }
+
+    // Keep the current range and resolution with a new speed of sound.
+    // A running scan plan keeps its sectors, the configuration restored once it stops follows.
+    pub async fn update_ping360_range_speed_of_sound(
+        &mut self,
+        device_id: Uuid,
+        speed_of_sound: f32,
+    ) -> Result<(), ManagerError> {
+        let previous = *self
+            .ping360_speed_of_sound(device_id)?
+            .read()
+            .map_err(|err| ManagerError::Other(err.to_string()))?;
+        let base = match self.scan_plan_runners.get(&device_id) {
+            Some(runner) => runner.base_config,
+            None => match self.get_ping360_config(device_id).await? {
+                Answer::DeviceConfig(ModifyDeviceResult::Ping360Config(info)) => info.config,
+                answer => {
+                    return Err(ManagerError::Other(format!(
+                        "Unexpected answer while reading Ping360Config: {answer:?}"
+                    )))
+                }
+            },
+        };
+        let config = Ping360Range::current(&base, previous)
+            .apply(&base, speed_of_sound)
+            .map_err(ManagerError::Other)?;
+
+        self.update_ping360_speed_of_sound(device_id, speed_of_sound)?;
+        match self.scan_plan_runners.get_mut(&device_id) {
+            Some(runner) => runner.base_config = config,
+            None => self.update_ping360_config(device_id, config).await?,
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Ping360Config {
+        Ping360Config {
+            mode: 1,
+            gain_setting: 0,
+            transmit_duration: 32,
+            sample_period: 80,
+            transmit_frequency: 740,
+            number_of_samples: 1200,
+            start_angle: 0,
+            stop_angle: 399,
+            num_steps: 1,
+            delay: 0,
+        }
+    }
+
+    fn range(range_m: f32, resolution: Option<f32>) -> Ping360Range {
+        Ping360Range {
+            range_m,
+            speed_of_sound: None,
+            resolution,
+        }
+    }
+
+    #[test]
+    fn test_long_range() {
+        let config = range(50.0, None).apply(&base(), 1500.0).unwrap();
+        assert_eq!(config.number_of_samples, 1200);
+        assert_eq!(config.sample_period, 2222);
+        assert_eq!(config.transmit_duration, 267);
+        assert_eq!(config.transmit_frequency, 740);
+        assert!((range_m(&config, 1500.0) - 50.0).abs() < 0.01);
+
+        let config = range(50.0, Some(0.25)).apply(&base(), 1500.0).unwrap();
+        assert_eq!(config.number_of_samples, 200);
+        assert!((resolution_m(&config, 1500.0) - 0.25).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_short_range() {
+        // The firmware can't sample fast enough for 1200 samples over a meter
+        let config = range(1.0, None).apply(&base(), 1500.0).unwrap();
+        assert_eq!(config.sample_period, MIN_SAMPLE_PERIOD);
+        assert_eq!(config.number_of_samples, 670);
+        assert_eq!(config.transmit_duration, MIN_TRANSMIT_DURATION);
+
+        assert!(range(0.1, None).apply(&base(), 1500.0).is_err());
+        assert!(range(-1.0, None).apply(&base(), 1500.0).is_err());
+        assert!(range(10.0, Some(0.0)).apply(&base(), 1500.0).is_err());
+    }
+
+    #[test]
+    fn test_speed_of_sound_change() {
+        for (range_m, resolution) in [(1.0, None), (10.0, Some(0.05)), (50.0, None)] {
+            let config = range(range_m, resolution).apply(&base(), 1500.0).unwrap();
+            let updated = Ping360Range::current(&config, 1500.0)
+                .apply(&config, 1450.0)
+                .unwrap();
+            assert_eq!(updated.number_of_samples, config.number_of_samples);
+            assert!(updated.sample_period > config.sample_period);
+            assert!((super::range_m(&updated, 1450.0) - range_m).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_config_validation() {
+        use validator::Validate;
+
+        assert!(base().validate().is_ok());
+        for range_m in [1.0, 10.0, 50.0] {
+            let config = range(range_m, None).apply(&base(), 1500.0).unwrap();
+            assert!(config.validate().is_ok());
+        }
+
+        let invalid = Ping360Config {
+            num_steps: 0,
+            stop_angle: 400,
+            number_of_samples: 1201,
+            ..base()
+        };
+        let ManagerError::InvalidConfig(fields) =
+            ManagerError::from(invalid.validate().unwrap_err())
+        else {
+            panic!("Expected an InvalidConfig error");
+        };
+        let names: Vec<&str> = fields.iter().map(|field| field.field.as_str()).collect();
+        assert_eq!(names, ["num_steps", "number_of_samples", "stop_angle"]);
+        assert_eq!(fields[0].message, "num_steps is 0, expected at least 1");
+    }
+}
@@ -0,0 +1,311 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::{task::JoinHandle, time::Instant};
+use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use super::{
+    Answer, DeviceManager, DeviceProperties, DeviceSelection, DeviceStatus, ManagerError,
+    Ping360Config, SourceSelection,
+};
+use crate::device::{
+    devices::{DeviceActor, DeviceActorHandler, PingCommonRequest, PingRequest},
+    replay::ReplayHandler,
+};
+
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+// Same timeout used by the status check before marking a device with error
+const RECONNECT_CHECK_TIMEOUT: Duration = Duration::from_millis(2000);
+
+// What should be restored once a device in error is reachable again
+#[derive(Debug, Clone)]
+pub struct ReconnectState {
+    previous_status: DeviceStatus,
+    ping360_config: Option<Ping360Config>,
+    attempts: u32,
+    next_attempt: Instant,
+    // An attempt is running in the background
+    in_progress: bool,
+}
+
+struct ReopenedDevice {
+    handler: DeviceActorHandler,
+    actor: JoinHandle<DeviceActor>,
+    replay: Option<ReplayHandler>,
+}
+
+/// Outcome of a reconnection attempt, sent back to the manager
+pub struct ReconnectAttempt {
+    device_id: Uuid,
+    result: Result<ReopenedDevice, ManagerError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceStatusTransition {
+    pub device_id: Uuid,
+    pub from: DeviceStatus,
+    pub to: DeviceStatus,
+    pub attempt: u32,
+    /// Time until the next reconnection attempt, only while the device stays with error
+    pub retry_in_ms: Option<u64>,
+    pub details: Option<String>,
+}
+
+fn backoff(attempts: u32) -> Duration {
+    RECONNECT_INITIAL_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts))
+        .min(RECONNECT_MAX_DELAY)
+}
+
+fn report(transition: DeviceStatusTransition) {
+    let device_id = transition.device_id;
+    crate::server::protocols::v1::websocket::send_to_websockets(
+        json!(Answer::DeviceStatusTransition(transition)),
+        Some(device_id),
+    );
+}
+
+impl DeviceManager {
+    // Register a device that was just marked with error, it will be reopened in the background
+    pub fn schedule_reconnection(&mut self, device_id: Uuid, previous_status: DeviceStatus) {
+        if self.reconnect.contains_key(&device_id) {
+            return;
+        }
+
+        let ping360_config = match self.get_device(device_id).map(|device| &device.properties) {
+            Ok(Some(DeviceProperties::Ping360(properties))) => properties
+                .continuous_mode_settings
+                .read()
+                .ok()
+                .map(|config| *config),
+            _ => None,
+        };
+
+        info!("Device {device_id} marked with error, scheduling reconnection");
+
+        self.reconnect.insert(
+            device_id,
+            ReconnectState {
+                previous_status: previous_status.clone(),
+                ping360_config,
+                attempts: 0,
+                next_attempt: Instant::now() + RECONNECT_INITIAL_DELAY,
+                in_progress: false,
+            },
+        );
+
+        report(DeviceStatusTransition {
+            device_id,
+            from: previous_status,
+            to: DeviceStatus::Error,
+            attempt: 0,
+            retry_in_ms: Some(RECONNECT_INITIAL_DELAY.as_millis() as u64),
+            details: None,
+        });
+    }
+
+    // Status the device had before being marked with error, while it's being reconnected
+    pub fn status_before_error(&self, device_id: Uuid) -> Option<&DeviceStatus> {
+        self.reconnect
+            .get(&device_id)
+            .map(|state| &state.previous_status)
+    }
+
+    pub fn forget_reconnection(&mut self, device_id: Uuid) {
+        self.reconnect.remove(&device_id);
+    }
+
+    // Start a reconnection attempt for every device whose backoff delay is over. Attempts run in
+    // the background and report back to the manager, a device slow to answer doesn't hold it.
+    pub fn reconnect_devices(&mut self) {
+        let now = Instant::now();
+        let due: Vec<Uuid> = self
+            .reconnect
+            .iter()
+            .filter(|(_, state)| !state.in_progress && state.next_attempt <= now)
+            .map(|(device_id, _)| *device_id)
+            .collect();
+
+        for device_id in due {
+            let Ok(device) = self.get_mut_device(device_id) else {
+                debug!("Device {device_id} was removed, dropping its reconnection");
+                self.reconnect.remove(&device_id);
+                continue;
+            };
+
+            // Release the previous source first, serial ports can't be opened twice
+            if let Some(broadcast) = device.broadcast.take() {
+                broadcast.abort();
+            }
+            if let Some(actor) = device.actor.take() {
+                actor.abort();
+            }
+            device.handler = None;
+            device.replay = None;
+
+            let source = device.source.clone();
+            let device_type = device.device_type.clone();
+
+            let Some(state) = self.reconnect.get_mut(&device_id) else {
+                continue;
+            };
+            state.attempts += 1;
+            state.in_progress = true;
+            debug!(
+                "Reconnecting device {device_id}, attempt {}",
+                state.attempts
+            );
+
+            let sender = self.reconnect_sender.clone();
+            tokio::spawn(async move {
+                let result = reopen_device(device_id, source, device_type).await;
+                if let Err(err) = sender.send(ReconnectAttempt { device_id, result }).await {
+                    if let Ok(device) = err.0.result {
+                        device.actor.abort();
+                    }
+                }
+            });
+        }
+    }
+
+    pub async fn finish_reconnection(&mut self, attempt: ReconnectAttempt) {
+        let ReconnectAttempt { device_id, result } = attempt;
+        let mut state = match self.reconnect.remove(&device_id) {
+            Some(state) if self.device.contains_key(&device_id) => state,
+            _ => {
+                debug!("Device {device_id} was removed, dropping its reconnection");
+                if let Ok(device) = result {
+                    device.actor.abort();
+                }
+                return;
+            }
+        };
+
+        let result = match result {
+            Ok(device) => self.replace_device(device_id, device, &state).await,
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(status) => {
+                info!(
+                    "Device {device_id} reconnected after {} attempts, status: {status:?}",
+                    state.attempts
+                );
+                report(DeviceStatusTransition {
+                    device_id,
+                    from: DeviceStatus::Error,
+                    to: status,
+                    attempt: state.attempts,
+                    retry_in_ms: None,
+                    details: None,
+                });
+            }
+            Err(err) => {
+                let delay = backoff(state.attempts);
+                warn!(
+                    "Device {device_id} reconnection attempt {} failed, retrying in {delay:?}, details: {err:?}",
+                    state.attempts
+                );
+
+                if let Ok(device) = self.get_mut_device(device_id) {
+                    device.status = DeviceStatus::Error;
+                }
+
+                report(DeviceStatusTransition {
+                    device_id,
+                    from: DeviceStatus::Error,
+                    to: DeviceStatus::Error,
+                    attempt: state.attempts,
+                    retry_in_ms: Some(delay.as_millis() as u64),
+                    details: Some(format!("{err:?}")),
+                });
+
+                state.in_progress = false;
+                state.next_attempt = Instant::now() + delay;
+                self.reconnect.insert(device_id, state);
+            }
+        }
+    }
+
+    // Replace the device with the reopened one, enabling continuous mode like a new device
+    async fn replace_device(
+        &mut self,
+        device_id: Uuid,
+        device: ReopenedDevice,
+        state: &ReconnectState,
+    ) -> Result<DeviceStatus, ManagerError> {
+        self.install_device(device_id, device.handler, device.actor, device.replay)?;
+
+        if let Err(err) = self.continuous_mode(device_id).await {
+            error!("Failed to enable continuous mode for device {device_id}: {err:?}");
+        }
+
+        if let Some(config) = state.ping360_config {
+            self.write_ping360_config(device_id, config)?;
+        }
+
+        match (&state.previous_status, self.get_device_status(device_id)?) {
+            (DeviceStatus::Running, DeviceStatus::ContinuousMode) => {
+                self.continuous_mode_off(device_id).await?;
+            }
+            (DeviceStatus::ContinuousMode, DeviceStatus::Running) => {
+                self.continuous_mode(device_id).await?;
+            }
+            _ => {}
+        }
+
+        self.get_device_status(device_id)
+    }
+}
+
+// Reopen the source and check the device answers, away from the manager
+async fn reopen_device(
+    device_id: Uuid,
+    source: SourceSelection,
+    device_type: DeviceSelection,
+) -> Result<ReopenedDevice, ManagerError> {
+    let (device_type_inner, replay) = DeviceManager::open_device(&source, &device_type).await?;
+    let (device_actor, handler) = DeviceActor::new(device_type_inner, 10);
+    let actor = tokio::spawn(async move { device_actor.run().await });
+
+    let check = tokio::time::timeout(
+        RECONNECT_CHECK_TIMEOUT,
+        handler.send(PingRequest::Common(PingCommonRequest::DeviceInformation)),
+    )
+    .await;
+    match check {
+        Ok(Ok(_answer)) => Ok(ReopenedDevice {
+            handler,
+            actor,
+            replay,
+        }),
+        Ok(Err(err)) => {
+            actor.abort();
+            Err(ManagerError::DeviceError(err))
+        }
+        Err(_) => {
+            actor.abort();
+            Err(ManagerError::Other(format!(
+                "Timeout while checking reconnected device {device_id}"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_backoff() {
+        assert_eq!(backoff(0), Duration::from_secs(1));
+        assert_eq!(backoff(1), Duration::from_secs(2));
+        assert_eq!(backoff(3), Duration::from_secs(8));
+        assert_eq!(backoff(10), RECONNECT_MAX_DELAY);
+        assert_eq!(backoff(u32::MAX), RECONNECT_MAX_DELAY);
+    }
+}
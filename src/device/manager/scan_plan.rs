@@ -0,0 +1,527 @@
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle, time::Instant};
+use tracing::{debug, error, info, trace, warn};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+use super::{
+    ping360_range, sonar_image::GRADIANS, Answer, DeviceManager, DeviceProperties, DeviceStatus,
+    ManagerActorHandler, ManagerError, Ping360Config, Request, UuidWrapper,
+};
+use crate::device::devices::{PingAnswer, PingRequest};
+
+// Time to wait before attaching again to a device that is not scanning
+const SCAN_PLAN_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// A sector swept with its own acquisition settings.
+// Limits are the same as the ones of Ping360Config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Apiv2Schema, Validate)]
+pub struct ScanSector {
+    #[validate(range(max = 399))]
+    pub start_angle: u16,
+    #[validate(range(max = 399))]
+    pub stop_angle: u16,
+    #[validate(range(min = 1))]
+    pub num_steps: u8,
+    #[validate(range(max = 2))]
+    pub gain_setting: u8,
+    #[validate(range(
+        min = ping360_range::MIN_TRANSMIT_DURATION,
+        max = ping360_range::MAX_TRANSMIT_DURATION
+    ))]
+    pub transmit_duration: u16,
+    /// Sample period and number of samples define the range of the sector
+    #[validate(range(min = ping360_range::MIN_SAMPLE_PERIOD))]
+    pub sample_period: u16,
+    #[validate(range(
+        min = ping360_range::MIN_NUMBER_OF_SAMPLES,
+        max = ping360_range::MAX_NUMBER_OF_SAMPLES
+    ))]
+    pub number_of_samples: u16,
+    /// Number of sweeps over the sector before moving to the next one
+    #[validate(range(min = 1))]
+    pub repeat: u16,
+}
+
+impl ScanSector {
+    pub fn is_full_circle(&self) -> bool {
+        self.start_angle == 0 && self.stop_angle as usize == GRADIANS - 1
+    }
+
+    // Number of lines received in a single pass from the start to the stop angle
+    pub fn lines_per_sweep(&self) -> u32 {
+        let steps = self.num_steps.max(1) as u32;
+        if self.is_full_circle() {
+            return (GRADIANS as u32).div_ceil(steps);
+        }
+        let span = (self.stop_angle as i32 - self.start_angle as i32).rem_euclid(GRADIANS as i32);
+        span as u32 / steps + 1
+    }
+
+    // Angles from the start to the stop angle, crossing zero when the stop angle is lower
+    pub fn contains(&self, angle: u16) -> bool {
+        if self.is_full_circle() {
+            return true;
+        }
+        let offset = (angle as i32 - self.start_angle as i32).rem_euclid(GRADIANS as i32);
+        let span = (self.stop_angle as i32 - self.start_angle as i32).rem_euclid(GRADIANS as i32);
+        offset <= span
+    }
+
+    pub fn apply(&self, base: &Ping360Config) -> Ping360Config {
+        Ping360Config {
+            gain_setting: self.gain_setting,
+            transmit_duration: self.transmit_duration,
+            sample_period: self.sample_period,
+            number_of_samples: self.number_of_samples,
+            start_angle: self.start_angle,
+            stop_angle: self.stop_angle,
+            num_steps: self.num_steps,
+            ..*base
+        }
+    }
+}
+
+/// Named sequence of sectors, executed while the Ping360 is on continuous mode.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Apiv2Schema, Validate)]
+pub struct ScanPlan {
+    #[validate(custom(function = "validate_plan_name"))]
+    pub name: String,
+    #[validate(length(min = 1), nested)]
+    pub sectors: Vec<ScanSector>,
+    /// Seconds between the start of each run, the device goes back to its own configuration
+    /// between runs. Without it the plan runs again right after finishing.
+    #[validate(range(min = 1))]
+    pub interval_s: Option<u32>,
+}
+
+fn validate_plan_name(name: &str) -> Result<(), ValidationError> {
+    if name.trim().is_empty() {
+        return Err(ValidationError::new("blank").with_message("name can't be empty".into()));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub struct ScanPlans {
+    pub plans: Vec<ScanPlan>,
+    /// Plan currently executed, it's resumed when the device is restored
+    pub active: Option<String>,
+}
+
+// A running plan and the configuration to be restored once it stops
+#[derive(Debug)]
+pub struct ScanPlanRunner {
+    pub(super) base_config: Ping360Config,
+    task: JoinHandle<()>,
+}
+
+// Current configuration and data stream of a device on continuous mode
+async fn attach(
+    manager_handler: &ManagerActorHandler,
+    device_id: Uuid,
+) -> Result<
+    (
+        Arc<RwLock<Ping360Config>>,
+        tokio::sync::broadcast::Receiver<bluerobotics_ping::message::ProtocolMessage>,
+    ),
+    ManagerError,
+> {
+    let info = match manager_handler
+        .send(Request::Info(UuidWrapper { uuid: device_id }))
+        .await?
+    {
+        Answer::DeviceInfo(info) => info.into_iter().next(),
+        _ => None,
+    }
+    .ok_or(ManagerError::DeviceNotExist(device_id))?;
+
+    if info.status != DeviceStatus::ContinuousMode {
+        return Err(ManagerError::Other(format!(
+            "Device {device_id} is not on continuous mode, status: {:?}",
+            info.status
+        )));
+    }
+    let Some(DeviceProperties::Ping360(properties)) = info.properties else {
+        return Err(ManagerError::Other(format!(
+            "Device {device_id} has no Ping360 properties"
+        )));
+    };
+
+    let handler = match manager_handler
+        .send(Request::GetDeviceHandler(UuidWrapper { uuid: device_id }))
+        .await?
+    {
+        Answer::InnerDeviceHandler(handler) => handler,
+        answer => {
+            return Err(ManagerError::Other(format!(
+                "Unexpected answer while getting device handler: {answer:?}"
+            )))
+        }
+    };
+    let subscriber = match handler.send(PingRequest::GetSubscriber).await {
+        Ok(PingAnswer::Subscriber(subscriber)) => subscriber,
+        Ok(answer) => {
+            return Err(ManagerError::Other(format!(
+                "Unexpected answer while getting subscriber: {answer:?}"
+            )))
+        }
+        Err(err) => return Err(ManagerError::DeviceError(err)),
+    };
+
+    Ok((properties.continuous_mode_settings, subscriber))
+}
+
+fn write_config(config: &RwLock<Ping360Config>, new_config: Ping360Config) {
+    match config.write() {
+        Ok(mut config) => {
+            // The continuous mode task restarts the scan on any change
+            if *config != new_config {
+                *config = new_config;
+            }
+        }
+        Err(err) => error!("Failed to write Ping360Config: {err}"),
+    }
+}
+
+fn scan_line_angle(msg: &bluerobotics_ping::message::ProtocolMessage) -> Option<u16> {
+    match bluerobotics_ping::Messages::try_from(msg) {
+        Ok(bluerobotics_ping::Messages::Ping360(
+            bluerobotics_ping::ping360::Messages::AutoDeviceData(data),
+        )) => Some(data.angle),
+        Ok(bluerobotics_ping::Messages::Ping360(
+            bluerobotics_ping::ping360::Messages::DeviceData(data),
+        )) => Some(data.angle),
+        _ => None,
+    }
+}
+
+// Apply the sector and wait for all its sweeps, following the device over reconnections
+async fn run_sector(
+    manager_handler: &ManagerActorHandler,
+    device_id: Uuid,
+    sector: &ScanSector,
+    base_config: &Ping360Config,
+) {
+    let target = sector.lines_per_sweep() * sector.repeat as u32;
+    let mut lines = 0;
+
+    while lines < target {
+        let (config, mut subscriber) = match attach(manager_handler, device_id).await {
+            Ok(attached) => attached,
+            Err(err) => {
+                trace!("Scan plan: waiting for device {device_id}: {err:?}");
+                tokio::time::sleep(SCAN_PLAN_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        write_config(&config, sector.apply(base_config));
+
+        while lines < target {
+            match subscriber.recv().await {
+                // Lines of the previous sector may still arrive after the new configuration
+                Ok(msg) => {
+                    if scan_line_angle(&msg).is_some_and(|angle| sector.contains(angle)) {
+                        lines += 1;
+                    }
+                }
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+async fn run_scan_plan(
+    manager_handler: ManagerActorHandler,
+    device_id: Uuid,
+    plan: ScanPlan,
+    base_config: Ping360Config,
+) {
+    info!("Scan plan {} started, device: {device_id}", plan.name);
+
+    loop {
+        let started = Instant::now();
+        for (index, sector) in plan.sectors.iter().enumerate() {
+            debug!(
+                "Scan plan {}: sector {index}, angles {}..{}, device: {device_id}",
+                plan.name, sector.start_angle, sector.stop_angle
+            );
+            run_sector(&manager_handler, device_id, sector, &base_config).await;
+        }
+
+        if let Some(interval) = plan.interval_s {
+            match attach(&manager_handler, device_id).await {
+                Ok((config, _)) => write_config(&config, base_config),
+                Err(err) => warn!(
+                    "Scan plan {}: failed to restore configuration: {err:?}",
+                    plan.name
+                ),
+            }
+            tokio::time::sleep_until(started + Duration::from_secs(interval as u64)).await;
+        }
+    }
+}
+
+impl DeviceManager {
+    fn scan_plans(&self, device_id: Uuid) -> Result<Arc<RwLock<ScanPlans>>, ManagerError> {
+        match &self.get_device(device_id)?.properties {
+            Some(DeviceProperties::Ping360(properties)) => Ok(properties.scan_plans.clone()),
+            _ => Err(ManagerError::DeviceSourceError(format!(
+                "scan_plans: Device {device_id} has no Ping360 properties"
+            ))),
+        }
+    }
+
+    pub fn get_ping360_scan_plans(&self, device_id: Uuid) -> Result<ScanPlans, ManagerError> {
+        self.scan_plans(device_id)?
+            .read()
+            .map(|plans| plans.clone())
+            .map_err(|err| ManagerError::Other(format!("get_ping360_scan_plans: {err}")))
+    }
+
+    // Restore persisted plans, without starting the active one
+    pub fn update_ping360_scan_plans(
+        &self,
+        device_id: Uuid,
+        new_plans: ScanPlans,
+    ) -> Result<(), ManagerError> {
+        let plans = self.scan_plans(device_id)?;
+        let mut plans = plans
+            .write()
+            .map_err(|err| ManagerError::Other(err.to_string()))?;
+        *plans = new_plans;
+        Ok(())
+    }
+
+    // Add a plan or replace the one with the same name, a running plan is restarted
+    pub async fn set_ping360_scan_plan(
+        &mut self,
+        device_id: Uuid,
+        plan: ScanPlan,
+    ) -> Result<(), ManagerError> {
+        plan.validate()?;
+
+        let is_active = {
+            let plans = self.scan_plans(device_id)?;
+            let mut plans = plans
+                .write()
+                .map_err(|err| ManagerError::Other(err.to_string()))?;
+            match plans
+                .plans
+                .iter_mut()
+                .find(|current| current.name == plan.name)
+            {
+                Some(current) => *current = plan.clone(),
+                None => plans.plans.push(plan.clone()),
+            }
+            plans.active.as_ref() == Some(&plan.name)
+        };
+
+        if is_active {
+            self.start_ping360_scan_plan(device_id, plan.name).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete_ping360_scan_plan(
+        &mut self,
+        device_id: Uuid,
+        name: String,
+    ) -> Result<(), ManagerError> {
+        let plans = self.scan_plans(device_id)?;
+        if plans
+            .read()
+            .map_err(|err| ManagerError::Other(err.to_string()))?
+            .active
+            .as_ref()
+            == Some(&name)
+        {
+            self.stop_ping360_scan_plan(device_id).await?;
+        }
+
+        let mut plans = plans
+            .write()
+            .map_err(|err| ManagerError::Other(err.to_string()))?;
+        let count = plans.plans.len();
+        plans.plans.retain(|plan| plan.name != name);
+        if plans.plans.len() == count {
+            return Err(ManagerError::Other(format!(
+                "Scan plan {name} not found, device: {device_id}"
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn start_ping360_scan_plan(
+        &mut self,
+        device_id: Uuid,
+        name: String,
+    ) -> Result<(), ManagerError> {
+        let plan = self
+            .get_ping360_scan_plans(device_id)?
+            .plans
+            .into_iter()
+            .find(|plan| plan.name == name)
+            .ok_or_else(|| {
+                ManagerError::Other(format!("Scan plan {name} not found, device: {device_id}"))
+            })?;
+
+        self.stop_ping360_scan_plan(device_id).await?;
+        if self.get_device_status(device_id)? != DeviceStatus::ContinuousMode {
+            self.continuous_mode(device_id).await?;
+        }
+
+        // Continuous mode may rebuild the properties, so the configuration is read afterwards
+        let base_config = match self.get_ping360_config(device_id).await? {
+            Answer::DeviceConfig(super::ModifyDeviceResult::Ping360Config(info)) => info.config,
+            answer => {
+                return Err(ManagerError::Other(format!(
+                    "Unexpected answer while reading Ping360Config: {answer:?}"
+                )))
+            }
+        };
+
+        self.scan_plans(device_id)?
+            .write()
+            .map_err(|err| ManagerError::Other(err.to_string()))?
+            .active = Some(name);
+
+        let task = tokio::spawn(run_scan_plan(
+            self.get_device_manager_handler(),
+            device_id,
+            plan,
+            base_config,
+        ));
+        self.scan_plan_runners
+            .insert(device_id, ScanPlanRunner { base_config, task });
+        Ok(())
+    }
+
+    pub async fn stop_ping360_scan_plan(&mut self, device_id: Uuid) -> Result<(), ManagerError> {
+        if let Some(runner) = self.scan_plan_runners.remove(&device_id) {
+            runner.task.abort();
+            if let Err(err) = self.write_ping360_config(device_id, runner.base_config) {
+                warn!(
+                    "Failed to restore Ping360Config after scan plan: {err:?}, device: {device_id}"
+                );
+            }
+            info!("Scan plan stopped, device: {device_id}");
+        }
+
+        if let Ok(plans) = self.scan_plans(device_id) {
+            if let Ok(mut plans) = plans.write() {
+                plans.active = None;
+            }
+        }
+        Ok(())
+    }
+
+    // Start the plan marked as active, after restoring settings
+    pub async fn resume_ping360_scan_plan(&mut self, device_id: Uuid) -> Result<(), ManagerError> {
+        if self.scan_plan_runners.contains_key(&device_id) {
+            return Ok(());
+        }
+        match self.get_ping360_scan_plans(device_id)?.active {
+            Some(name) => self.start_ping360_scan_plan(device_id, name).await,
+            None => Ok(()),
+        }
+    }
+
+    pub fn forget_scan_plan(&mut self, device_id: Uuid) {
+        if let Some(runner) = self.scan_plan_runners.remove(&device_id) {
+            runner.task.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sector(start_angle: u16, stop_angle: u16, num_steps: u8) -> ScanSector {
+        ScanSector {
+            start_angle,
+            stop_angle,
+            num_steps,
+            gain_setting: 0,
+            transmit_duration: 32,
+            sample_period: 80,
+            number_of_samples: 1200,
+            repeat: 1,
+        }
+    }
+
+    #[test]
+    fn test_scan_sector_lines_per_sweep() {
+        assert_eq!(sector(0, 399, 1).lines_per_sweep(), 400);
+        assert_eq!(sector(0, 399, 3).lines_per_sweep(), 134);
+        assert_eq!(sector(100, 200, 1).lines_per_sweep(), 101);
+        assert_eq!(sector(100, 200, 10).lines_per_sweep(), 11);
+        // Sectors crossing the zero angle
+        assert_eq!(sector(350, 50, 2).lines_per_sweep(), 51);
+    }
+
+    #[test]
+    fn test_scan_sector_contains() {
+        assert!(sector(0, 399, 1).contains(250));
+        assert!(sector(100, 200, 1).contains(100));
+        assert!(sector(100, 200, 1).contains(200));
+        assert!(!sector(100, 200, 1).contains(201));
+        assert!(!sector(100, 200, 1).contains(50));
+        // Sectors crossing the zero angle
+        assert!(sector(350, 50, 2).contains(399));
+        assert!(sector(350, 50, 2).contains(10));
+        assert!(!sector(350, 50, 2).contains(200));
+    }
+
+    #[test]
+    fn test_scan_plan_validation() {
+        let plan = ScanPlan {
+            name: "survey".to_string(),
+            sectors: vec![sector(0, 399, 1), sector(350, 50, 2)],
+            interval_s: Some(60),
+        };
+        assert!(plan.validate().is_ok());
+
+        // Invalid sectors are reported as fields of the plan
+        let mut invalid = plan.clone();
+        invalid.sectors[1].stop_angle = 400;
+        match ManagerError::from(invalid.validate().unwrap_err()) {
+            ManagerError::InvalidConfig(fields) => {
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].field, "sectors[1].stop_angle");
+            }
+            err => panic!("Unexpected error: {err:?}"),
+        }
+
+        let mut invalid = plan.clone();
+        invalid.sectors[0].repeat = 0;
+        assert!(invalid.validate().is_err());
+
+        let invalid = ScanPlan {
+            sectors: vec![],
+            ..plan.clone()
+        };
+        assert!(invalid.validate().is_err());
+
+        let invalid = ScanPlan {
+            name: " ".to_string(),
+            interval_s: Some(0),
+            ..plan
+        };
+        match ManagerError::from(invalid.validate().unwrap_err()) {
+            ManagerError::InvalidConfig(fields) => {
+                let fields: Vec<_> = fields.iter().map(|field| field.field.as_str()).collect();
+                assert_eq!(fields, ["interval_s", "name"]);
+            }
+            err => panic!("Unexpected error: {err:?}"),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, trace, warn};
+use uuid::Uuid;
+
+use crate::device::rangefinder::RangefinderConfig;
+
+use super::{
+    distance_filter::DistanceFilterConfig, scan_plan::ScanPlans,
+    speed_of_sound::SpeedOfSoundConfig, Answer, DeviceInfo, DeviceManager, DeviceProperties,
+    DeviceSelection, DeviceStatus, ManagerError, Ping360Config, SourceSelection,
+};
+
+/// Current layout version of the settings file, bump it when `SettingsFile` changes.
+pub const SETTINGS_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DeviceSettings {
+    pub id: Uuid,
+    pub source: SourceSelection,
+    pub device_selection: DeviceSelection,
+    pub continuous_mode: bool,
+    pub ping360_config: Option<Ping360Config>,
+    #[serde(default)]
+    pub ping1d_distance_filter: Option<DistanceFilterConfig>,
+    #[serde(default)]
+    pub ping1d_rangefinder: Option<RangefinderConfig>,
+    #[serde(default)]
+    pub ping360_scan_plans: Option<ScanPlans>,
+    #[serde(default)]
+    pub ping360_speed_of_sound: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SettingsFile {
+    pub version: u32,
+    pub devices: Vec<DeviceSettings>,
+    #[serde(default)]
+    pub speed_of_sound: Option<SpeedOfSoundConfig>,
+}
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            devices: Vec::new(),
+            speed_of_sound: None,
+        }
+    }
+}
+
+impl SettingsFile {
+    pub fn load(path: &Path) -> Result<Option<Self>, ManagerError> {
+        if !path.exists() {
+            debug!("Settings file not found at {path:?}, starting without previous devices");
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path).map_err(|err| {
+            ManagerError::Other(format!("Failed to read settings file {path:?}: {err}"))
+        })?;
+
+        let settings: SettingsFile = serde_json::from_str(&content).map_err(|err| {
+            ManagerError::Other(format!("Failed to parse settings file {path:?}: {err}"))
+        })?;
+
+        if settings.version != SETTINGS_VERSION {
+            warn!(
+                "Settings file {path:?} has version {}, expected {SETTINGS_VERSION}, ignoring it",
+                settings.version
+            );
+            return Ok(None);
+        }
+
+        Ok(Some(settings))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), ManagerError> {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).map_err(|err| {
+                ManagerError::Other(format!("Failed to create settings directory: {err}"))
+            })?;
+        }
+
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| ManagerError::Other(format!("Failed to serialize settings: {err}")))?;
+
+        // Write to a temporary file first, so a crash while saving never leaves a truncated file
+        let temporary_path = path.with_extension("tmp");
+        fs::write(&temporary_path, content)
+            .map_err(|err| ManagerError::Other(format!("Failed to write settings file: {err}")))?;
+        fs::rename(&temporary_path, path).map_err(|err| {
+            ManagerError::Other(format!("Failed to replace settings file: {err}"))
+        })?;
+
+        trace!("Settings file saved at {path:?}");
+        Ok(())
+    }
+}
+
+// Deletes the settings file, used by the `--reset` command line flag
+pub fn reset(path: &Path) -> Result<(), ManagerError> {
+    if !path.exists() {
+        return Ok(());
+    }
+    fs::remove_file(path).map_err(|err| {
+        ManagerError::Other(format!("Failed to delete settings file {path:?}: {err}"))
+    })?;
+    info!("Settings file {path:?} deleted");
+    Ok(())
+}
+
+impl DeviceManager {
+    // Collect the settings of every created device, plus the ones that could not be restored yet
+    pub fn settings(&self) -> SettingsFile {
+        let mut devices: Vec<DeviceSettings> = self
+            .device
+            .values()
+            .filter(|device| device.handler.is_some())
+            .map(|device| DeviceSettings {
+                id: device.id,
+                source: device.source.clone(),
+                device_selection: device.device_type.clone(),
+                continuous_mode: match &device.status {
+                    DeviceStatus::Error => self.status_before_error(device.id),
+                    status => Some(status),
+                } == Some(&DeviceStatus::ContinuousMode),
+                ping360_config: match &device.properties {
+                    Some(DeviceProperties::Ping360(properties)) => properties
+                        .continuous_mode_settings
+                        .read()
+                        .ok()
+                        .map(|config| *config),
+                    _ => None,
+                },
+                ping1d_distance_filter: match &device.properties {
+                    Some(DeviceProperties::Ping1D(properties)) => {
+                        properties.distance_filter.read().ok().map(|config| *config)
+                    }
+                    _ => None,
+                },
+                ping1d_rangefinder: match &device.properties {
+                    Some(DeviceProperties::Ping1D(properties)) => {
+                        properties.rangefinder.read().ok().map(|config| *config)
+                    }
+                    _ => None,
+                },
+                ping360_scan_plans: match &device.properties {
+                    Some(DeviceProperties::Ping360(properties)) => {
+                        properties.scan_plans.read().ok().map(|plans| plans.clone())
+                    }
+                    _ => None,
+                },
+                ping360_speed_of_sound: match &device.properties {
+                    Some(DeviceProperties::Ping360(properties)) => {
+                        properties.speed_of_sound.read().ok().map(|speed| *speed)
+                    }
+                    _ => None,
+                },
+            })
+            .collect();
+
+        devices.extend(
+            self.pending_settings
+                .iter()
+                .filter(|pending| !self.device.contains_key(&pending.id))
+                .cloned(),
+        );
+
+        SettingsFile {
+            version: SETTINGS_VERSION,
+            devices,
+            speed_of_sound: Some(self.speed_of_sound.config),
+        }
+    }
+
+    pub fn save_settings(&self) {
+        let Some(path) = &self.settings_path else {
+            return;
+        };
+
+        if let Err(err) = self.settings().save(path) {
+            error!("DeviceManager: Failed to save settings, details: {err:?}");
+        }
+    }
+
+    // Recreate all devices stored on settings file and restore their continuous mode state
+    pub async fn restore_settings(&mut self) -> Result<Answer, ManagerError> {
+        let Some(path) = self.settings_path.clone() else {
+            return Ok(Answer::DeviceInfo(Vec::new()));
+        };
+
+        let Some(settings) = SettingsFile::load(&path)? else {
+            return Ok(Answer::DeviceInfo(Vec::new()));
+        };
+
+        if let Some(config) = settings.speed_of_sound {
+            self.restore_speed_of_sound(config);
+        }
+
+        let mut results = Vec::new();
+        for device_settings in settings.devices {
+            match self.restore_device(&device_settings).await {
+                Ok(device_info) => {
+                    trace!("Successfully restored device: {device_info:?}");
+                    results.push(device_info);
+                }
+                Err(err) => {
+                    error!(
+                        "Failed to restore device {}, keeping it on settings, details: {err:?}",
+                        device_settings.id
+                    );
+                    self.pending_settings.push(device_settings);
+                }
+            }
+        }
+
+        self.save_settings();
+
+        Ok(Answer::DeviceInfo(results))
+    }
+
+    async fn restore_device(
+        &mut self,
+        device_settings: &DeviceSettings,
+    ) -> Result<DeviceInfo, ManagerError> {
+        let device_id = match self
+            .create(
+                device_settings.source.clone(),
+                device_settings.device_selection.clone(),
+            )
+            .await
+        {
+            Ok(Answer::DeviceInfo(info)) => info.first().map(|info| info.id).ok_or(
+                ManagerError::Other("Unexpected empty answer during restore".to_string()),
+            )?,
+            Ok(unexpected) => {
+                return Err(ManagerError::Other(format!(
+                    "Unexpected response during restore: {unexpected:?}"
+                )))
+            }
+            Err(ManagerError::DeviceAlreadyExist(device_id)) => device_id,
+            Err(err) => return Err(err),
+        };
+
+        if let Some(config) = device_settings.ping360_config {
+            // A configuration saved by an older version may not pass validation anymore
+            match self.update_ping360_config(device_id, config).await {
+                Err(ManagerError::InvalidConfig(fields)) => {
+                    warn!("Ignoring saved Ping360Config of device {device_id}: {fields:?}")
+                }
+                result => result?,
+            }
+        }
+
+        if let Some(config) = device_settings.ping1d_distance_filter {
+            self.update_ping1d_distance_filter(device_id, config)?;
+        }
+
+        if let Some(config) = device_settings.ping1d_rangefinder {
+            self.update_ping1d_rangefinder(device_id, config)?;
+        }
+
+        if let Some(speed_of_sound) = device_settings.ping360_speed_of_sound {
+            self.update_ping360_speed_of_sound(device_id, speed_of_sound)?;
+        }
+
+        if let Some(plans) = &device_settings.ping360_scan_plans {
+            self.update_ping360_scan_plans(device_id, plans.clone())?;
+        }
+
+        if !device_settings.continuous_mode
+            && self.get_device_status(device_id)? == DeviceStatus::ContinuousMode
+        {
+            self.continuous_mode_off(device_id).await?;
+        }
+
+        if device_settings.ping360_scan_plans.is_some() {
+            if let Err(error) = self.resume_ping360_scan_plan(device_id).await {
+                warn!("Failed to resume scan plan for device {device_id}: {error:?}");
+            }
+        }
+
+        Ok(self.get_device(device_id)?.info())
+    }
+
+    // Devices removed by the user should not come back from the pending list
+    pub fn forget_settings(&mut self, device_id: Uuid) {
+        self.pending_settings
+            .retain(|pending| pending.id != device_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::manager::SourceUdpStruct;
+    use std::net::Ipv4Addr;
+
+    fn settings_sample() -> SettingsFile {
+        SettingsFile {
+            version: SETTINGS_VERSION,
+            devices: vec![DeviceSettings {
+                id: Uuid::from_u128(1),
+                source: SourceSelection::UdpStream(SourceUdpStruct {
+                    ip: Ipv4Addr::new(192, 168, 2, 2),
+                    port: 12345,
+                }),
+                device_selection: DeviceSelection::Ping360,
+                continuous_mode: true,
+                ping360_config: Some(Ping360Config {
+                    mode: 1,
+                    gain_setting: 0,
+                    transmit_duration: 32,
+                    sample_period: 80,
+                    transmit_frequency: 740,
+                    number_of_samples: 1200,
+                    start_angle: 0,
+                    stop_angle: 399,
+                    num_steps: 1,
+                    delay: 0,
+                }),
+                ping1d_distance_filter: None,
+                ping1d_rangefinder: None,
+                ping360_scan_plans: Some(ScanPlans {
+                    plans: vec![crate::device::manager::scan_plan::ScanPlan {
+                        name: "survey".to_string(),
+                        sectors: vec![crate::device::manager::scan_plan::ScanSector {
+                            start_angle: 350,
+                            stop_angle: 50,
+                            num_steps: 2,
+                            gain_setting: 1,
+                            transmit_duration: 32,
+                            sample_period: 80,
+                            number_of_samples: 1200,
+                            repeat: 3,
+                        }],
+                        interval_s: Some(60),
+                    }],
+                    active: Some("survey".to_string()),
+                }),
+                ping360_speed_of_sound: Some(1480.0),
+            }],
+            speed_of_sound: Some(SpeedOfSoundConfig {
+                enabled: true,
+                salinity: 0.0,
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn test_settings_round_trip() {
+        let path = std::env::temp_dir().join(format!("settings_{}.json", Uuid::new_v4()));
+
+        let settings = settings_sample();
+        settings.save(&path).unwrap();
+        assert_eq!(SettingsFile::load(&path).unwrap(), Some(settings));
+
+        reset(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(SettingsFile::load(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn test_settings_version_mismatch() {
+        let path = std::env::temp_dir().join(format!("settings_{}.json", Uuid::new_v4()));
+
+        let mut settings = settings_sample();
+        settings.version = SETTINGS_VERSION + 1;
+        settings.save(&path).unwrap();
+        assert_eq!(SettingsFile::load(&path).unwrap(), None);
+
+        reset(&path).unwrap();
+    }
+}
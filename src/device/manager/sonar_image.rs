@@ -0,0 +1,384 @@
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use lazy_static::lazy_static;
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{error, trace};
+use uuid::Uuid;
+
+use super::Answer;
+
+/// Number of angular positions of a Ping360 head, one per gradian.
+pub const GRADIANS: usize = 400;
+pub const MIN_IMAGE_SIZE: u32 = 16;
+pub const MAX_IMAGE_SIZE: u32 = 4096;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub enum Colormap {
+    #[default]
+    Grayscale,
+    Ocean,
+    Thermal,
+}
+
+impl Colormap {
+    // Color stops from the weakest to the strongest echo
+    fn stops(&self) -> &'static [[u8; 3]] {
+        match self {
+            Colormap::Grayscale => &[[0, 0, 0], [255, 255, 255]],
+            Colormap::Ocean => &[[0, 0, 0], [0, 40, 120], [0, 170, 210], [255, 255, 255]],
+            Colormap::Thermal => &[
+                [0, 0, 0],
+                [90, 0, 140],
+                [220, 40, 40],
+                [255, 200, 0],
+                [255, 255, 255],
+            ],
+        }
+    }
+
+    fn channels(&self) -> usize {
+        match self {
+            Colormap::Grayscale => 1,
+            _ => 3,
+        }
+    }
+
+    fn push_pixel(&self, value: u8, pixels: &mut Vec<u8>) {
+        if *self == Colormap::Grayscale {
+            pixels.push(value);
+            return;
+        }
+
+        let stops = self.stops();
+
+        let position = value as f32 / 255.0 * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let fraction = position - index as f32;
+        let (low, high) = (stops[index], stops[index + 1]);
+        for channel in 0..3 {
+            let color =
+                low[channel] as f32 + (high[channel] as f32 - low[channel] as f32) * fraction;
+            pixels.push(color.round() as u8);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+#[serde(default)]
+pub struct SonarImageSettings {
+    /// Width and height of the rendered image, in pixels
+    pub size: u32,
+    pub colormap: Colormap,
+    /// Push the updated region of the image over the websocket as new data arrives
+    pub tiles: bool,
+}
+
+impl Default for SonarImageSettings {
+    fn default() -> Self {
+        Self {
+            size: 512,
+            colormap: Colormap::default(),
+            tiles: true,
+        }
+    }
+}
+
+impl SonarImageSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_IMAGE_SIZE..=MAX_IMAGE_SIZE).contains(&self.size) {
+            return Err(format!(
+                "Invalid image size {}, expected a value between {MIN_IMAGE_SIZE} and {MAX_IMAGE_SIZE}",
+                self.size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A rectangular region of the sonar image, encoded as PNG.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SonarImageTile {
+    pub device_id: Uuid,
+    /// First gradian updated by the data that generated this tile
+    pub angle: u16,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the full image the tile belongs to
+    pub image_size: u32,
+    pub colormap: Colormap,
+    /// PNG encoded as base64
+    pub png: String,
+}
+
+// Latest echo intensities for each gradian, the sector image is built from them
+#[derive(Debug, Clone)]
+pub struct ScanBuffer {
+    lines: Vec<Option<Arc<Vec<u8>>>>,
+}
+
+impl Default for ScanBuffer {
+    fn default() -> Self {
+        Self {
+            lines: vec![None; GRADIANS],
+        }
+    }
+}
+
+impl ScanBuffer {
+    // Store a new line, it also covers the following gradians skipped by the step size
+    pub fn update(&mut self, angle: u16, steps: u8, data: &[u8]) {
+        let line = Arc::new(data.to_vec());
+        for offset in 0..(steps.max(1) as usize) {
+            self.lines[(angle as usize + offset) % GRADIANS] = Some(line.clone());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.iter_mut().for_each(|line| *line = None);
+    }
+
+    // Echo intensity at a pixel position, lines are always stretched to the image radius,
+    // so the image keeps the scale of the current range
+    fn sample(&self, size: u32, x: u32, y: u32) -> u8 {
+        let radius = size as f32 / 2.0;
+        let dx = x as f32 + 0.5 - radius;
+        let dy = y as f32 + 0.5 - radius;
+        let distance = (dx * dx + dy * dy).sqrt() / radius;
+        if distance >= 1.0 {
+            return 0;
+        }
+
+        // Gradian 0 points up and angles grow clockwise, as seen from above the vehicle
+        let angle = dx.atan2(-dy).rem_euclid(2.0 * PI);
+        let gradian = ((angle * GRADIANS as f32 / (2.0 * PI)) as usize).min(GRADIANS - 1);
+
+        match &self.lines[gradian] {
+            Some(line) if !line.is_empty() => {
+                line[((distance * line.len() as f32) as usize).min(line.len() - 1)]
+            }
+            _ => 0,
+        }
+    }
+
+    pub fn render_region(
+        &self,
+        settings: &SonarImageSettings,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Vec<u8> {
+        let mut pixels =
+            Vec::with_capacity((width * height) as usize * settings.colormap.channels());
+        for row in y..y + height {
+            for column in x..x + width {
+                settings
+                    .colormap
+                    .push_pixel(self.sample(settings.size, column, row), &mut pixels);
+            }
+        }
+        pixels
+    }
+
+    pub fn render_png(&self, settings: &SonarImageSettings) -> Result<Vec<u8>, String> {
+        let pixels = self.render_region(settings, 0, 0, settings.size, settings.size);
+        encode_png(settings, settings.size, settings.size, &pixels)
+    }
+
+    // Render the region covered by a new line, from the center up to the image border
+    pub fn render_tile(
+        &self,
+        device_id: Uuid,
+        settings: &SonarImageSettings,
+        angle: u16,
+        steps: u8,
+    ) -> Result<SonarImageTile, String> {
+        let (x, y, width, height) = wedge_bounds(settings.size, angle, steps);
+        let pixels = self.render_region(settings, x, y, width, height);
+        let png = encode_png(settings, width, height, &pixels)?;
+
+        Ok(SonarImageTile {
+            device_id,
+            angle,
+            x,
+            y,
+            width,
+            height,
+            image_size: settings.size,
+            colormap: settings.colormap,
+            png: STANDARD.encode(png),
+        })
+    }
+}
+
+// Bounding box (x, y, width, height) of the wedge between `angle` and `angle + steps` gradians
+fn wedge_bounds(size: u32, angle: u16, steps: u8) -> (u32, u32, u32, u32) {
+    let radius = size as f32 / 2.0;
+    let start = angle as f32;
+    let end = start + steps.max(1) as f32;
+
+    // The wedge extremes are its border lines and any axis crossed by the arc
+    let mut gradians = vec![start, end];
+    let mut axis = (start / 100.0).ceil() * 100.0;
+    while axis < end {
+        gradians.push(axis);
+        axis += 100.0;
+    }
+
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (radius, radius, radius, radius);
+    for gradian in gradians {
+        let theta = gradian * 2.0 * PI / GRADIANS as f32;
+        let x = radius + radius * theta.sin();
+        let y = radius - radius * theta.cos();
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+
+    let clamp = |value: f32| (value.max(0.0) as u32).min(size);
+    let (x, y) = (clamp(min_x.floor()), clamp(min_y.floor()));
+    let width = (clamp(max_x.ceil()) - x).max(1).min(size - x);
+    let height = (clamp(max_y.ceil()) - y).max(1).min(size - y);
+    (x, y, width, height)
+}
+
+fn encode_png(
+    settings: &SonarImageSettings,
+    width: u32,
+    height: u32,
+    pixels: &[u8],
+) -> Result<Vec<u8>, String> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(match settings.colormap.channels() {
+        1 => png::ColorType::Grayscale,
+        _ => png::ColorType::Rgb,
+    });
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer
+        .write_image_data(pixels)
+        .map_err(|err| err.to_string())?;
+    writer.finish().map_err(|err| err.to_string())?;
+
+    Ok(png)
+}
+
+#[derive(Debug, Default)]
+struct SonarImage {
+    scan: ScanBuffer,
+    settings: SonarImageSettings,
+}
+
+lazy_static! {
+    // Continuous mode tasks write here while the REST server reads, without going through the manager
+    static ref SONAR_IMAGES: Mutex<HashMap<Uuid, SonarImage>> = Mutex::new(HashMap::new());
+}
+
+// Feed a new Ping360 line and push the updated tile to websocket clients
+pub fn update(device_id: Uuid, angle: u16, steps: u8, data: &[u8]) {
+    let tile = {
+        let Ok(mut images) = SONAR_IMAGES.lock() else {
+            error!("Sonar image: failed to lock scan buffers, device: {device_id}");
+            return;
+        };
+        let image = images.entry(device_id).or_default();
+        image.scan.update(angle, steps, data);
+
+        if !image.settings.tiles || !crate::server::protocols::v1::websocket::has_clients() {
+            return;
+        }
+        image
+            .scan
+            .render_tile(device_id, &image.settings, angle, steps)
+    };
+
+    match tile {
+        Ok(tile) => crate::server::protocols::v1::websocket::send_to_websockets(
+            json!(Answer::SonarImageTile(tile)),
+            Some(device_id),
+        ),
+        Err(err) => trace!("Sonar image: failed to render tile: {err}, device: {device_id}"),
+    }
+}
+
+pub fn remove(device_id: Uuid) {
+    if let Ok(mut images) = SONAR_IMAGES.lock() {
+        images.remove(&device_id);
+    }
+}
+
+pub fn get_settings(device_id: Uuid) -> Option<SonarImageSettings> {
+    SONAR_IMAGES
+        .lock()
+        .ok()?
+        .get(&device_id)
+        .map(|image| image.settings)
+}
+
+pub fn set_settings(device_id: Uuid, settings: SonarImageSettings) -> Result<(), String> {
+    settings.validate()?;
+    let mut images = SONAR_IMAGES.lock().map_err(|err| err.to_string())?;
+    images.entry(device_id).or_default().settings = settings;
+    Ok(())
+}
+
+// Copy of the device scan and settings, so rendering happens without holding the lock
+pub fn snapshot(device_id: Uuid) -> Option<(ScanBuffer, SonarImageSettings)> {
+    SONAR_IMAGES
+        .lock()
+        .ok()?
+        .get(&device_id)
+        .map(|image| (image.scan.clone(), image.settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sonar_image_orientation() {
+        let settings = SonarImageSettings {
+            size: 64,
+            colormap: Colormap::Grayscale,
+            tiles: false,
+        };
+        let mut scan = ScanBuffer::default();
+        scan.update(0, 2, &[255; 100]);
+        scan.update(100, 2, &[128; 100]);
+
+        let pixels = scan.render_region(&settings, 0, 0, settings.size, settings.size);
+        let pixel = |x: usize, y: usize| pixels[y * settings.size as usize + x];
+
+        // Gradian 0 is right above the center, gradian 100 is at its right
+        assert_eq!(pixel(32, 4), 255);
+        assert_eq!(pixel(60, 32), 128);
+        // Nothing was received for the left side, and corners are outside of the sector
+        assert_eq!(pixel(4, 32), 0);
+        assert_eq!(pixel(0, 0), 0);
+    }
+
+    #[test]
+    fn test_sonar_image_tile_bounds() {
+        // A single gradian pointing up covers a thin column above the center
+        let (x, y, width, height) = wedge_bounds(400, 0, 1);
+        assert_eq!((x, y), (200, 0));
+        assert!(width <= 4);
+        assert_eq!(height, 200);
+
+        // A wedge crossing the right axis reaches the image border
+        let (x, _, width, _) = wedge_bounds(400, 95, 10);
+        assert_eq!(x + width, 400);
+    }
+}
@@ -0,0 +1,434 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use bluerobotics_ping::ping1d::SetSpeedOfSoundStruct;
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{Answer, DeviceManager, DeviceProperties, DeviceStatus, ManagerError};
+use crate::device::devices::{Ping1DRequest, PingRequest};
+
+pub const SPEED_OF_SOUND_UPDATE_INTERVAL: Duration = Duration::from_secs(5);
+// Same timeout used by the status check before marking a device with error
+const SET_SPEED_OF_SOUND_TIMEOUT: Duration = Duration::from_millis(2000);
+// Vehicle measurements older than this are replaced by the configured ones
+const VEHICLE_DATA_MAX_AGE_MS: i64 = 10_000;
+
+// Validity range of both models, a bit wider for water close to freezing
+const MIN_TEMPERATURE: f32 = -2.0;
+const MAX_TEMPERATURE: f32 = 40.0;
+
+const WATER_DENSITY: f64 = 1025.0;
+const GRAVITY: f64 = 9.80665;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub enum SpeedOfSoundModel {
+    /// Mackenzie (1981), from temperature, salinity and depth
+    #[default]
+    Mackenzie,
+    /// UNESCO, Chen and Millero (1977) with the Wong and Zhu (1995) ITS-90 coefficients
+    Unesco,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub enum SpeedOfSoundSource {
+    /// Temperature and depth from the vehicle, falling back to the configured ones
+    #[default]
+    Vehicle,
+    /// Only the configured temperature and depth
+    Manual,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema, Validate)]
+#[serde(default)]
+pub struct SpeedOfSoundConfig {
+    /// Apply the computed speed of sound to every Ping1D and Ping360
+    pub enabled: bool,
+    pub model: SpeedOfSoundModel,
+    pub source: SpeedOfSoundSource,
+    /// Salinity in PSU, around 35 for sea water and 0 for fresh water
+    #[validate(range(min = 0.0, max = 45.0))]
+    pub salinity: f32,
+    /// Water temperature in degrees Celsius
+    #[validate(range(min = MIN_TEMPERATURE, max = MAX_TEMPERATURE))]
+    pub temperature: f32,
+    /// Depth in meters
+    #[validate(range(min = 0.0, max = 11_000.0))]
+    pub depth: f32,
+    /// Smallest change, in meters per second, sent to the devices
+    #[validate(range(min = 0.0))]
+    pub threshold: f32,
+}
+
+impl Default for SpeedOfSoundConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            model: SpeedOfSoundModel::default(),
+            source: SpeedOfSoundSource::default(),
+            salinity: 35.0,
+            temperature: 15.0,
+            depth: 0.0,
+            threshold: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SpeedOfSoundReading {
+    /// Speed of sound in meters per second
+    pub speed_of_sound: f32,
+    /// Temperature and depth used to compute it
+    pub temperature: f32,
+    pub depth: f32,
+    /// True when the vehicle provided the temperature or the depth
+    pub from_vehicle: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeedOfSoundStatus {
+    pub config: SpeedOfSoundConfig,
+    /// Latest computation, empty until the service is enabled
+    pub latest: Option<SpeedOfSoundReading>,
+    /// Speed of sound last applied to each device
+    pub devices: HashMap<Uuid, f32>,
+}
+
+#[derive(Debug, Default)]
+pub struct SpeedOfSoundService {
+    pub config: SpeedOfSoundConfig,
+    latest: Option<SpeedOfSoundReading>,
+    applied: HashMap<Uuid, f32>,
+    // Devices with a request still running in the background
+    pending: HashSet<Uuid>,
+}
+
+/// Outcome of sending the speed of sound to a device, sent back to the manager
+pub struct SpeedOfSoundUpdate {
+    device_id: Uuid,
+    speed_of_sound: f32,
+    result: Result<(), ManagerError>,
+}
+
+/// Mackenzie (1981), valid from 2 to 30 °C, 25 to 40 PSU and 0 to 8000 m
+pub fn mackenzie(temperature: f64, salinity: f64, depth: f64) -> f64 {
+    let t = temperature;
+    let s = salinity - 35.0;
+    let d = depth;
+    1448.96 + 4.591 * t - 5.304e-2 * t.powi(2)
+        + 2.374e-4 * t.powi(3)
+        + 1.340 * s
+        + 1.630e-2 * d
+        + 1.675e-7 * d.powi(2)
+        - 1.025e-2 * t * s
+        - 7.139e-13 * t * d.powi(3)
+}
+
+fn polynomial(x: f64, coefficients: &[f64]) -> f64 {
+    coefficients.iter().rev().fold(0.0, |sum, c| sum * x + c)
+}
+
+/// UNESCO equation, valid from 0 to 40 °C, 0 to 40 PSU and 0 to 1000 bar
+pub fn unesco(temperature: f64, salinity: f64, pressure_bar: f64) -> f64 {
+    let t = temperature;
+    let p = pressure_bar;
+    let s = salinity;
+
+    let cw = polynomial(
+        p,
+        &[
+            polynomial(
+                t,
+                &[
+                    1402.388,
+                    5.03830,
+                    -5.81090e-2,
+                    3.3432e-4,
+                    -1.47797e-6,
+                    3.1419e-9,
+                ],
+            ),
+            polynomial(
+                t,
+                &[0.153563, 6.8999e-4, -8.1829e-6, 1.3632e-7, -6.1260e-10],
+            ),
+            polynomial(
+                t,
+                &[3.1260e-5, -1.7111e-6, 2.5986e-8, -2.5353e-10, 1.0415e-12],
+            ),
+            polynomial(t, &[-9.7729e-9, 3.8513e-10, -2.3654e-12]),
+        ],
+    );
+    let a = polynomial(
+        p,
+        &[
+            polynomial(t, &[1.389, -1.262e-2, 7.166e-5, 2.008e-6, -3.21e-8]),
+            polynomial(
+                t,
+                &[9.4742e-5, -1.2583e-5, -6.4928e-8, 1.0515e-8, -2.0142e-10],
+            ),
+            polynomial(t, &[-3.9064e-7, 9.1061e-9, -1.6009e-10, 7.994e-12]),
+            polynomial(t, &[1.100e-10, 6.651e-12, -3.391e-13]),
+        ],
+    );
+    let b = -1.922e-2 - 4.42e-5 * t + (7.3637e-5 + 1.7950e-7 * t) * p;
+    let d = 1.727e-3 - 7.9836e-6 * p;
+
+    cw + a * s + b * s.powf(1.5) + d * s.powi(2)
+}
+
+/// Water column pressure at a depth, in bar
+pub fn depth_pressure_bar(depth: f64) -> f64 {
+    depth * WATER_DENSITY * GRAVITY / 1e5
+}
+
+impl SpeedOfSoundModel {
+    pub fn speed_of_sound(&self, temperature: f32, salinity: f32, depth: f32) -> f32 {
+        let (temperature, salinity, depth) = (temperature as f64, salinity as f64, depth as f64);
+        match self {
+            Self::Mackenzie => mackenzie(temperature, salinity, depth) as f32,
+            Self::Unesco => unesco(temperature, salinity, depth_pressure_bar(depth)) as f32,
+        }
+    }
+}
+
+impl SpeedOfSoundService {
+    fn compute(&mut self) -> f32 {
+        let config = &self.config;
+        let pose = match config.source {
+            SpeedOfSoundSource::Vehicle => crate::vehicle::latest_pose().filter(|pose| {
+                chrono::Utc::now().timestamp_millis() - pose.timestamp < VEHICLE_DATA_MAX_AGE_MS
+            }),
+            SpeedOfSoundSource::Manual => None,
+        };
+        let temperature = pose.as_ref().and_then(|pose| pose.pose.water_temperature);
+        let depth = pose.as_ref().and_then(|pose| pose.pose.depth);
+
+        let from_vehicle = temperature.is_some() || depth.is_some();
+        let temperature = temperature.unwrap_or(config.temperature);
+        let depth = depth.unwrap_or(config.depth).max(0.0);
+        let speed_of_sound = config
+            .model
+            .speed_of_sound(temperature, config.salinity, depth);
+
+        self.latest = Some(SpeedOfSoundReading {
+            speed_of_sound,
+            temperature,
+            depth,
+            from_vehicle,
+        });
+        speed_of_sound
+    }
+
+    pub fn status(&self) -> SpeedOfSoundStatus {
+        SpeedOfSoundStatus {
+            config: self.config,
+            latest: self.latest,
+            devices: self.applied.clone(),
+        }
+    }
+}
+
+impl DeviceManager {
+    pub fn get_speed_of_sound(&self) -> Result<Answer, ManagerError> {
+        Ok(Answer::SpeedOfSound(self.speed_of_sound.status()))
+    }
+
+    pub async fn set_speed_of_sound_config(
+        &mut self,
+        config: SpeedOfSoundConfig,
+    ) -> Result<Answer, ManagerError> {
+        config.validate()?;
+        self.speed_of_sound.config = config;
+        // Devices are updated right away with the new configuration
+        self.speed_of_sound.applied.clear();
+        self.update_speed_of_sound().await;
+        self.get_speed_of_sound()
+    }
+
+    // Send the speed of sound to the devices that are behind by more than the threshold
+    pub async fn update_speed_of_sound(&mut self) {
+        self.speed_of_sound
+            .applied
+            .retain(|device_id, _| self.device.contains_key(device_id));
+        if !self.speed_of_sound.config.enabled {
+            return;
+        }
+
+        let speed_of_sound = self.speed_of_sound.compute();
+        let threshold = self.speed_of_sound.config.threshold;
+
+        let device_ids: Vec<Uuid> = self
+            .device
+            .values()
+            .filter(|device| {
+                matches!(
+                    device.status,
+                    DeviceStatus::Running | DeviceStatus::ContinuousMode
+                )
+            })
+            .map(|device| device.id)
+            .filter(|device_id| !self.speed_of_sound.pending.contains(device_id))
+            .filter(|device_id| {
+                self.speed_of_sound
+                    .applied
+                    .get(device_id)
+                    .is_none_or(|applied| (applied - speed_of_sound).abs() >= threshold)
+            })
+            .collect();
+
+        for device_id in device_ids {
+            if let Err(err) = self.apply_speed_of_sound(device_id, speed_of_sound).await {
+                self.finish_speed_of_sound(SpeedOfSoundUpdate {
+                    device_id,
+                    speed_of_sound,
+                    result: Err(err),
+                });
+            }
+        }
+    }
+
+    // Ping1D requests run in the background, so a slow device doesn't hold the manager
+    async fn apply_speed_of_sound(
+        &mut self,
+        device_id: Uuid,
+        speed_of_sound: f32,
+    ) -> Result<(), ManagerError> {
+        let device = self.get_device(device_id)?;
+        match &device.properties {
+            Some(DeviceProperties::Ping1D(_)) => {
+                let Some(handler) = device.handler.clone() else {
+                    return Err(ManagerError::Other(format!(
+                        "No handler available for device {device_id}"
+                    )));
+                };
+                let request =
+                    PingRequest::Ping1D(Ping1DRequest::SetSpeedOfSound(SetSpeedOfSoundStruct {
+                        // Ping1D expects millimeters per second
+                        speed_of_sound: (speed_of_sound * 1000.0).round() as u32,
+                    }));
+
+                self.speed_of_sound.pending.insert(device_id);
+                let sender = self.speed_of_sound_sender.clone();
+                tokio::spawn(async move {
+                    let result = match tokio::time::timeout(
+                        SET_SPEED_OF_SOUND_TIMEOUT,
+                        handler.send(request),
+                    )
+                    .await
+                    {
+                        Ok(Ok(_)) => Ok(()),
+                        Ok(Err(err)) => Err(ManagerError::DeviceError(err)),
+                        Err(_) => Err(ManagerError::Other(format!(
+                            "Timeout while setting speed of sound of device {device_id}"
+                        ))),
+                    };
+                    let update = SpeedOfSoundUpdate {
+                        device_id,
+                        speed_of_sound,
+                        result,
+                    };
+                    if sender.send(update).await.is_err() {
+                        warn!("DeviceManager stopped before speed of sound of device {device_id} was applied");
+                    }
+                });
+                Ok(())
+            }
+            Some(DeviceProperties::Ping360(_)) => {
+                let result = self
+                    .update_ping360_range_speed_of_sound(device_id, speed_of_sound)
+                    .await;
+                self.finish_speed_of_sound(SpeedOfSoundUpdate {
+                    device_id,
+                    speed_of_sound,
+                    result,
+                });
+                Ok(())
+            }
+            _ => Err(ManagerError::DeviceSourceError(
+                "apply_speed_of_sound: Can't set speed of sound".to_string(),
+            )),
+        }
+    }
+
+    pub fn finish_speed_of_sound(&mut self, update: SpeedOfSoundUpdate) {
+        let SpeedOfSoundUpdate {
+            device_id,
+            speed_of_sound,
+            result,
+        } = update;
+        self.speed_of_sound.pending.remove(&device_id);
+        match result {
+            Ok(()) => {
+                debug!("Speed of sound of {speed_of_sound:.1} m/s applied to device {device_id}");
+                // The device may have been deleted while the request was running
+                if self.device.contains_key(&device_id) {
+                    self.speed_of_sound
+                        .applied
+                        .insert(device_id, speed_of_sound);
+                }
+            }
+            Err(err) => warn!("Failed to apply speed of sound to device {device_id}: {err:?}"),
+        }
+    }
+
+    pub fn restore_speed_of_sound(&mut self, config: SpeedOfSoundConfig) {
+        match config.validate() {
+            Ok(()) => {
+                info!("Speed of sound configuration restored: {config:?}");
+                self.speed_of_sound.config = config;
+            }
+            Err(err) => warn!("Ignoring saved speed of sound configuration: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mackenzie() {
+        // Check value from Mackenzie (1981)
+        assert!((mackenzie(25.0, 35.0, 1000.0) - 1550.744).abs() < 0.001);
+        let model = SpeedOfSoundModel::Mackenzie;
+        assert!((model.speed_of_sound(10.0, 35.0, 0.0) - 1489.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_unesco() {
+        assert!((unesco(0.0, 0.0, 0.0) - 1402.388).abs() < 1e-6);
+        assert!((unesco(40.0, 40.0, 1000.0) - 1732.0).abs() < 0.1);
+        // Both models agree close to the surface
+        let model = SpeedOfSoundModel::Unesco;
+        assert!((model.speed_of_sound(10.0, 35.0, 0.0) - 1489.8).abs() < 0.1);
+        assert!((model.speed_of_sound(25.0, 35.0, 1000.0) - 1550.7).abs() < 1.0);
+    }
+
+    #[test]
+    fn test_manual_source() {
+        let mut service = SpeedOfSoundService {
+            config: SpeedOfSoundConfig {
+                enabled: true,
+                source: SpeedOfSoundSource::Manual,
+                salinity: 0.0,
+                temperature: 15.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(service.status().latest, None);
+
+        let speed_of_sound = service.compute();
+        assert!((speed_of_sound - 1465.2).abs() < 0.1);
+        let latest = service.status().latest.unwrap();
+        assert_eq!(latest.speed_of_sound, speed_of_sound);
+        assert_eq!(latest.temperature, 15.0);
+        assert!(!latest.from_vehicle);
+    }
+}
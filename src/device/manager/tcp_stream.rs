@@ -0,0 +1,230 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddrV4,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use lazy_static::lazy_static;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream},
+    net::TcpStream,
+    sync::mpsc,
+    time::{sleep_until, timeout, Instant},
+};
+use tracing::{debug, info, trace, warn};
+
+use super::{ManagerError, SourceTcpStruct};
+
+// Size of the in-memory pipe between the TCP bridge task and the device
+const TCP_BUFFER_SIZE: usize = 64 * 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_millis(100);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    // Bridges by address, devices often accept a single connection so anything written to them
+    // outside of their driver goes through the open one
+    static ref BRIDGES: Mutex<HashMap<SocketAddrV4, mpsc::Sender<Vec<u8>>>> =
+        Mutex::new(HashMap::new());
+}
+
+// Open a TCP source, the returned stream survives connection drops: a background task keeps
+// reconnecting to the same address while the device is alive.
+pub async fn connect(source: &SourceTcpStruct) -> Result<DuplexStream, ManagerError> {
+    let address = SocketAddrV4::new(source.ip, source.port);
+
+    // The first connection should succeed, so unreachable hosts are reported during creation
+    let stream = open(address).await?;
+
+    let (device_port, bridge_port) = tokio::io::duplex(TCP_BUFFER_SIZE);
+    let (sender, receiver) = mpsc::channel(8);
+    BRIDGES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(address, sender.clone());
+    tokio::spawn(async move {
+        run_bridge(address, stream, bridge_port, receiver).await;
+        let mut bridges = BRIDGES.lock().unwrap_or_else(PoisonError::into_inner);
+        // A newer bridge may have replaced this one
+        if bridges
+            .get(&address)
+            .is_some_and(|current| current.same_channel(&sender))
+        {
+            bridges.remove(&address);
+        }
+    });
+
+    Ok(device_port)
+}
+
+// Write to a TCP source through the connection of its bridge, next to its driver requests
+pub async fn write(source: &SourceTcpStruct, data: Vec<u8>) -> Result<(), ManagerError> {
+    let address = SocketAddrV4::new(source.ip, source.port);
+    let sender = BRIDGES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&address)
+        .cloned()
+        .ok_or_else(|| {
+            ManagerError::DeviceSourceError(format!("No open connection to {address}"))
+        })?;
+    sender
+        .send(data)
+        .await
+        .map_err(|_| ManagerError::DeviceSourceError(format!("Connection to {address} was closed")))
+}
+
+async fn open(address: SocketAddrV4) -> Result<TcpStream, ManagerError> {
+    let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+        .await
+        .map_err(|_| {
+            ManagerError::DeviceSourceError(format!("Timeout while connecting to {address}"))
+        })?
+        .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
+
+    // Ping messages are small request/answer pairs, don't wait to fill TCP segments
+    if let Err(err) = stream.set_nodelay(true) {
+        debug!("TCP source {address}: failed to set nodelay: {err}");
+    }
+
+    Ok(stream)
+}
+
+enum BridgeEnd {
+    DeviceClosed,
+    ConnectionDropped,
+}
+
+async fn run_bridge(
+    address: SocketAddrV4,
+    stream: TcpStream,
+    port: DuplexStream,
+    mut writes: mpsc::Receiver<Vec<u8>>,
+) {
+    let (mut port_reader, mut port_writer) = tokio::io::split(port);
+    let mut connection = Some(stream);
+    let mut retry_delay = RECONNECT_INITIAL_DELAY;
+    let mut next_attempt = Instant::now();
+    let mut port_buffer = [0u8; 1024];
+    let mut tcp_buffer = [0u8; 4096];
+
+    loop {
+        let Some(mut stream) = connection.take() else {
+            tokio::select! {
+                _ = sleep_until(next_attempt) => {
+                    match open(address).await {
+                        Ok(stream) => {
+                            info!("TCP source {address}: reconnected");
+                            connection = Some(stream);
+                            retry_delay = RECONNECT_INITIAL_DELAY;
+                        }
+                        Err(err) => {
+                            trace!("TCP source {address}: reconnection failed, retrying in {retry_delay:?}: {err:?}");
+                            next_attempt = Instant::now() + retry_delay;
+                            retry_delay = (retry_delay * 2).min(RECONNECT_MAX_DELAY);
+                        }
+                    }
+                }
+                // Requests done while disconnected are dropped, the device will time out on them
+                read = port_reader.read(&mut port_buffer) => match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(size) => trace!("TCP source {address}: dropping {size} bytes while disconnected"),
+                },
+                Some(data) = writes.recv() => {
+                    trace!("TCP source {address}: dropping {} bytes while disconnected", data.len());
+                }
+            }
+            continue;
+        };
+
+        let (mut tcp_reader, mut tcp_writer) = stream.split();
+        let end = loop {
+            tokio::select! {
+                read = tcp_reader.read(&mut tcp_buffer) => match read {
+                    Ok(0) | Err(_) => break BridgeEnd::ConnectionDropped,
+                    Ok(size) => {
+                        if port_writer.write_all(&tcp_buffer[..size]).await.is_err() {
+                            break BridgeEnd::DeviceClosed;
+                        }
+                    }
+                },
+                read = port_reader.read(&mut port_buffer) => match read {
+                    Ok(0) | Err(_) => break BridgeEnd::DeviceClosed,
+                    Ok(size) => {
+                        if tcp_writer.write_all(&port_buffer[..size]).await.is_err() {
+                            break BridgeEnd::ConnectionDropped;
+                        }
+                    }
+                },
+                Some(data) = writes.recv() => {
+                    if tcp_writer.write_all(&data).await.is_err() {
+                        break BridgeEnd::ConnectionDropped;
+                    }
+                }
+            }
+        };
+
+        match end {
+            BridgeEnd::DeviceClosed => break,
+            BridgeEnd::ConnectionDropped => {
+                warn!("TCP source {address}: connection dropped, reconnecting");
+                next_attempt = Instant::now();
+            }
+        }
+    }
+
+    debug!("TCP source {address}: device closed, stopping bridge");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_tcp_source_reconnects() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let mut device = connect(&SourceTcpStruct {
+            ip: Ipv4Addr::LOCALHOST,
+            port,
+        })
+        .await
+        .unwrap();
+
+        let (mut server, _) = listener.accept().await.unwrap();
+        device.write_all(b"first").await.unwrap();
+        let mut buffer = [0u8; 5];
+        server.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"first");
+
+        // Drop the connection on the server side, the bridge should connect again by itself
+        drop(server);
+        let (mut server, _) = timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+
+        server.write_all(b"second").await.unwrap();
+        let mut buffer = [0u8; 6];
+        device.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"second");
+
+        // Writes from outside the driver share the same connection
+        write(
+            &SourceTcpStruct {
+                ip: Ipv4Addr::LOCALHOST,
+                port,
+            },
+            b"stop".to_vec(),
+        )
+        .await
+        .unwrap();
+        let mut buffer = [0u8; 4];
+        server.read_exact(&mut buffer).await.unwrap();
+        assert_eq!(&buffer, b"stop");
+    }
+}
@@ -14,6 +14,22 @@ pub mod devices;
 /// and made available again.
 pub mod manager;
 
+/// The `rangefinder` module sends Ping1D distances to the autopilot as MAVLink
+/// DISTANCE_SENSOR messages, through the Zenoh bridge or a MAVLink connection.
+pub mod rangefinder;
+
 /// The `recording` module provides functionalities for recording device measurements
 /// and managing current recording sessions.
 pub mod recording;
+
+/// The `replay` module provides virtual devices that play back recorded sessions,
+/// feeding the same broadcast subscribers used by live devices.
+pub mod replay;
+
+/// The `simulator` module provides software Ping1D and Ping360 devices, answering
+/// the Ping protocol over a local UDP port or a pty from a synthetic seabed.
+pub mod simulator;
+
+/// The `zenoh_publisher` module publishes sonar data and device status on the Zenoh
+/// session shared with the vehicle bridge, under `ping/<device_id>/<topic>`.
+pub mod zenoh_publisher;
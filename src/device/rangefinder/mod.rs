@@ -0,0 +1,284 @@
+use std::{str::FromStr, sync::RwLock, time::Duration};
+
+use lazy_static::lazy_static;
+use mavlink::{
+    ardupilotmega::{MavDistanceSensor, MavMessage, MavSensorOrientation, DISTANCE_SENSOR_DATA},
+    MavHeader,
+};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc, task::JoinHandle, time::Instant};
+use tracing::{error, info, trace, warn};
+
+use crate::vehicle::ZenohSession;
+
+/// MAV_COMP_ID_PERIPHERAL, ping-viewer-next acts as a generic peripheral driver.
+pub const COMPONENT_ID: u8 = 158;
+
+// Ping1D beam width is about 30 degrees
+const PING1D_FIELD_OF_VIEW: f32 = 30.0 * std::f32::consts::PI / 180.0;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const QUEUE_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RangefinderConfig {
+    /// Send DISTANCE_SENSOR messages for this device
+    pub enabled: bool,
+    pub orientation: MavSensorOrientation,
+    /// Instance of the rangefinder on the autopilot
+    pub sensor_id: u8,
+    pub min_distance_cm: u16,
+    pub max_distance_cm: u16,
+    /// Send the distance from the filtering pipeline instead of the raw one, when available
+    pub use_filtered: bool,
+}
+
+impl Default for RangefinderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            orientation: MavSensorOrientation::MAV_SENSOR_ROTATION_PITCH_270,
+            sensor_id: 0,
+            min_distance_cm: 30,
+            max_distance_cm: 5000,
+            use_filtered: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum RangefinderTransport {
+    /// Any MAVLink connection string, e.g: "udpout:127.0.0.1:14550"
+    Mavlink(String),
+    /// Through the Zenoh MAVLink bridge, on the session shared with the vehicle bridge
+    Zenoh { session: ZenohSession, key: String },
+}
+
+/// Transport chosen from the command line, the Zenoh session is only known at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RangefinderTransportSelection {
+    Mavlink(String),
+    Zenoh,
+}
+
+impl FromStr for RangefinderTransportSelection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.eq_ignore_ascii_case("zenoh") {
+            return Ok(Self::Zenoh);
+        }
+
+        let is_valid = ["udpout:", "udpin:", "udpbcast:", "tcpout:", "tcpin:"]
+            .iter()
+            .any(|prefix| value.starts_with(prefix));
+        if !is_valid {
+            return Err(format!(
+                "Invalid MAVLink connection \"{value}\", expected \"zenoh\" or an address like \"udpout:127.0.0.1:14550\""
+            ));
+        }
+        Ok(Self::Mavlink(value.to_string()))
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    header: MavHeader,
+    message: &'a MavMessage,
+}
+
+lazy_static! {
+    static ref SENDER: RwLock<Option<mpsc::Sender<MavMessage>>> = RwLock::new(None);
+    static ref BOOT_TIME: Instant = Instant::now();
+}
+
+// Build the message for a reading, confidence from 0 to 100 becomes the signal quality,
+// where 0 means unknown and 1 invalid
+pub fn distance_sensor(
+    config: &RangefinderConfig,
+    distance_mm: u32,
+    confidence: u16,
+) -> MavMessage {
+    let distance_cm = (distance_mm / 10).min(u16::MAX as u32) as u16;
+
+    MavMessage::DISTANCE_SENSOR(DISTANCE_SENSOR_DATA {
+        time_boot_ms: BOOT_TIME.elapsed().as_millis() as u32,
+        min_distance: config.min_distance_cm,
+        max_distance: config.max_distance_cm,
+        current_distance: distance_cm,
+        mavtype: MavDistanceSensor::MAV_DISTANCE_SENSOR_ULTRASOUND,
+        id: config.sensor_id,
+        orientation: config.orientation,
+        // Unknown covariance
+        covariance: u8::MAX,
+        horizontal_fov: PING1D_FIELD_OF_VIEW,
+        vertical_fov: PING1D_FIELD_OF_VIEW,
+        signal_quality: confidence.clamp(1, 100) as u8,
+        ..Default::default()
+    })
+}
+
+// Queue a reading to the autopilot, readings are dropped while the transport is busy or not started
+pub fn send(config: &RangefinderConfig, distance_mm: u32, confidence: u16) {
+    if !config.enabled {
+        return;
+    }
+
+    let Ok(sender) = SENDER.read() else {
+        return;
+    };
+    let Some(sender) = sender.as_ref() else {
+        return;
+    };
+
+    if let Err(err) = sender.try_send(distance_sensor(config, distance_mm, confidence)) {
+        trace!("Rangefinder: dropping DISTANCE_SENSOR message: {err}");
+    }
+}
+
+// Messages are sent on behalf of the vehicle system, as a peripheral component
+pub fn start(transport: RangefinderTransport, system_id: u8) -> JoinHandle<()> {
+    let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+    match SENDER.write() {
+        Ok(mut current) => *current = Some(sender),
+        Err(err) => error!("Rangefinder: failed to register sender: {err}"),
+    }
+
+    tokio::spawn(async move {
+        match transport {
+            RangefinderTransport::Mavlink(address) => {
+                run_mavlink(address, system_id, receiver).await
+            }
+            RangefinderTransport::Zenoh { session, key } => {
+                run_zenoh(session, key, system_id, receiver).await
+            }
+        }
+    })
+}
+
+async fn run_mavlink(address: String, system_id: u8, mut receiver: mpsc::Receiver<MavMessage>) {
+    info!("Rangefinder: sending DISTANCE_SENSOR messages to {address}");
+
+    loop {
+        let connection = match mavlink::connect_async::<MavMessage>(&address).await {
+            Ok(connection) => connection,
+            Err(err) => {
+                warn!("Rangefinder: failed to connect to {address}: {err}, retrying in {RECONNECT_DELAY:?}");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+                continue;
+            }
+        };
+
+        let mut sequence: u8 = 0;
+        loop {
+            let Some(message) = receiver.recv().await else {
+                return;
+            };
+
+            let header = MavHeader {
+                system_id,
+                component_id: COMPONENT_ID,
+                sequence,
+            };
+            sequence = sequence.wrapping_add(1);
+
+            if let Err(err) = connection.send(&header, &message).await {
+                warn!("Rangefinder: failed to send to {address}: {err}, reconnecting");
+                break;
+            }
+        }
+    }
+}
+
+async fn run_zenoh(
+    session: ZenohSession,
+    key: String,
+    system_id: u8,
+    mut receiver: mpsc::Receiver<MavMessage>,
+) {
+    info!("Rangefinder: sending DISTANCE_SENSOR messages through Zenoh on {key}");
+
+    let mut sequence: u8 = 0;
+    while let Some(message) = receiver.recv().await {
+        let Some(session) = session.read().await.clone() else {
+            trace!("Rangefinder: Zenoh session not available, dropping message");
+            continue;
+        };
+
+        let envelope = Envelope {
+            header: MavHeader {
+                system_id,
+                component_id: COMPONENT_ID,
+                sequence,
+            },
+            message: &message,
+        };
+        sequence = sequence.wrapping_add(1);
+
+        let payload = match serde_json::to_vec(&envelope) {
+            Ok(payload) => payload,
+            Err(err) => {
+                error!("Rangefinder: failed to serialize message: {err}");
+                continue;
+            }
+        };
+
+        if let Err(err) = session
+            .put(&key, payload)
+            .encoding(zenoh::bytes::Encoding::APPLICATION_JSON)
+            .await
+        {
+            trace!("Rangefinder: failed to put on {key}: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_sensor_message() {
+        let config = RangefinderConfig {
+            enabled: true,
+            sensor_id: 2,
+            ..Default::default()
+        };
+
+        let MavMessage::DISTANCE_SENSOR(data) = distance_sensor(&config, 12_345, 0) else {
+            panic!("Unexpected message");
+        };
+        assert_eq!(data.current_distance, 1234);
+        assert_eq!(data.min_distance, 30);
+        assert_eq!(data.max_distance, 5000);
+        assert_eq!(data.id, 2);
+        assert_eq!(
+            data.orientation,
+            MavSensorOrientation::MAV_SENSOR_ROTATION_PITCH_270
+        );
+        // Zero confidence is reported as an invalid reading, not as unknown quality
+        assert_eq!(data.signal_quality, 1);
+
+        let MavMessage::DISTANCE_SENSOR(data) = distance_sensor(&config, 1_000, 250) else {
+            panic!("Unexpected message");
+        };
+        assert_eq!(data.signal_quality, 100);
+    }
+
+    #[test]
+    fn test_transport_parsing() {
+        assert_eq!(
+            "zenoh".parse::<RangefinderTransportSelection>().unwrap(),
+            RangefinderTransportSelection::Zenoh
+        );
+        assert_eq!(
+            "udpout:127.0.0.1:14550"
+                .parse::<RangefinderTransportSelection>()
+                .unwrap(),
+            RangefinderTransportSelection::Mavlink("udpout:127.0.0.1:14550".to_string())
+        );
+        assert!("127.0.0.1:14550"
+            .parse::<RangefinderTransportSelection>()
+            .is_err());
+    }
+}
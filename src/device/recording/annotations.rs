@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::export::ConversionError;
+
+/// Channel holding the markers of a session, shared by all its devices
+pub const ANNOTATIONS_TOPIC: &str = "session/Annotations";
+/// Name of the MCAP metadata records describing the session, the last one is the most recent
+pub const SESSION_METADATA: &str = "session";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Validate, Apiv2Schema)]
+#[serde(default)]
+pub struct SessionTags {
+    #[validate(length(max = 200))]
+    pub dive: Option<String>,
+    #[validate(length(max = 200))]
+    pub operator: Option<String>,
+    #[validate(length(max = 200))]
+    pub site: Option<String>,
+}
+
+impl SessionTags {
+    pub fn insert_into(&self, metadata: &mut BTreeMap<String, String>) {
+        for (key, value) in [
+            ("dive", &self.dive),
+            ("operator", &self.operator),
+            ("site", &self.site),
+        ] {
+            if let Some(value) = value {
+                metadata.insert(key.to_string(), value.clone());
+            }
+        }
+    }
+}
+
+/// Free text dropped by an operator while recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Apiv2Schema, schemars::JsonSchema)]
+pub struct Marker {
+    #[schemars(description = "Time of the marker, in microseconds since the Unix epoch")]
+    pub timestamp_us: i64,
+    pub text: String,
+    #[schemars(
+        with = "Option<String>",
+        description = "Device the marker refers to, empty for the whole session"
+    )]
+    pub device_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, Apiv2Schema)]
+pub struct NewMarker {
+    #[validate(length(min = 1, max = 1000))]
+    pub text: String,
+    /// Device the marker refers to, empty for the whole session
+    pub device_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct AddMarker {
+    pub session_id: Uuid,
+    pub marker: NewMarker,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct SetSessionTags {
+    pub session_id: Uuid,
+    pub tags: SessionTags,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct MetadataRecord {
+    pub name: String,
+    pub values: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Apiv2Schema)]
+pub struct RecordingAnnotations {
+    pub metadata: Vec<MetadataRecord>,
+    pub markers: Vec<Marker>,
+}
+
+pub fn read_metadata(content: &[u8]) -> Result<Vec<MetadataRecord>, ConversionError> {
+    if let Some(summary) = mcap::Summary::read(content)? {
+        return indexed_metadata(content, &summary);
+    }
+
+    // Recordings still being written have no summary section yet
+    let mut records = Vec::new();
+    for record in mcap::read::LinearReader::new(content)? {
+        if let mcap::records::Record::Metadata(metadata) = record? {
+            records.push(MetadataRecord {
+                name: metadata.name,
+                values: metadata.metadata,
+            });
+        }
+    }
+    Ok(records)
+}
+
+/// Metadata records listed on the summary section, read without going through the messages
+pub fn indexed_metadata(
+    content: &[u8],
+    summary: &mcap::Summary,
+) -> Result<Vec<MetadataRecord>, ConversionError> {
+    let mut indexes: Vec<_> = summary.metadata_indexes.iter().collect();
+    indexes.sort_by_key(|index| index.offset);
+    indexes
+        .into_iter()
+        .map(|index| {
+            let metadata = mcap::read::metadata(content, index)?;
+            Ok(MetadataRecord {
+                name: metadata.name,
+                values: metadata.metadata,
+            })
+        })
+        .collect()
+}
+
+/// Metadata records and markers of a recording file, in the order they were written
+pub fn read_annotations(content: &[u8]) -> Result<RecordingAnnotations, ConversionError> {
+    let mut annotations = RecordingAnnotations {
+        metadata: read_metadata(content)?,
+        markers: Vec::new(),
+    };
+
+    for message in mcap::MessageStream::new(content)? {
+        let message = message?;
+        if message.channel.topic != ANNOTATIONS_TOPIC {
+            continue;
+        }
+        match serde_json::from_slice::<Marker>(&message.data) {
+            Ok(marker) => annotations.markers.push(marker),
+            Err(err) => warn!("Skipping undecodable marker: {err}"),
+        }
+    }
+
+    Ok(annotations)
+}
@@ -0,0 +1,507 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Seek, Write},
+    path::Path,
+};
+
+use bluerobotics_ping::{
+    decoder::{Decoder, DecoderResult},
+    message::ProtocolMessage,
+    ping1d::ProfileStruct,
+    ping360::AutoDeviceDataStruct,
+};
+use chrono::{DateTime, Duration, Utc};
+use foxglove::Context;
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    ping_viewer,
+    protocol::{self, RawPingMessage},
+};
+use crate::device::{manager::DeviceSelection, replay::serialize_message};
+use crate::vehicle::pose_history::InterpolatedPose;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// Ping Viewer sensor log
+    Bin,
+    /// One row per sonar message, with the vehicle position when recorded
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Bin => "bin",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Bin => "application/octet-stream",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConversionError {
+    /// The source file can't be converted
+    Invalid(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::Invalid(message) => write!(f, "{message}"),
+            ConversionError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<io::Error> for ConversionError {
+    fn from(err: io::Error) -> Self {
+        ConversionError::Io(err)
+    }
+}
+
+impl From<mcap::McapError> for ConversionError {
+    fn from(err: mcap::McapError) -> Self {
+        match err {
+            mcap::McapError::Io(err) => ConversionError::Io(err),
+            err => ConversionError::Invalid(format!("Invalid MCAP file: {err}")),
+        }
+    }
+}
+
+const PING1D_CSV_HEADER: &str = "timestamp,distance_mm,confidence,latitude,longitude";
+const PING360_CSV_HEADER: &str = "timestamp,angle,gain_setting,sample_period,transmit_frequency,\
+                                  number_of_samples,latitude,longitude,intensities";
+
+pub fn log_time_to_date(log_time: u64) -> DateTime<Utc> {
+    DateTime::from_timestamp_nanos(log_time as i64)
+}
+
+fn for_each_message(
+    content: &[u8],
+    mut handle: impl FnMut(&mcap::Message) -> Result<(), ConversionError>,
+) -> Result<(), ConversionError> {
+    for message in mcap::MessageStream::new(content)? {
+        handle(&message?)?;
+    }
+    Ok(())
+}
+
+/// Log times of the messages to keep, in nanoseconds since the Unix epoch, the end excluded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl TimeRange {
+    pub const ALL: TimeRange = TimeRange {
+        start: 0,
+        end: u64::MAX,
+    };
+
+    pub fn contains(&self, log_time: u64) -> bool {
+        (self.start..self.end).contains(&log_time)
+    }
+}
+
+/// Copy the messages of the range to a new recording, keeping its metadata records.
+/// All topics are kept when none is given.
+pub fn extract_mcap(
+    content: &[u8],
+    time_range: TimeRange,
+    topics: &[String],
+    output: impl Write + Seek,
+) -> Result<u64, ConversionError> {
+    let mut writer = mcap::Writer::new(output)?;
+    let mut message_count = 0;
+    for_each_message(content, |message| {
+        if !time_range.contains(message.log_time)
+            || !(topics.is_empty() || topics.contains(&message.channel.topic))
+        {
+            return Ok(());
+        }
+        writer.write(message)?;
+        message_count += 1;
+        Ok(())
+    })?;
+    if message_count == 0 {
+        return Err(ConversionError::Invalid(
+            "Recording has no message in the requested range".to_string(),
+        ));
+    }
+
+    for record in super::annotations::read_metadata(content)? {
+        writer.write_metadata(&mcap::records::Metadata {
+            name: record.name,
+            metadata: record.values,
+        })?;
+    }
+    writer.finish()?;
+    Ok(message_count)
+}
+
+/// Single device of a recording, converted to the formats used by other tools
+pub struct RecordingExport<'a> {
+    // Usually a memory mapped recording, they can be several gigabytes long
+    content: &'a [u8],
+    // Channels of the device start with it, like `device_{id}/`
+    prefix: String,
+    device_type: DeviceSelection,
+    has_raw: bool,
+    start_time: u64,
+    // Vehicle position interpolated to each sonar message, by log time
+    positions: HashMap<u64, (f64, f64)>,
+    time_range: TimeRange,
+}
+
+impl<'a> RecordingExport<'a> {
+    /// Without a device, the first one found on the recording is exported
+    pub fn new(content: &'a [u8], device_id: Option<Uuid>) -> Result<Self, ConversionError> {
+        let mut sonar_topics: Vec<(String, DeviceSelection)> = Vec::new();
+        let mut first_times: HashMap<String, u64> = HashMap::new();
+        let mut positions: HashMap<(String, u64), (f64, f64)> = HashMap::new();
+
+        for_each_message(content, |message| {
+            let topic = &message.channel.topic;
+            first_times
+                .entry(topic.clone())
+                .and_modify(|time| *time = (*time).min(message.log_time))
+                .or_insert(message.log_time);
+
+            if let Some(prefix) = topic.strip_suffix("Ping1D") {
+                if !sonar_topics.iter().any(|(known, _)| known == prefix) {
+                    sonar_topics.push((prefix.to_string(), DeviceSelection::Ping1D));
+                }
+            } else if let Some(prefix) = topic.strip_suffix("Ping360") {
+                if !sonar_topics.iter().any(|(known, _)| known == prefix) {
+                    sonar_topics.push((prefix.to_string(), DeviceSelection::Ping360));
+                }
+            } else if let Some(prefix) = topic.strip_suffix("InterpolatedPose") {
+                match serde_json::from_slice::<InterpolatedPose>(&message.data) {
+                    Ok(pose) => {
+                        positions.insert(
+                            (prefix.to_string(), message.log_time),
+                            (pose.pose.lat, pose.pose.lon),
+                        );
+                    }
+                    Err(err) => warn!("Export: skipping undecodable pose on {topic}: {err}"),
+                }
+            }
+            Ok(())
+        })?;
+
+        let (prefix, device_type) = match device_id {
+            Some(device_id) => {
+                let prefix = format!("device_{device_id}/");
+                sonar_topics
+                    .into_iter()
+                    .find(|(known, _)| *known == prefix)
+                    .ok_or_else(|| {
+                        ConversionError::Invalid(format!(
+                            "Recording has no Ping1D or Ping360 data of device {device_id}"
+                        ))
+                    })?
+            }
+            None => sonar_topics.into_iter().next().ok_or_else(|| {
+                ConversionError::Invalid("Recording has no Ping1D or Ping360 data".to_string())
+            })?,
+        };
+
+        let start_time = first_times
+            .iter()
+            .filter(|(topic, _)| topic.starts_with(&prefix))
+            .map(|(_, time)| *time)
+            .min()
+            .unwrap_or_default();
+        let has_raw = first_times.contains_key(&format!("{prefix}Raw"));
+        let positions = positions
+            .into_iter()
+            .filter(|((topic_prefix, _), _)| *topic_prefix == prefix)
+            .map(|((_, log_time), position)| (log_time, position))
+            .collect();
+
+        Ok(Self {
+            content,
+            prefix,
+            device_type,
+            has_raw,
+            start_time,
+            positions,
+            time_range: TimeRange::ALL,
+        })
+    }
+
+    /// Only export the messages logged during the range
+    pub fn with_time_range(self, time_range: TimeRange) -> Self {
+        Self { time_range, ..self }
+    }
+
+    pub fn write(&self, format: ExportFormat, output: impl Write) -> Result<(), ConversionError> {
+        match format {
+            ExportFormat::Bin => self.write_bin(output),
+            ExportFormat::Csv => self.write_csv(output),
+        }
+    }
+
+    fn sonar_topic(&self) -> String {
+        match self.device_type {
+            DeviceSelection::Ping1D => format!("{}Ping1D", self.prefix),
+            _ => format!("{}Ping360", self.prefix),
+        }
+    }
+
+    fn position(&self, log_time: u64) -> String {
+        match self.positions.get(&log_time) {
+            Some((lat, lon)) => format!("{lat},{lon}"),
+            None => ",".to_string(),
+        }
+    }
+
+    fn write_csv(&self, mut output: impl Write) -> Result<(), ConversionError> {
+        let topic = self.sonar_topic();
+        let header = match self.device_type {
+            DeviceSelection::Ping1D => PING1D_CSV_HEADER,
+            _ => PING360_CSV_HEADER,
+        };
+        writeln!(output, "{header}")?;
+
+        for_each_message(self.content, |message| {
+            if message.channel.topic != topic || !self.time_range.contains(message.log_time) {
+                return Ok(());
+            }
+            let timestamp = log_time_to_date(message.log_time).to_rfc3339();
+            let position = self.position(message.log_time);
+            match self.device_type {
+                DeviceSelection::Ping1D => {
+                    let Ok(profile) = serde_json::from_slice::<ProfileStruct>(&message.data) else {
+                        warn!("Export: skipping undecodable message on {topic}");
+                        return Ok(());
+                    };
+                    writeln!(
+                        output,
+                        "{timestamp},{},{},{position}",
+                        profile.distance, profile.confidence
+                    )?;
+                }
+                _ => {
+                    let Ok(data) = serde_json::from_slice::<AutoDeviceDataStruct>(&message.data)
+                    else {
+                        warn!("Export: skipping undecodable message on {topic}");
+                        return Ok(());
+                    };
+                    let intensities: Vec<String> =
+                        data.data.iter().map(|value| value.to_string()).collect();
+                    writeln!(
+                        output,
+                        "{timestamp},{},{},{},{},{},{position},{}",
+                        data.angle,
+                        data.gain_setting,
+                        data.sample_period,
+                        data.transmit_frequency,
+                        data.number_of_samples,
+                        intensities.join(" ")
+                    )?;
+                }
+            }
+            Ok(())
+        })?;
+        output.flush()?;
+        Ok(())
+    }
+
+    // Raw frames are exported as is when recorded, otherwise the sonar data is serialized again
+    fn write_bin(&self, mut output: impl Write) -> Result<(), ConversionError> {
+        let sensor_type = match self.device_type {
+            DeviceSelection::Ping1D => ping_viewer::SENSOR_TYPE_PING1D,
+            _ => ping_viewer::SENSOR_TYPE_PING360,
+        };
+        let header = ping_viewer::LogHeader::new(
+            sensor_type,
+            log_time_to_date(self.start_time).to_rfc3339(),
+        );
+        ping_viewer::write_header(&mut output, &header)?;
+
+        let topic = if self.has_raw {
+            format!("{}Raw", self.prefix)
+        } else {
+            self.sonar_topic()
+        };
+
+        for_each_message(self.content, |message| {
+            if message.channel.topic != topic || !self.time_range.contains(message.log_time) {
+                return Ok(());
+            }
+            let frame = if self.has_raw {
+                serde_json::from_slice::<RawPingMessage>(&message.data)
+                    .ok()
+                    .and_then(|raw| raw.frame_bytes().ok())
+            } else {
+                match self.device_type {
+                    DeviceSelection::Ping1D => {
+                        serde_json::from_slice::<ProfileStruct>(&message.data)
+                            .ok()
+                            .map(|profile| {
+                                serialize_message(&bluerobotics_ping::ping1d::Messages::Profile(
+                                    profile,
+                                ))
+                            })
+                    }
+                    _ => serde_json::from_slice::<AutoDeviceDataStruct>(&message.data)
+                        .ok()
+                        .map(|data| {
+                            serialize_message(
+                                &bluerobotics_ping::ping360::Messages::AutoDeviceData(data),
+                            )
+                        }),
+                }
+            };
+            match frame {
+                Some(frame) => ping_viewer::write_entry(
+                    &mut output,
+                    log_time_to_date(message.log_time).time(),
+                    &frame,
+                )?,
+                None => warn!("Export: skipping undecodable message on {topic}"),
+            }
+            Ok(())
+        })?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct ImportedLog {
+    pub file_name: String,
+    /// Identifier given to the device on the channels of the new recording
+    pub device_id: Uuid,
+    pub device_type: DeviceSelection,
+    pub message_count: usize,
+}
+
+fn decode_frame(frame: &[u8]) -> Option<ProtocolMessage> {
+    let mut decoder = Decoder::new();
+    frame
+        .iter()
+        .find_map(|byte| match decoder.parse_byte(*byte) {
+            DecoderResult::Success(message) => Some(message),
+            _ => None,
+        })
+}
+
+/// Convert a Ping Viewer sensor log to a recording, with the same channels a live one would have.
+/// Entries only carry the time of day, so they are placed relative to the start of the log.
+pub fn import_ping_viewer_log(
+    content: &[u8],
+    start_time: DateTime<Utc>,
+    output: &Path,
+) -> Result<ImportedLog, ConversionError> {
+    let (header, entries) = ping_viewer::read_log(content)
+        .map_err(|err| ConversionError::Invalid(format!("Invalid Ping Viewer log: {err}")))?;
+    let start_time = DateTime::parse_from_rfc3339(&header.date)
+        .map(|date| date.to_utc())
+        .unwrap_or(start_time);
+
+    let mut first_time = None;
+    let mut offset = Duration::zero();
+    let mut messages = Vec::new();
+    for entry in entries {
+        if let Some(time) = entry.time {
+            let first_time = *first_time.get_or_insert(time);
+            let mut entry_offset = time - first_time;
+            // The time of day wraps at midnight
+            while entry_offset < offset - Duration::hours(12) {
+                entry_offset += Duration::days(1);
+            }
+            offset = entry_offset;
+        }
+        match decode_frame(&entry.frame) {
+            Some(message) => messages.push((start_time + offset, message)),
+            None => warn!(
+                "Import: skipping undecodable entry of {} bytes",
+                entry.frame.len()
+            ),
+        }
+    }
+    if messages.is_empty() {
+        return Err(ConversionError::Invalid(
+            "Ping Viewer log has no valid message".to_string(),
+        ));
+    }
+
+    let device_id = Uuid::new_v4();
+    let ctx = Context::new();
+    let writer = ctx
+        .mcap_writer()
+        .create_new_buffered_file(output)
+        .map_err(|err| io::Error::other(format!("Failed to create MCAP file: {err}")))?;
+    let raw_topic = format!("device_{device_id}/Raw");
+    let ping1d_topic = format!("device_{device_id}/Ping1D");
+    let ping360_topic = format!("device_{device_id}/Ping360");
+    let raw_channel = ctx.channel_builder(&raw_topic).build::<RawPingMessage>();
+    let ping1d_channel = ctx.channel_builder(&ping1d_topic).build::<ProfileStruct>();
+    let ping360_channel = ctx
+        .channel_builder(&ping360_topic)
+        .build::<AutoDeviceDataStruct>();
+
+    let mut device_type = match header.sensor_type {
+        ping_viewer::SENSOR_TYPE_PING1D => DeviceSelection::Ping1D,
+        ping_viewer::SENSOR_TYPE_PING360 => DeviceSelection::Ping360,
+        _ => DeviceSelection::Auto,
+    };
+    let message_count = messages.len();
+    for (time, message) in messages {
+        let timestamp = foxglove::schemas::Timestamp::new(
+            time.timestamp() as u32,
+            time.timestamp_subsec_nanos(),
+        );
+        raw_channel.log_with_time(&RawPingMessage::from(&message), timestamp);
+        match bluerobotics_ping::Messages::try_from(&message) {
+            Ok(bluerobotics_ping::Messages::Ping1D(
+                bluerobotics_ping::ping1d::Messages::Profile(profile),
+            )) => {
+                ping1d_channel.log_with_time(&profile, timestamp);
+                device_type = DeviceSelection::Ping1D;
+            }
+            Ok(bluerobotics_ping::Messages::Ping360(
+                bluerobotics_ping::ping360::Messages::AutoDeviceData(data),
+            )) => {
+                ping360_channel.log_with_time(&data, timestamp);
+                device_type = DeviceSelection::Ping360;
+            }
+            Ok(bluerobotics_ping::Messages::Ping360(
+                bluerobotics_ping::ping360::Messages::DeviceData(data),
+            )) => {
+                ping360_channel.log_with_time(&protocol::auto_device_data(data), timestamp);
+                device_type = DeviceSelection::Ping360;
+            }
+            _ => {}
+        }
+    }
+    writer
+        .close()
+        .map_err(|err| io::Error::other(format!("Failed to close MCAP file: {err}")))?;
+
+    Ok(ImportedLog {
+        file_name: output
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        device_id,
+        device_type,
+        message_count,
+    })
+}
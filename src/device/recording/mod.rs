@@ -1,4 +1,6 @@
-use bluerobotics_ping::{ping1d::ProfileStruct, ping360::AutoDeviceDataStruct};
+use bluerobotics_ping::{
+    message::ProtocolMessage, ping1d::ProfileStruct, ping360::AutoDeviceDataStruct,
+};
 use foxglove::Context;
 use foxglove::McapWriterHandle;
 use paperclip::actix::Apiv2Schema;
@@ -6,7 +8,7 @@ use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::BufWriter;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -15,53 +17,173 @@ use tokio::sync::{
     broadcast::{self, Receiver},
     mpsc, RwLock,
 };
+use tokio::time::{interval, Duration, MissedTickBehavior};
 use tracing::{error, info, trace, warn};
 use uuid::Uuid;
+use validator::Validate;
 
-use crate::device::{
-    devices::DeviceActorHandler,
-    manager::{DeviceSelection, ManagerError},
+use crate::device::devices::PingControlMessage;
+use crate::device::manager::{
+    distance_filter::{self, FilteredDistance},
+    DeviceSelection, ManagerError,
+};
+use crate::vehicle::{
+    pose_history::{InterpolatedPose, SharedPoseHistory, POSE_STALE_AFTER},
+    VehicleData,
 };
-use crate::vehicle::VehicleData;
 
 use super::manager::{ManagerActorHandler, UuidWrapper};
 
+pub mod annotations;
+pub mod export;
+pub mod ping_viewer;
+pub mod protocol;
+pub mod rolling;
+pub mod summary;
+
+use annotations::{Marker, SessionTags};
+use rolling::RecordingPolicy;
+
+/// Channel holding the vehicle poses received while recording, shared by all the devices
+pub const VEHICLE_TOPIC: &str = "session/VehicleData";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RecordingSession {
+pub struct RecordedDevice {
     pub device_id: Uuid,
+    pub device_type: DeviceSelection,
+    /// Cleared when the device stops sending data, the other devices keep recording
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordingSession {
+    pub session_id: Uuid,
+    /// Devices recorded together on the same file
+    pub devices: Vec<RecordedDevice>,
     pub file_path: PathBuf,
     pub is_active: bool,
     pub start_time: chrono::DateTime<chrono::Utc>,
-    pub device_type: DeviceSelection,
+    /// Index of the file being written, increased each time the recording is split
+    pub segment: u32,
+    pub segment_start_time: chrono::DateTime<chrono::Utc>,
+    /// Reason the recording was stopped by the service, like a full disk
+    pub warning: Option<String>,
+    /// Number of messages written on each channel since the session started
+    pub channel_counts: BTreeMap<String, u64>,
+    pub tags: SessionTags,
+    /// Markers dropped since the session started, also written on the annotations channel
+    pub markers: Vec<Marker>,
+}
+
+impl RecordingSession {
+    pub fn has_device(&self, device_id: Uuid) -> bool {
+        self.devices
+            .iter()
+            .any(|device| device.device_id == device_id)
+    }
+
+    /// Written as an MCAP metadata record on each file of the session
+    pub fn metadata(&self) -> BTreeMap<String, String> {
+        let devices: Vec<String> = self
+            .devices
+            .iter()
+            .map(|device| format!("{}:{:?}", device.device_id, device.device_type))
+            .collect();
+        let mut metadata = BTreeMap::from([
+            ("session_id".to_string(), self.session_id.to_string()),
+            ("start_time".to_string(), self.start_time.to_rfc3339()),
+            ("segment".to_string(), self.segment.to_string()),
+            ("devices".to_string(), devices.join(",")),
+        ]);
+        self.tags.insert_into(&mut metadata);
+        metadata
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct StartSession {
+    /// Devices recorded on the same file, started and stopped together
+    pub devices: Vec<Uuid>,
+    #[serde(default)]
+    pub tags: SessionTags,
+}
+
+// Message counters shared by the recording tasks of a session, read when reporting its status
+#[derive(Debug, Clone, Default)]
+pub struct ChannelCounts(Arc<std::sync::Mutex<BTreeMap<String, u64>>>);
+
+impl ChannelCounts {
+    pub fn increment(&self, topic: &str) {
+        if let Ok(mut counts) = self.0.lock() {
+            *counts.entry(topic.to_string()).or_default() += 1;
+        }
+    }
+
+    pub fn snapshot(&self) -> BTreeMap<String, u64> {
+        self.0
+            .lock()
+            .map(|counts| counts.clone())
+            .unwrap_or_default()
+    }
 }
 
 pub struct SessionGuard {
     pub session: RecordingSession,
     pub writer: Option<McapWriterHandle<BufWriter<File>>>,
+    // Kept to open the next file of a split recording on the same channels
+    pub context: Arc<Context>,
+    pub channel_counts: ChannelCounts,
+    pub annotations: foxglove::Channel<Marker>,
+    file_prefix: String,
+}
+
+impl SessionGuard {
+    pub fn status(&self) -> RecordingSession {
+        RecordingSession {
+            channel_counts: self.channel_counts.snapshot(),
+            ..self.session.clone()
+        }
+    }
 }
 
 pub struct RecordingManager {
     receiver: mpsc::Receiver<ManagerActorRequest>,
+    // Sessions by their id
     sessions: Arc<RwLock<HashMap<Uuid, SessionGuard>>>,
     base_path: PathBuf,
     status_broadcast: broadcast::Sender<RecordingSession>,
     devices_manager_handler: ManagerActorHandler,
-    vehicle_data: Arc<RwLock<Option<VehicleData>>>,
+    pose_history: SharedPoseHistory,
+    policy: RecordingPolicy,
+    // Sessions with a device that stopped recording, stopped once none of them is left
+    ended_sender: mpsc::Sender<Uuid>,
+    ended_receiver: mpsc::Receiver<Uuid>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
 #[serde(tag = "command", content = "payload")]
 pub enum RecordingManagerCommand {
+    /// Start a session recording a single device
     StartRecording(UuidWrapper),
+    /// Stop the session recording the device
     StopRecording(UuidWrapper),
+    /// Status of the session recording the device
     GetRecordingStatus(UuidWrapper),
     GetAllRecordingStatus,
     GetSubscriber,
+    GetRecordingPolicy,
+    SetRecordingPolicy(RecordingPolicy),
+    StartSession(StartSession),
+    StopSession(UuidWrapper),
+    GetSessionStatus(UuidWrapper),
+    SetSessionTags(annotations::SetSessionTags),
+    AddMarker(annotations::AddMarker),
 }
 
 #[derive(Clone)]
 pub struct RecordingsManagerHandler {
     sender: mpsc::Sender<ManagerActorRequest>,
+    base_path: PathBuf,
 }
 
 #[derive(Debug)]
@@ -75,27 +197,167 @@ pub enum Answer {
     RecordingSession(RecordingSession),
     RecordingStatus(Option<RecordingSession>),
     AllRecordingStatus(Vec<RecordingSession>),
+    RecordingPolicy(RecordingPolicy),
     #[serde(skip)]
     RecordingManager(Receiver<RecordingSession>),
 }
 
+// The session keeps recording without its metadata, so a failure is only reported
+fn write_session_metadata(writer: &McapWriterHandle<BufWriter<File>>, session: &RecordingSession) {
+    if let Err(err) = writer.write_metadata(annotations::SESSION_METADATA, session.metadata()) {
+        warn!(
+            "Failed to write metadata of recording session {}: {err}",
+            session.session_id
+        );
+    }
+}
+
+// Everything needed to record a device, gathered before the session starts
+struct DeviceSource {
+    device: RecordedDevice,
+    receiver: broadcast::Receiver<ProtocolMessage>,
+    control_receiver: broadcast::Receiver<PingControlMessage>,
+}
+
+// How often sonar samples waiting for the next vehicle pose are checked
+const POSE_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+fn foxglove_time(time: chrono::DateTime<chrono::Utc>) -> foxglove::schemas::Timestamp {
+    foxglove::schemas::Timestamp::new(time.timestamp() as u32, time.timestamp_subsec_nanos())
+}
+
+// Pairs each sonar sample of a device with the vehicle pose interpolated to its arrival time,
+// which is only possible once the pose following the sample is received
+struct PoseInterpolation {
+    topic: String,
+    channel: foxglove::Channel<InterpolatedPose>,
+    pending: VecDeque<chrono::DateTime<chrono::Utc>>,
+}
+
+impl PoseInterpolation {
+    fn new(ctx: &Arc<Context>, device_id: Uuid) -> Self {
+        let topic = format!("device_{}/InterpolatedPose", device_id);
+        Self {
+            channel: ctx.channel_builder(&topic).build::<InterpolatedPose>(),
+            topic,
+            pending: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, sample_time: chrono::DateTime<chrono::Utc>) {
+        self.pending.push_back(sample_time);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    // Samples without a following pose after POSE_STALE_AFTER are logged with the last one,
+    // flagged as stale, all of them when the recording is finished
+    async fn log_ready(
+        &mut self,
+        pose_history: &SharedPoseHistory,
+        channel_counts: &ChannelCounts,
+        finished: bool,
+    ) {
+        let history = pose_history.read().await;
+        let latest = history.latest().map(|sample| sample.timestamp);
+        let now = chrono::Utc::now();
+
+        while let Some(&sample_time) = self.pending.front() {
+            let ready = finished
+                || latest.is_some_and(|latest| latest >= sample_time)
+                || now - sample_time > POSE_STALE_AFTER;
+            if !ready {
+                break;
+            }
+            self.pending.pop_front();
+
+            let Some(pose) = history.at(sample_time) else {
+                continue;
+            };
+            if pose.stale {
+                trace!(
+                    "Recording: stale pose ({} ms) on {}",
+                    pose.age_ms,
+                    self.topic
+                );
+            }
+            self.channel
+                .log_with_time(&pose, foxglove_time(sample_time));
+            channel_counts.increment(&self.topic);
+        }
+    }
+}
+
+// Log each pose received by the bridge once for the whole session, until the session stops
+async fn record_vehicle(
+    session_id: Uuid,
+    sessions: Arc<RwLock<HashMap<Uuid, SessionGuard>>>,
+    ctx: Arc<Context>,
+    pose_history: SharedPoseHistory,
+    channel_counts: ChannelCounts,
+) {
+    let vehicle_channel = ctx.channel_builder(VEHICLE_TOPIC).build::<VehicleData>();
+    let mut last_pose_time = chrono::Utc::now();
+    let mut pose_check = interval(POSE_CHECK_INTERVAL);
+    pose_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        pose_check.tick().await;
+        let is_active = sessions
+            .read()
+            .await
+            .get(&session_id)
+            .is_some_and(|guard| guard.session.is_active);
+        if !is_active {
+            break;
+        }
+
+        for sample in pose_history.read().await.since(last_pose_time) {
+            vehicle_channel.log_with_time(&sample.pose, foxglove_time(sample.timestamp));
+            channel_counts.increment(VEHICLE_TOPIC);
+            last_pose_time = sample.timestamp;
+        }
+    }
+}
+
+// Records a device of a session until the session stops or the device goes away
+struct RecordingTask {
+    source: DeviceSource,
+    session_id: Uuid,
+    raw_messages: bool,
+    sessions: Arc<RwLock<HashMap<Uuid, SessionGuard>>>,
+    ctx: Arc<Context>,
+    pose_history: SharedPoseHistory,
+    channel_counts: ChannelCounts,
+    ended: mpsc::Sender<Uuid>,
+}
+
 impl RecordingManager {
     pub fn new_with_pose(
         size: usize,
         base_path: impl AsRef<Path>,
         device_manager: ManagerActorHandler,
-        vehicle_data: Arc<RwLock<Option<VehicleData>>>,
+        pose_history: SharedPoseHistory,
     ) -> (Self, RecordingsManagerHandler) {
         let (sender, receiver) = mpsc::channel(size);
-        let actor_handler: RecordingsManagerHandler = RecordingsManagerHandler { sender };
+        let actor_handler: RecordingsManagerHandler = RecordingsManagerHandler {
+            sender,
+            base_path: base_path.as_ref().to_path_buf(),
+        };
         let (status_broadcast, _) = broadcast::channel(100);
+        let (ended_sender, ended_receiver) = mpsc::channel(size);
         let actor = RecordingManager {
             sessions: Arc::new(RwLock::new(HashMap::new())),
             base_path: base_path.as_ref().to_path_buf(),
             status_broadcast,
             receiver,
             devices_manager_handler: device_manager,
-            vehicle_data,
+            pose_history,
+            policy: RecordingPolicy::default(),
+            ended_sender,
+            ended_receiver,
         };
         (actor, actor_handler)
     }
@@ -105,17 +367,25 @@ impl RecordingManager {
         base_path: impl AsRef<Path>,
         device_manager: ManagerActorHandler,
     ) -> (Self, RecordingsManagerHandler) {
-        Self::new_with_pose(size, base_path, device_manager, Arc::new(RwLock::new(None)))
+        Self::new_with_pose(size, base_path, device_manager, Default::default())
     }
 
     pub async fn run(mut self) {
         info!("RecordingsManager is running");
 
+        let mut policy_interval = tokio::time::interval(rolling::POLICY_CHECK_INTERVAL);
+
         loop {
             tokio::select! {
                 Some(msg) = self.receiver.recv() => {
                     self.handle_message(msg).await;
                 }
+                _ = policy_interval.tick() => {
+                    self.apply_policy().await;
+                }
+                Some(session_id) = self.ended_receiver.recv() => {
+                    self.stop_ended_session(session_id).await;
+                }
                 else => break,
             }
         }
@@ -146,6 +416,30 @@ impl RecordingManager {
             RecordingManagerCommand::GetSubscriber => {
                 Ok(Answer::RecordingManager(self.subscribe()))
             }
+            RecordingManagerCommand::GetRecordingPolicy => Ok(Answer::RecordingPolicy(self.policy)),
+            RecordingManagerCommand::SetRecordingPolicy(policy) => self
+                .set_policy(policy)
+                .map(|_| Answer::RecordingPolicy(self.policy)),
+            RecordingManagerCommand::StartSession(request) => self
+                .start_session(request.devices, request.tags)
+                .await
+                .map(Answer::RecordingSession),
+            RecordingManagerCommand::StopSession(uuid_wrapper) => self
+                .stop_session(*uuid_wrapper)
+                .await
+                .map(Answer::RecordingSession),
+            RecordingManagerCommand::GetSessionStatus(uuid_wrapper) => self
+                .get_session_status(*uuid_wrapper)
+                .await
+                .map(Answer::RecordingStatus),
+            RecordingManagerCommand::SetSessionTags(request) => self
+                .set_session_tags(request.session_id, request.tags)
+                .await
+                .map(Answer::RecordingSession),
+            RecordingManagerCommand::AddMarker(request) => self
+                .add_marker(request.session_id, request.marker)
+                .await
+                .map(Answer::RecordingSession),
         };
 
         if let Err(e) = actor_request.respond_to.send(result) {
@@ -161,12 +455,138 @@ impl RecordingManager {
         let _ = self.status_broadcast.send(session.clone());
     }
 
+    pub fn set_policy(&mut self, policy: RecordingPolicy) -> Result<(), ManagerError> {
+        policy.validate()?;
+        info!("RecordingsManager: Using recording policy {policy:?}");
+        self.policy = policy;
+        Ok(())
+    }
+
+    fn file_path(&self, file_prefix: &str, timestamp: chrono::DateTime<chrono::Utc>) -> PathBuf {
+        let filename = format!("{}_{}.mcap", file_prefix, timestamp.format("%Y%m%d_%H%M%S"));
+        self.base_path.join(filename)
+    }
+
+    fn check_free_disk_space(&self) -> Result<(), String> {
+        match rolling::free_disk_space(&self.base_path) {
+            Some(free_space) if self.policy.is_disk_low(free_space) => Err(format!(
+                "Free disk space of {} MB is below the minimum of {} MB",
+                free_space / (1024 * 1024),
+                self.policy.min_free_disk_mb
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    async fn active_files(&self) -> Vec<PathBuf> {
+        self.sessions
+            .read()
+            .await
+            .values()
+            .map(|guard| guard.session.file_path.clone())
+            .collect()
+    }
+
+    async fn enforce_retention(&self) {
+        let base_path = self.base_path.clone();
+        let policy = self.policy;
+        let active = self.active_files().await;
+        let result = tokio::task::spawn_blocking(move || {
+            rolling::enforce_retention(&base_path, &policy, &active)
+        })
+        .await;
+        if let Err(err) = result {
+            error!("RecordingsManager: Retention task failed: {err:?}");
+        }
+    }
+
+    async fn session_of_device(&self, device_id: Uuid) -> Option<Uuid> {
+        self.sessions
+            .read()
+            .await
+            .values()
+            .find(|guard| guard.session.is_active && guard.session.has_device(device_id))
+            .map(|guard| guard.session.session_id)
+    }
+
+    async fn device_source(&self, device_id: Uuid) -> Result<DeviceSource, ManagerError> {
+        let device_type = match self
+            .devices_manager_handler
+            .send(crate::device::manager::Request::Info(UuidWrapper {
+                uuid: device_id,
+            }))
+            .await?
+        {
+            crate::device::manager::Answer::DeviceInfo(info) => info
+                .first()
+                .map(|info| info.device_type.clone())
+                .ok_or(ManagerError::DeviceNotExist(device_id))?,
+            _ => return Err(ManagerError::Other("Invalid device info".to_string())),
+        };
+
+        let handler = match self
+            .devices_manager_handler
+            .send(crate::device::manager::Request::GetDeviceHandler(
+                UuidWrapper { uuid: device_id },
+            ))
+            .await?
+        {
+            crate::device::manager::Answer::InnerDeviceHandler(handler) => handler,
+            _ => return Err(ManagerError::Other("Invalid device handler".to_string())),
+        };
+
+        let control_receiver = handler.subscribe_control();
+        let receiver = match handler
+            .send(super::devices::PingRequest::GetSubscriber)
+            .await
+            .map_err(|err| {
+                warn!("Something went wrong while executing get_subscriber, details: {err:?}");
+                ManagerError::DeviceError(err)
+            })? {
+            super::devices::PingAnswer::Subscriber(subscriber) => subscriber,
+            msg => {
+                error!("Failed to receive broadcasted message: {:?}", msg);
+                return Err(ManagerError::NoDevices);
+            }
+        };
+
+        Ok(DeviceSource {
+            device: RecordedDevice {
+                device_id,
+                device_type,
+                is_active: true,
+            },
+            receiver,
+            control_receiver,
+        })
+    }
+
     pub async fn start_recording(&self, device_id: Uuid) -> Result<RecordingSession, ManagerError> {
-        if self.sessions.read().await.contains_key(&device_id) {
-            return Err(ManagerError::Other(format!(
-                "Device {} is already recording",
-                device_id
-            )));
+        self.start_session(vec![device_id], SessionTags::default())
+            .await
+    }
+
+    // Every device is checked and subscribed before the file is created, so either all of them
+    // start recording at the same time or none does
+    pub async fn start_session(
+        &self,
+        mut device_ids: Vec<Uuid>,
+        tags: SessionTags,
+    ) -> Result<RecordingSession, ManagerError> {
+        tags.validate()?;
+        device_ids.sort();
+        device_ids.dedup();
+        if device_ids.is_empty() {
+            return Err(ManagerError::Other(
+                "A recording session needs at least one device".to_string(),
+            ));
+        }
+        for device_id in &device_ids {
+            if let Some(session_id) = self.session_of_device(*device_id).await {
+                return Err(ManagerError::Other(format!(
+                    "Device {device_id} is already recording on session {session_id}"
+                )));
+            }
         }
 
         tokio::fs::create_dir_all(&self.base_path)
@@ -175,25 +595,22 @@ impl RecordingManager {
                 ManagerError::Other(format!("Failed to create recording directory: {}", e))
             })?;
 
-        let timestamp = chrono::Utc::now();
-        let filename = format!(
-            "device_{}_{}.mcap",
-            device_id,
-            timestamp.format("%Y%m%d_%H%M%S")
-        );
-        let file_path = self.base_path.join(filename);
+        self.enforce_retention().await;
+        self.check_free_disk_space().map_err(ManagerError::Other)?;
 
-        let request = self
-            .devices_manager_handler
-            .send(crate::device::manager::Request::Info(
-                crate::device::manager::UuidWrapper { uuid: device_id },
-            ))
-            .await?;
+        let mut sources = Vec::with_capacity(device_ids.len());
+        for device_id in &device_ids {
+            sources.push(self.device_source(*device_id).await?);
+        }
 
-        let device_info = match request {
-            crate::device::manager::Answer::DeviceInfo(h) => h.first().unwrap().clone(),
-            _ => return Err(ManagerError::Other("Invalid device handler".to_string())),
+        let session_id = Uuid::new_v4();
+        // Single device recordings keep the name they always had
+        let file_prefix = match device_ids.as_slice() {
+            [device_id] => format!("device_{device_id}"),
+            _ => format!("session_{session_id}"),
         };
+        let timestamp = chrono::Utc::now();
+        let file_path = self.file_path(&file_prefix, timestamp);
 
         let ctx = Context::new();
         let mcap_writer: McapWriterHandle<BufWriter<File>> = ctx
@@ -202,53 +619,79 @@ impl RecordingManager {
             .map_err(|e| ManagerError::Other(format!("Failed to create MCAP file: {}", e)))?;
 
         let session = RecordingSession {
-            device_id,
-            file_path: file_path.clone(),
+            session_id,
+            devices: sources.iter().map(|source| source.device.clone()).collect(),
+            file_path,
             is_active: true,
             start_time: timestamp,
-            device_type: device_info.device_type.clone(),
+            segment: 0,
+            segment_start_time: timestamp,
+            warning: None,
+            channel_counts: BTreeMap::new(),
+            tags,
+            markers: Vec::new(),
         };
+        let channel_counts = ChannelCounts::default();
+        write_session_metadata(&mcap_writer, &session);
+        let annotations = ctx
+            .channel_builder(annotations::ANNOTATIONS_TOPIC)
+            .build::<Marker>();
 
-        let session_guard = SessionGuard {
-            session: session.clone(),
-            writer: Some(mcap_writer),
-        };
-
-        self.sessions.write().await.insert(device_id, session_guard);
+        self.sessions.write().await.insert(
+            session_id,
+            SessionGuard {
+                session: session.clone(),
+                writer: Some(mcap_writer),
+                context: ctx.clone(),
+                channel_counts: channel_counts.clone(),
+                annotations,
+                file_prefix,
+            },
+        );
+        info!("Recording session {session_id} started with devices {device_ids:?}");
         self.broadcast_status(&session).await;
 
-        let sessions = self.sessions.clone();
-        let devices_manager_handler = self.devices_manager_handler.clone();
-        let vehicle_data = self.vehicle_data.clone();
-
-        let device_handler = devices_manager_handler
-            .send(crate::device::manager::Request::GetDeviceHandler(
-                crate::device::manager::UuidWrapper { uuid: device_id },
-            ))
-            .await?;
-
-        let handler = match device_handler {
-            crate::device::manager::Answer::InnerDeviceHandler(h) => h,
-            _ => return Err(ManagerError::Other("Invalid device handler".to_string())),
-        };
-
-        tokio::spawn(async move {
-            if let Err(e) =
-                Self::recording_task(handler, file_path, sessions, device_id, ctx, vehicle_data)
-                    .await
-            {
-                error!("Recording task failed for device {}: {:?}", device_id, e);
-            }
-        });
+        for source in sources {
+            let task = RecordingTask {
+                source,
+                session_id,
+                raw_messages: self.policy.raw_messages,
+                sessions: self.sessions.clone(),
+                ctx: ctx.clone(),
+                pose_history: self.pose_history.clone(),
+                channel_counts: channel_counts.clone(),
+                ended: self.ended_sender.clone(),
+            };
+            tokio::spawn(task.run());
+        }
+        tokio::spawn(record_vehicle(
+            session_id,
+            self.sessions.clone(),
+            ctx.clone(),
+            self.pose_history.clone(),
+            channel_counts.clone(),
+        ));
 
         Ok(session)
     }
 
     pub async fn stop_recording(&self, device_id: Uuid) -> Result<RecordingSession, ManagerError> {
-        let mut sessions = self.sessions.write().await;
-        let session_guard = sessions.get_mut(&device_id).ok_or_else(|| {
+        let session_id = self.session_of_device(device_id).await.ok_or_else(|| {
             ManagerError::Other(format!("No recording session for device {}", device_id))
         })?;
+        self.stop_session(session_id).await
+    }
+
+    pub async fn stop_session(&self, session_id: Uuid) -> Result<RecordingSession, ManagerError> {
+        // Recording tasks finish once their session is gone
+        let mut session_guard =
+            self.sessions
+                .write()
+                .await
+                .remove(&session_id)
+                .ok_or_else(|| {
+                    ManagerError::Other(format!("No recording session with id {}", session_id))
+                })?;
 
         session_guard.session.is_active = false;
         if let Some(writer) = session_guard.writer.take() {
@@ -256,11 +699,194 @@ impl RecordingManager {
                 .close()
                 .map_err(|e| ManagerError::Other(format!("Failed to close MCAP writer: {}", e)))?;
         }
-        let session = session_guard.session.clone();
+        let session = session_guard.status();
+        info!("Recording session {session_id} stopped");
+        self.broadcast_status(&session).await;
+        Ok(session)
+    }
+
+    async fn stop_ended_session(&self, session_id: Uuid) {
+        let ended = self
+            .sessions
+            .read()
+            .await
+            .get(&session_id)
+            .is_some_and(|guard| {
+                guard.session.is_active
+                    && guard.session.devices.iter().all(|device| !device.is_active)
+            });
+        if !ended {
+            return;
+        }
+
+        info!("Recording session {session_id}: every device stopped sending data");
+        if let Err(err) = self.stop_session(session_id).await {
+            error!("Failed to stop recording session {session_id}: {err:?}");
+        }
+    }
+
+    // Close the current file and continue the recording on a new one
+    async fn split_recording(&self, session_id: Uuid) -> Result<RecordingSession, ManagerError> {
+        let timestamp = chrono::Utc::now();
+
+        let mut sessions = self.sessions.write().await;
+        let session_guard = sessions
+            .get_mut(&session_id)
+            .filter(|guard| guard.session.is_active)
+            .ok_or_else(|| {
+                ManagerError::Other(format!("No recording session with id {}", session_id))
+            })?;
+        let file_path = self.file_path(&session_guard.file_prefix, timestamp);
+
+        if let Some(writer) = session_guard.writer.take() {
+            writer
+                .close()
+                .map_err(|e| ManagerError::Other(format!("Failed to close MCAP writer: {}", e)))?;
+        }
+        let writer = session_guard
+            .context
+            .mcap_writer()
+            .create_new_buffered_file(&file_path)
+            .map_err(|e| ManagerError::Other(format!("Failed to create MCAP file: {}", e)))?;
+
+        info!(
+            "Recording session {session_id} split, continuing on {file_path:?} after {:?}",
+            session_guard.session.file_path
+        );
+        session_guard.session.file_path = file_path;
+        session_guard.session.segment += 1;
+        session_guard.session.segment_start_time = timestamp;
+        write_session_metadata(&writer, &session_guard.session);
+        session_guard.writer = Some(writer);
+        let session = session_guard.status();
+        drop(sessions);
+
+        self.broadcast_status(&session).await;
+        Ok(session)
+    }
+
+    pub async fn set_session_tags(
+        &self,
+        session_id: Uuid,
+        tags: SessionTags,
+    ) -> Result<RecordingSession, ManagerError> {
+        tags.validate()?;
+
+        let mut sessions = self.sessions.write().await;
+        let session_guard = sessions.get_mut(&session_id).ok_or_else(|| {
+            ManagerError::Other(format!("No recording session with id {}", session_id))
+        })?;
+        session_guard.session.tags = tags;
+        if let Some(writer) = &session_guard.writer {
+            write_session_metadata(writer, &session_guard.session);
+        }
+        let session = session_guard.status();
+        drop(sessions);
+
         self.broadcast_status(&session).await;
         Ok(session)
     }
 
+    pub async fn add_marker(
+        &self,
+        session_id: Uuid,
+        marker: annotations::NewMarker,
+    ) -> Result<RecordingSession, ManagerError> {
+        marker.validate()?;
+
+        let mut sessions = self.sessions.write().await;
+        let session_guard = sessions.get_mut(&session_id).ok_or_else(|| {
+            ManagerError::Other(format!("No recording session with id {}", session_id))
+        })?;
+        if let Some(device_id) = marker.device_id {
+            if !session_guard.session.has_device(device_id) {
+                return Err(ManagerError::Other(format!(
+                    "Device {device_id} is not recorded on session {session_id}"
+                )));
+            }
+        }
+
+        let now = chrono::Utc::now();
+        let marker = Marker {
+            timestamp_us: now.timestamp_micros(),
+            text: marker.text,
+            device_id: marker.device_id,
+        };
+        session_guard.annotations.log_with_time(
+            &marker,
+            foxglove::schemas::Timestamp::new(now.timestamp() as u32, now.timestamp_subsec_nanos()),
+        );
+        session_guard
+            .channel_counts
+            .increment(annotations::ANNOTATIONS_TOPIC);
+        session_guard.session.markers.push(marker);
+        let session = session_guard.status();
+        drop(sessions);
+
+        info!("Recording session {session_id}: marker added");
+        self.broadcast_status(&session).await;
+        Ok(session)
+    }
+
+    // Split long recordings, delete old ones and stop everything before the disk gets full
+    async fn apply_policy(&self) {
+        let sessions: Vec<RecordingSession> = self
+            .sessions
+            .read()
+            .await
+            .values()
+            .filter(|guard| guard.session.is_active)
+            .map(|guard| guard.session.clone())
+            .collect();
+        if sessions.is_empty() {
+            return;
+        }
+
+        if let Err(warning) = self.check_free_disk_space() {
+            for session in sessions {
+                warn!(
+                    "Stopping recording session {}: {warning}",
+                    session.session_id
+                );
+                if let Some(guard) = self.sessions.write().await.get_mut(&session.session_id) {
+                    guard.session.warning = Some(warning.clone());
+                }
+                if let Err(err) = self.stop_session(session.session_id).await {
+                    error!(
+                        "Failed to stop recording session {}: {err:?}",
+                        session.session_id
+                    );
+                }
+            }
+            return;
+        }
+
+        let mut split = false;
+        for session in sessions {
+            let file_size = tokio::fs::metadata(&session.file_path)
+                .await
+                .map(|metadata| metadata.len())
+                .unwrap_or_default();
+            let elapsed = (chrono::Utc::now() - session.segment_start_time)
+                .to_std()
+                .unwrap_or_default();
+            if !self.policy.should_split(file_size, elapsed) {
+                continue;
+            }
+            match self.split_recording(session.session_id).await {
+                Ok(_) => split = true,
+                Err(err) => error!(
+                    "Failed to split recording session {}: {err:?}",
+                    session.session_id
+                ),
+            }
+        }
+
+        if split {
+            self.enforce_retention().await;
+        }
+    }
+
     pub async fn get_recording_status(
         &self,
         device_id: Uuid,
@@ -269,8 +895,21 @@ impl RecordingManager {
             .sessions
             .read()
             .await
-            .get(&device_id)
-            .map(|g| g.session.clone()))
+            .values()
+            .find(|guard| guard.session.has_device(device_id))
+            .map(SessionGuard::status))
+    }
+
+    pub async fn get_session_status(
+        &self,
+        session_id: Uuid,
+    ) -> Result<Option<RecordingSession>, ManagerError> {
+        Ok(self
+            .sessions
+            .read()
+            .await
+            .get(&session_id)
+            .map(SessionGuard::status))
     }
 
     pub async fn get_all_recording_status(&self) -> Result<Vec<RecordingSession>, ManagerError> {
@@ -279,105 +918,165 @@ impl RecordingManager {
             .read()
             .await
             .values()
-            .map(|g| g.session.clone())
+            .map(SessionGuard::status)
             .collect())
     }
+}
 
-    async fn recording_task(
-        handler: DeviceActorHandler,
-        _file_path: PathBuf,
-        sessions: Arc<RwLock<HashMap<Uuid, SessionGuard>>>,
-        device_id: Uuid,
-        ctx: Arc<Context>,
-        vehicle_data: Arc<RwLock<Option<VehicleData>>>,
-    ) -> Result<(), ManagerError> {
-        let subscriber = handler
-            .send(super::devices::PingRequest::GetSubscriber)
-            .await
-            .map_err(|err| {
-                warn!("Something went wrong while executing get_subscriber, details: {err:?}");
-                ManagerError::DeviceError(err)
-            })?;
-
-        let mut receiver = match subscriber {
-            super::devices::PingAnswer::Subscriber(subscriber) => subscriber,
-            msg => {
-                error!("Failed to receive broadcasted message: {:?}", msg);
-                return Err(ManagerError::NoDevices);
-            }
-        };
+impl RecordingTask {
+    async fn run(self) {
+        let RecordingTask {
+            source:
+                DeviceSource {
+                    device,
+                    mut receiver,
+                    mut control_receiver,
+                },
+            session_id,
+            raw_messages,
+            sessions,
+            ctx,
+            pose_history,
+            channel_counts,
+            ended,
+        } = self;
+        let device_id = device.device_id;
 
         // Define topic strings
         let ping1d_topic = format!("device_{}/Ping1D", device_id);
         let ping360_topic = format!("device_{}/Ping360", device_id);
-        let vehicle_topic = format!("device_{}/VehicleData", device_id);
+        let filtered_distance_topic = format!("device_{}/Ping1D/FilteredDistance", device_id);
+        let raw_topic = format!("device_{}/Raw", device_id);
+        let control_topic = format!("device_{}/Control", device_id);
 
         // Create device-specific channels with proper schema
         let ping1d_channel = ctx.channel_builder(&ping1d_topic).build::<ProfileStruct>();
         let ping360_channel = ctx
             .channel_builder(&ping360_topic)
             .build::<AutoDeviceDataStruct>();
-        let vehicle_channel = ctx.channel_builder(&vehicle_topic).build::<VehicleData>();
+        let mut pose_interpolation = PoseInterpolation::new(&ctx, device_id);
+        let mut pose_check = interval(POSE_CHECK_INTERVAL);
+        pose_check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let filtered_distance_channel = ctx
+            .channel_builder(&filtered_distance_topic)
+            .build::<FilteredDistance>();
+        let mut filtered_distance_receiver = distance_filter::subscribe(device_id);
+        // Every message byte for byte, including the ones without a decoded channel
+        let raw_channel = raw_messages.then(|| {
+            ctx.channel_builder(&raw_topic)
+                .build::<protocol::RawPingMessage>()
+        });
+        let control_channel = ctx
+            .channel_builder(&control_topic)
+            .build::<protocol::ControlRecord>();
 
         while {
             let sessions_guard = sessions.read().await;
             sessions_guard
-                .get(&device_id)
+                .get(&session_id)
                 .map(|s| s.session.is_active)
                 .unwrap_or(false)
         } {
-            match receiver.recv().await {
+            let received = tokio::select! {
+                received = receiver.recv() => received,
+                Ok(filtered_distance) = filtered_distance_receiver.recv() => {
+                    filtered_distance_channel
+                        .log_with_time(&filtered_distance, foxglove::schemas::Timestamp::now());
+                    channel_counts.increment(&filtered_distance_topic);
+                    continue;
+                }
+                _ = pose_check.tick(), if !pose_interpolation.is_empty() => {
+                    pose_interpolation
+                        .log_ready(&pose_history, &channel_counts, false)
+                        .await;
+                    continue;
+                }
+                Ok(control) = control_receiver.recv() => {
+                    control_channel.log_with_time(
+                        &protocol::ControlRecord::from(&control),
+                        foxglove::schemas::Timestamp::now(),
+                    );
+                    channel_counts.increment(&control_topic);
+                    continue;
+                }
+            };
+
+            match received {
                 Ok(msg) => {
-                    let timestamp = foxglove::schemas::Timestamp::now();
+                    let now = chrono::Utc::now();
+                    let timestamp = foxglove::schemas::Timestamp::new(
+                        now.timestamp() as u32,
+                        now.timestamp_subsec_nanos(),
+                    );
+                    if let Some(raw_channel) = &raw_channel {
+                        raw_channel.log_with_time(&protocol::RawPingMessage::from(&msg), timestamp);
+                        channel_counts.increment(&raw_topic);
+                    }
+                    let mut is_sonar_data = true;
                     // Handle Ping360
                     if let Ok(bluerobotics_ping::Messages::Ping360(
                         bluerobotics_ping::ping360::Messages::AutoDeviceData(answer),
                     )) = bluerobotics_ping::Messages::try_from(&msg)
                     {
                         ping360_channel.log_with_time(&answer, timestamp);
+                        channel_counts.increment(&ping360_topic);
                     } else if let Ok(bluerobotics_ping::Messages::Ping360(
                         bluerobotics_ping::ping360::Messages::DeviceData(answer),
                     )) = bluerobotics_ping::Messages::try_from(&msg)
                     {
-                        let autotransducer = AutoDeviceDataStruct {
-                            mode: answer.mode,
-                            gain_setting: answer.gain_setting,
-                            angle: answer.angle,
-                            transmit_duration: answer.transmit_duration,
-                            sample_period: answer.sample_period,
-                            transmit_frequency: answer.transmit_frequency,
-                            start_angle: 0,
-                            stop_angle: 399,
-                            num_steps: 1,
-                            delay: 0,
-                            number_of_samples: answer.number_of_samples,
-                            data_length: answer.number_of_samples,
-                            data: answer.data,
-                        };
+                        let autotransducer = protocol::auto_device_data(answer);
                         ping360_channel.log_with_time(&autotransducer, timestamp);
+                        channel_counts.increment(&ping360_topic);
                     } else if let Ok(bluerobotics_ping::Messages::Ping1D(
                         bluerobotics_ping::ping1d::Messages::Profile(answer),
                     )) = bluerobotics_ping::Messages::try_from(&msg)
                     {
                         ping1d_channel.log_with_time(&answer, timestamp);
+                        channel_counts.increment(&ping1d_topic);
+                    } else {
+                        is_sonar_data = false;
                     }
-                    if let Some(vehicle) = vehicle_data.read().await.as_ref() {
-                        vehicle_channel.log_with_time(vehicle, timestamp);
+
+                    if is_sonar_data {
+                        pose_interpolation.push(now);
+                        pose_interpolation
+                            .log_ready(&pose_history, &channel_counts, false)
+                            .await;
                     }
                 }
                 Err(e) => {
-                    error!("Failed to receive broadcasted message: {:?}", e);
+                    error!(
+                        "Recording of device {device_id} on session {session_id} failed to receive broadcasted message: {:?}",
+                        e
+                    );
                     break;
                 }
             }
         }
 
-        sessions.write().await.remove(&device_id);
-        Ok(())
+        pose_interpolation
+            .log_ready(&pose_history, &channel_counts, true)
+            .await;
+
+        // The rest of the session keeps recording without this device
+        if let Some(guard) = sessions.write().await.get_mut(&session_id) {
+            guard
+                .session
+                .devices
+                .iter_mut()
+                .filter(|device| device.device_id == device_id)
+                .for_each(|device| device.is_active = false);
+        }
+        let _ = ended.send(session_id).await;
     }
 }
 
 impl RecordingsManagerHandler {
+    /// Directory where the recordings are stored
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
+    }
+
     pub async fn send(&self, request: RecordingManagerCommand) -> Result<Answer, ManagerError> {
         let (result_sender, result_receiver) = oneshot::channel();
 
@@ -407,3 +1106,195 @@ impl RecordingsManagerHandler {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vehicle::pose_history::PoseHistory;
+
+    #[test]
+    fn test_channel_counts() {
+        let counts = ChannelCounts::default();
+        let shared = counts.clone();
+        counts.increment("device_1/Ping360");
+        shared.increment("device_1/Ping360");
+        shared.increment("device_2/Ping1D");
+
+        assert_eq!(
+            counts.snapshot(),
+            BTreeMap::from([
+                ("device_1/Ping360".to_string(), 2),
+                ("device_2/Ping1D".to_string(), 1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_session_metadata() {
+        let device_id = Uuid::new_v4();
+        let start_time = chrono::Utc::now();
+        let session = RecordingSession {
+            session_id: Uuid::new_v4(),
+            devices: vec![RecordedDevice {
+                device_id,
+                device_type: DeviceSelection::Ping360,
+                is_active: true,
+            }],
+            file_path: PathBuf::from("session.mcap"),
+            is_active: true,
+            start_time,
+            segment: 2,
+            segment_start_time: start_time,
+            warning: None,
+            channel_counts: BTreeMap::new(),
+            tags: SessionTags {
+                dive: Some("Dive 4".to_string()),
+                operator: None,
+                site: Some("North reef".to_string()),
+            },
+            markers: Vec::new(),
+        };
+
+        let metadata = session.metadata();
+        assert_eq!(metadata["devices"], format!("{device_id}:Ping360"));
+        assert_eq!(metadata["segment"], "2");
+        assert_eq!(metadata["dive"], "Dive 4");
+        assert_eq!(metadata["site"], "North reef");
+        assert!(!metadata.contains_key("operator"));
+    }
+
+    #[tokio::test]
+    async fn test_pose_interpolated_once_next_pose_received() {
+        let path = std::env::temp_dir().join(format!("recording_{}.mcap", Uuid::new_v4()));
+        let ctx = Context::new();
+        let writer = ctx.mcap_writer().create_new_buffered_file(&path).unwrap();
+
+        let device_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let start_time = chrono::Utc::now();
+        let device = RecordedDevice {
+            device_id,
+            device_type: DeviceSelection::Ping1D,
+            is_active: true,
+        };
+        let session = RecordingSession {
+            session_id,
+            devices: vec![device.clone()],
+            file_path: path.clone(),
+            is_active: true,
+            start_time,
+            segment: 0,
+            segment_start_time: start_time,
+            warning: None,
+            channel_counts: BTreeMap::new(),
+            tags: SessionTags::default(),
+            markers: Vec::new(),
+        };
+        let channel_counts = ChannelCounts::default();
+        let sessions = Arc::new(RwLock::new(HashMap::from([(
+            session_id,
+            SessionGuard {
+                session,
+                writer: None,
+                context: ctx.clone(),
+                channel_counts: channel_counts.clone(),
+                annotations: ctx
+                    .channel_builder(annotations::ANNOTATIONS_TOPIC)
+                    .build::<Marker>(),
+                file_prefix: String::new(),
+            },
+        )])));
+
+        let pose_history: SharedPoseHistory = Arc::new(RwLock::new(PoseHistory::default()));
+        let (sender, receiver) = broadcast::channel(10);
+        let (_control_sender, control_receiver) = broadcast::channel(10);
+        let (ended, mut ended_receiver) = mpsc::channel(1);
+        let task = tokio::spawn(
+            RecordingTask {
+                source: DeviceSource {
+                    device,
+                    receiver,
+                    control_receiver,
+                },
+                session_id,
+                raw_messages: false,
+                sessions,
+                ctx: ctx.clone(),
+                pose_history: pose_history.clone(),
+                channel_counts: channel_counts.clone(),
+                ended,
+            }
+            .run(),
+        );
+
+        let pose = |lat| VehicleData {
+            lat,
+            ..Default::default()
+        };
+        let pose_topic = format!("device_{device_id}/InterpolatedPose");
+        let pose_count = || {
+            channel_counts
+                .snapshot()
+                .get(&pose_topic)
+                .copied()
+                .unwrap_or_default()
+        };
+
+        pose_history
+            .write()
+            .await
+            .push(chrono::Utc::now(), pose(10.0));
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let mut message = ProtocolMessage::new();
+        message.set_message(&bluerobotics_ping::ping1d::Messages::Profile(
+            ProfileStruct {
+                distance: 1000,
+                confidence: 100,
+                transmit_duration: 0,
+                ping_number: 0,
+                scan_start: 0,
+                scan_length: 0,
+                gain_setting: 0,
+                profile_data_length: 0,
+                profile_data: vec![],
+            },
+        ));
+        sender.send(message).unwrap();
+
+        // The sample waits for the next pose
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(pose_count(), 0);
+
+        pose_history
+            .write()
+            .await
+            .push(chrono::Utc::now(), pose(20.0));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(pose_count(), 1);
+
+        drop(sender);
+        task.await.unwrap();
+        assert_eq!(ended_receiver.recv().await, Some(session_id));
+        writer.close().unwrap();
+
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut sample_time = None;
+        let mut interpolated = None;
+        for message in mcap::MessageStream::new(&content).unwrap() {
+            let message = message.unwrap();
+            if message.channel.topic.ends_with("/Ping1D") {
+                sample_time = Some(message.log_time);
+            } else if message.channel.topic == pose_topic {
+                let pose: InterpolatedPose = serde_json::from_slice(&message.data).unwrap();
+                interpolated = Some((message.log_time, pose));
+            }
+        }
+
+        let (log_time, pose) = interpolated.unwrap();
+        assert_eq!(Some(log_time), sample_time);
+        assert!(pose.interpolated);
+        assert!(!pose.stale);
+        assert!(pose.pose.lat > 10.0 && pose.pose.lat < 20.0);
+    }
+}
@@ -0,0 +1,247 @@
+use std::io::{self, Read, Write};
+
+use chrono::{DateTime, NaiveDateTime, NaiveTime, Utc};
+
+// Sensor log files written by Ping Viewer: a header followed by every frame received from the
+// device, with the Qt data stream layout (big endian integers, UTF-16 strings prefixed with
+// their length in bytes)
+const HEADER_STRING: &str = "PingViewer sensor log file";
+const HEADER_VERSION: i32 = 1;
+const TIME_FORMAT: &str = "%H:%M:%S%.3f";
+// Null byte arrays are written with this length and no content
+const NULL_LENGTH: u32 = u32::MAX;
+
+pub const SENSOR_FAMILY_PING: i32 = 1;
+pub const SENSOR_TYPE_PING1D: i32 = 1;
+pub const SENSOR_TYPE_PING360: i32 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogHeader {
+    pub version: i32,
+    pub hash_commit: String,
+    pub date: String,
+    pub tag: String,
+    pub os_name: String,
+    pub os_version: String,
+    pub sensor_family: i32,
+    pub sensor_type: i32,
+}
+
+impl LogHeader {
+    pub fn new(sensor_type: i32, date: String) -> Self {
+        Self {
+            version: HEADER_VERSION,
+            hash_commit: String::new(),
+            date,
+            tag: format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+            os_name: std::env::consts::OS.to_string(),
+            os_version: String::new(),
+            sensor_family: SENSOR_FAMILY_PING,
+            sensor_type,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    /// Time of day the frame was received
+    pub time: Option<NaiveTime>,
+    /// Whole frame, header and checksum included
+    pub frame: Vec<u8>,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn write_int(output: &mut impl Write, value: i32) -> io::Result<()> {
+    output.write_all(&value.to_be_bytes())
+}
+
+fn write_array(output: &mut impl Write, value: &[u8]) -> io::Result<()> {
+    let length = u32::try_from(value.len())
+        .ok()
+        .filter(|length| *length != NULL_LENGTH)
+        .ok_or_else(|| invalid_data(format!("Entry too large: {} bytes", value.len())))?;
+    output.write_all(&length.to_be_bytes())?;
+    output.write_all(value)
+}
+
+fn write_string(output: &mut impl Write, value: &str) -> io::Result<()> {
+    let value: Vec<u8> = value.encode_utf16().flat_map(u16::to_be_bytes).collect();
+    write_array(output, &value)
+}
+
+fn read_int(input: &mut impl Read) -> io::Result<i32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(i32::from_be_bytes(bytes))
+}
+
+fn read_array(input: &mut &[u8]) -> io::Result<Vec<u8>> {
+    let length = read_int(input)? as u32;
+    if length == NULL_LENGTH {
+        return Ok(Vec::new());
+    }
+    let length = length as usize;
+    if length > input.len() {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    let (value, rest) = input.split_at(length);
+    *input = rest;
+    Ok(value.to_vec())
+}
+
+fn read_string(input: &mut &[u8]) -> io::Result<String> {
+    let value = read_array(input)?;
+    if value.len() % 2 != 0 {
+        return Err(invalid_data(format!(
+            "Invalid string: odd length {}",
+            value.len()
+        )));
+    }
+    let value: Vec<u16> = value
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect();
+    String::from_utf16(&value).map_err(|err| invalid_data(format!("Invalid string: {err}")))
+}
+
+/// Start of the log from its file name, Ping Viewer names them like `20190626-104135491.bin`
+pub fn file_name_time(file_name: &str) -> Option<DateTime<Utc>> {
+    let date = file_name.get(..15)?;
+    NaiveDateTime::parse_from_str(date, "%Y%m%d-%H%M%S")
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+pub fn write_header(output: &mut impl Write, header: &LogHeader) -> io::Result<()> {
+    write_string(output, HEADER_STRING)?;
+    write_int(output, header.version)?;
+    for value in [
+        &header.hash_commit,
+        &header.date,
+        &header.tag,
+        &header.os_name,
+        &header.os_version,
+    ] {
+        write_string(output, value)?;
+    }
+    write_int(output, header.sensor_family)?;
+    write_int(output, header.sensor_type)
+}
+
+pub fn write_entry(output: &mut impl Write, time: NaiveTime, frame: &[u8]) -> io::Result<()> {
+    write_string(output, &time.format(TIME_FORMAT).to_string())?;
+    write_array(output, frame)
+}
+
+pub fn read_log(mut content: &[u8]) -> io::Result<(LogHeader, Vec<LogEntry>)> {
+    let input = &mut content;
+    let header_string = read_string(input)?;
+    if header_string != HEADER_STRING {
+        return Err(invalid_data(
+            "Not a Ping Viewer sensor log file".to_string(),
+        ));
+    }
+    let header = LogHeader {
+        version: read_int(input)?,
+        hash_commit: read_string(input)?,
+        date: read_string(input)?,
+        tag: read_string(input)?,
+        os_name: read_string(input)?,
+        os_version: read_string(input)?,
+        sensor_family: read_int(input)?,
+        sensor_type: read_int(input)?,
+    };
+
+    let mut entries = Vec::new();
+    while !input.is_empty() {
+        let entry = read_string(input).and_then(|time| {
+            Ok(LogEntry {
+                time: NaiveTime::parse_from_str(&time, TIME_FORMAT).ok(),
+                frame: read_array(input)?,
+            })
+        });
+        match entry {
+            Ok(entry) => entries.push(entry),
+            // Logs of a Ping Viewer that was closed abruptly end with a partial entry
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok((header, entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Log of a Ping1D written by Ping Viewer, with two general requests for a profile
+    const PING1D_LOG: &[u8] = b"\
+        \x00\x00\x00\x34\x00P\x00i\x00n\x00g\x00V\x00i\x00e\x00w\x00e\x00r\x00 \x00s\x00e\x00n\x00s\x00o\x00r\x00 \x00l\x00o\x00g\x00 \x00f\x00i\x00l\x00e\
+        \x00\x00\x00\x01\
+        \x00\x00\x00\x0e\x004\x00f\x00d\x003\x00a\x006\x00e\
+        \x00\x00\x00\x14\x002\x000\x001\x009\x00-\x000\x006\x00-\x002\x006\
+        \x00\x00\x00\x00\
+        \x00\x00\x00\x0a\x00l\x00i\x00n\x00u\x00x\
+        \x00\x00\x00\x18\x00U\x00b\x00u\x00n\x00t\x00u\x00 \x001\x008\x00.\x000\x004\
+        \x00\x00\x00\x01\
+        \x00\x00\x00\x01\
+        \x00\x00\x00\x18\x001\x000\x00:\x004\x001\x00:\x003\x005\x00.\x004\x009\x001\
+        \x00\x00\x00\x0c\x42\x52\x02\x00\x06\x00\x00\x00\xbb\x04\x5b\x01\
+        \x00\x00\x00\x18\x001\x000\x00:\x004\x001\x00:\x003\x005\x00.\x005\x009\x001\
+        \x00\x00\x00\x0c\x42\x52\x02\x00\x06\x00\x00\x00\xbb\x04\x5b\x01";
+
+    #[test]
+    fn test_ping_viewer_log() {
+        let header = LogHeader {
+            version: 1,
+            hash_commit: "4fd3a6e".to_string(),
+            date: "2019-06-26".to_string(),
+            tag: String::new(),
+            os_name: "linux".to_string(),
+            os_version: "Ubuntu 18.04".to_string(),
+            sensor_family: SENSOR_FAMILY_PING,
+            sensor_type: SENSOR_TYPE_PING1D,
+        };
+        let frame = [
+            0x42, 0x52, 0x02, 0x00, 0x06, 0x00, 0x00, 0x00, 0xbb, 0x04, 0x5b, 0x01,
+        ];
+        let times = [
+            NaiveTime::from_hms_milli_opt(10, 41, 35, 491).unwrap(),
+            NaiveTime::from_hms_milli_opt(10, 41, 35, 591).unwrap(),
+        ];
+
+        let (read_header, entries) = read_log(PING1D_LOG).unwrap();
+        assert_eq!(read_header, header);
+        assert_eq!(entries.len(), times.len());
+        for (entry, time) in entries.iter().zip(times) {
+            assert_eq!(entry.time, Some(time));
+            assert_eq!(entry.frame, frame);
+        }
+
+        let mut content = Vec::new();
+        write_header(&mut content, &header).unwrap();
+        for time in times {
+            write_entry(&mut content, time, &frame).unwrap();
+        }
+        assert_eq!(content, PING1D_LOG);
+
+        // A truncated last entry is dropped
+        let truncated = &PING1D_LOG[..PING1D_LOG.len() - 2];
+        assert_eq!(read_log(truncated).unwrap().1.len(), 1);
+
+        assert!(read_log(b"\x00\x00\x00\x08\x00t\x00e\x00s\x00t").is_err());
+    }
+
+    #[test]
+    fn test_file_name_time() {
+        assert_eq!(
+            file_name_time("20190626-104135491.bin").map(|time| time.to_rfc3339()),
+            Some("2019-06-26T10:41:35+00:00".to_string())
+        );
+        assert_eq!(file_name_time("log.bin"), None);
+    }
+}
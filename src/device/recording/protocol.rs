@@ -0,0 +1,105 @@
+use base64::Engine;
+use bluerobotics_ping::{
+    message::ProtocolMessage,
+    ping360::{AutoDeviceDataStruct, DeviceDataStruct},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::device::devices::PingControlMessage;
+
+/// Protocol message exactly as received from the device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct RawPingMessage {
+    pub message_id: u16,
+    /// Whole frame, header and checksum included, base64 encoded
+    pub frame: String,
+}
+
+impl From<&ProtocolMessage> for RawPingMessage {
+    fn from(message: &ProtocolMessage) -> Self {
+        Self {
+            message_id: message.message_id,
+            frame: base64::engine::general_purpose::STANDARD.encode(message.serialized()),
+        }
+    }
+}
+
+impl RawPingMessage {
+    pub fn frame_bytes(&self) -> Result<Vec<u8>, base64::DecodeError> {
+        base64::engine::general_purpose::STANDARD.decode(&self.frame)
+    }
+}
+
+/// Ping360 data is always recorded as a scan, single angle requests become a one step scan
+pub fn auto_device_data(data: DeviceDataStruct) -> AutoDeviceDataStruct {
+    AutoDeviceDataStruct {
+        mode: data.mode,
+        gain_setting: data.gain_setting,
+        angle: data.angle,
+        transmit_duration: data.transmit_duration,
+        sample_period: data.sample_period,
+        transmit_frequency: data.transmit_frequency,
+        start_angle: 0,
+        stop_angle: 399,
+        num_steps: 1,
+        delay: 0,
+        number_of_samples: data.number_of_samples,
+        data_length: data.number_of_samples,
+        data: data.data,
+    }
+}
+
+/// Request sent to the device while recording
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct ControlRecord {
+    pub request: serde_json::Value,
+    /// Error returned while handling the request, empty when it succeeded
+    pub error: Option<String>,
+}
+
+impl From<&PingControlMessage> for ControlRecord {
+    fn from(message: &PingControlMessage) -> Self {
+        Self {
+            request: serde_json::to_value(&message.request).unwrap_or_default(),
+            error: message.error.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bluerobotics_ping::decoder::{Decoder, DecoderResult};
+
+    #[test]
+    fn test_raw_message_round_trip() {
+        let mut message = ProtocolMessage::new();
+        message.set_message(&bluerobotics_ping::ping1d::Messages::Distance(
+            bluerobotics_ping::ping1d::DistanceStruct {
+                distance: 1234,
+                confidence: 90,
+                transmit_duration: 100,
+                ping_number: 7,
+                scan_start: 0,
+                scan_length: 5000,
+                gain_setting: 2,
+            },
+        ));
+
+        let raw = RawPingMessage::from(&message);
+        let frame = raw.frame_bytes().unwrap();
+        assert_eq!(frame, message.serialized());
+
+        let mut decoder = Decoder::new();
+        let decoded = frame
+            .iter()
+            .map(|byte| decoder.parse_byte(*byte))
+            .find_map(|result| match result {
+                DecoderResult::Success(message) => Some(message),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(decoded.message_id, raw.message_id);
+        assert_eq!(decoded.serialized(), frame);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use validator::Validate;
+
+/// Interval between checks of the split, retention and free disk space policies
+pub const POLICY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+const MEGABYTE: u64 = 1024 * 1024;
+const GIGABYTE: f64 = 1024.0 * 1024.0 * 1024.0;
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Validate, Apiv2Schema)]
+#[serde(default)]
+pub struct RecordingPolicy {
+    /// Start a new file once the current one reaches this size, in megabytes
+    #[validate(range(min = 1))]
+    pub max_file_size_mb: Option<u64>,
+    /// Start a new file once the current one covers this duration, in seconds
+    #[validate(range(min = 10))]
+    pub max_duration_s: Option<u64>,
+    /// Keep at most this amount of recordings, in gigabytes, deleting the oldest first
+    #[validate(range(exclusive_min = 0.0))]
+    pub retention_gb: Option<f64>,
+    /// Delete recordings older than this number of days
+    #[validate(range(min = 1))]
+    pub retention_days: Option<u32>,
+    /// Stop recording when the free disk space falls below this amount, in megabytes
+    pub min_free_disk_mb: u64,
+    /// Also record every protocol message byte for byte, used by new sessions
+    pub raw_messages: bool,
+}
+
+impl Default for RecordingPolicy {
+    fn default() -> Self {
+        Self {
+            max_file_size_mb: None,
+            max_duration_s: None,
+            retention_gb: None,
+            retention_days: None,
+            min_free_disk_mb: 500,
+            raw_messages: false,
+        }
+    }
+}
+
+impl RecordingPolicy {
+    pub fn should_split(&self, file_size: u64, elapsed: Duration) -> bool {
+        self.max_file_size_mb
+            .is_some_and(|max| file_size >= max.saturating_mul(MEGABYTE))
+            || self
+                .max_duration_s
+                .is_some_and(|max| elapsed >= Duration::from_secs(max))
+    }
+
+    pub fn is_disk_low(&self, free_space: u64) -> bool {
+        free_space < self.min_free_disk_mb.saturating_mul(MEGABYTE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFile {
+    pub path: PathBuf,
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+// Files deleted by the retention policy: the expired ones, then the oldest until the total fits
+pub fn retention_victims(
+    mut files: Vec<RecordedFile>,
+    policy: &RecordingPolicy,
+    now: SystemTime,
+) -> Vec<PathBuf> {
+    files.sort_by_key(|file| file.modified);
+
+    let mut victims = Vec::new();
+    if let Some(days) = policy.retention_days {
+        let max_age = DAY * days;
+        files.retain(|file| {
+            let expired = now
+                .duration_since(file.modified)
+                .is_ok_and(|age| age > max_age);
+            if expired {
+                victims.push(file.path.clone());
+            }
+            !expired
+        });
+    }
+
+    if let Some(gigabytes) = policy.retention_gb {
+        let max_size = (gigabytes * GIGABYTE) as u64;
+        let mut total: u64 = files.iter().map(|file| file.size).sum();
+        for file in &files {
+            if total <= max_size {
+                break;
+            }
+            total -= file.size;
+            victims.push(file.path.clone());
+        }
+    }
+
+    victims
+}
+
+fn recorded_files(base_path: &Path) -> Vec<RecordedFile> {
+    let Ok(entries) = fs::read_dir(base_path) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "mcap"))
+        .filter_map(|entry| {
+            let metadata = entry
+                .metadata()
+                .ok()
+                .filter(|metadata| metadata.is_file())?;
+            Some(RecordedFile {
+                path: entry.path(),
+                size: metadata.len(),
+                modified: metadata.modified().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Delete old recordings according to the policy, files being written are never deleted
+pub fn enforce_retention(base_path: &Path, policy: &RecordingPolicy, active: &[PathBuf]) {
+    if policy.retention_gb.is_none() && policy.retention_days.is_none() {
+        return;
+    }
+
+    // Files being written still count on the total, so the oldest closed ones go first
+    let files = recorded_files(base_path);
+    for path in retention_victims(files, policy, SystemTime::now()) {
+        if active.contains(&path) {
+            continue;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => info!("Recording {path:?} deleted by the retention policy"),
+            Err(err) => warn!("Failed to delete recording {path:?}: {err}"),
+        }
+    }
+}
+
+/// Space available to the application on the disk holding the path, in bytes
+#[cfg(unix)]
+pub fn free_disk_space(path: &Path) -> Option<u64> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: the path is a valid C string and stat is a properly sized output buffer
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+pub fn free_disk_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size_mb: u64, age_days: u32) -> RecordedFile {
+        RecordedFile {
+            path: PathBuf::from(name),
+            size: size_mb * MEGABYTE,
+            modified: SystemTime::UNIX_EPOCH + DAY * (100 - age_days),
+        }
+    }
+
+    #[test]
+    fn test_retention_victims() {
+        let now = SystemTime::UNIX_EPOCH + DAY * 100;
+        let files = vec![
+            file("new.mcap", 600, 0),
+            file("old.mcap", 300, 10),
+            file("middle.mcap", 400, 2),
+        ];
+
+        let policy = RecordingPolicy {
+            retention_days: Some(5),
+            ..Default::default()
+        };
+        assert_eq!(
+            retention_victims(files.clone(), &policy, now),
+            [PathBuf::from("old.mcap")]
+        );
+
+        let policy = RecordingPolicy {
+            retention_gb: Some(0.9),
+            ..Default::default()
+        };
+        assert_eq!(
+            retention_victims(files.clone(), &policy, now),
+            [PathBuf::from("old.mcap"), PathBuf::from("middle.mcap")]
+        );
+
+        assert!(retention_victims(files, &RecordingPolicy::default(), now).is_empty());
+    }
+
+    #[test]
+    fn test_should_split() {
+        let policy = RecordingPolicy {
+            max_file_size_mb: Some(100),
+            max_duration_s: Some(600),
+            ..Default::default()
+        };
+        assert!(!policy.should_split(10 * MEGABYTE, Duration::from_secs(60)));
+        assert!(policy.should_split(100 * MEGABYTE, Duration::from_secs(60)));
+        assert!(policy.should_split(0, Duration::from_secs(600)));
+        assert!(!RecordingPolicy::default().should_split(u64::MAX, Duration::MAX));
+
+        assert!(policy.is_disk_low(100 * MEGABYTE));
+        assert!(!policy.is_disk_low(1024 * MEGABYTE));
+    }
+}
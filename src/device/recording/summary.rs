@@ -0,0 +1,283 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io,
+    path::Path,
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use uuid::Uuid;
+
+use super::{
+    annotations::{self, MetadataRecord},
+    export::{log_time_to_date, ConversionError},
+};
+use crate::vehicle::VehicleData;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct ChannelSummary {
+    pub topic: String,
+    pub schema: Option<String>,
+    pub message_count: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub min_longitude: f64,
+    pub max_latitude: f64,
+    pub max_longitude: f64,
+}
+
+impl BoundingBox {
+    fn point(latitude: f64, longitude: f64) -> Self {
+        Self {
+            min_latitude: latitude,
+            min_longitude: longitude,
+            max_latitude: latitude,
+            max_longitude: longitude,
+        }
+    }
+
+    fn extend(&mut self, latitude: f64, longitude: f64) {
+        self.min_latitude = self.min_latitude.min(latitude);
+        self.min_longitude = self.min_longitude.min(longitude);
+        self.max_latitude = self.max_latitude.max(latitude);
+        self.max_longitude = self.max_longitude.max(longitude);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct RecordingSummary {
+    pub file_size: u64,
+    /// False for files without a summary section, like recordings still being written,
+    /// everything is then counted from the messages
+    pub has_summary: bool,
+    pub channels: Vec<ChannelSummary>,
+    pub message_count: u64,
+    /// Time of the first message, RFC 3339
+    pub start_time: Option<String>,
+    /// Time of the last message, RFC 3339
+    pub end_time: Option<String>,
+    pub duration_s: f64,
+    pub devices: Vec<Uuid>,
+    /// Area covered by the vehicle while recording, empty without GPS positions
+    pub bounding_box: Option<BoundingBox>,
+    pub metadata: Vec<MetadataRecord>,
+}
+
+/// Map a recording in memory instead of reading it, as they can be several gigabytes long
+pub fn map_file(path: &Path) -> io::Result<memmap2::Mmap> {
+    let file = File::open(path)?;
+    // SAFETY: recordings are only appended to while being written, never truncated in place
+    unsafe { memmap2::Mmap::map(&file) }
+}
+
+/// Device of a channel written by RecordingManager, like `device_{id}/Ping360`
+pub fn topic_device(topic: &str) -> Option<Uuid> {
+    let (prefix, _) = topic.split_once('/')?;
+    Uuid::parse_str(prefix.strip_prefix("device_")?).ok()
+}
+
+/// Start of a recording from its file name, like `device_{id}_20250102_030405.mcap`
+pub fn file_name_time(file_name: &str) -> Option<DateTime<Utc>> {
+    let stem = file_name.strip_suffix(".mcap")?;
+    let date = stem.get(stem.len().checked_sub(15)?..)?;
+    NaiveDateTime::parse_from_str(date, "%Y%m%d_%H%M%S")
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+/// Devices recorded on a file, from its name or from the channels of its summary section
+pub fn file_devices(path: &Path) -> Vec<Uuid> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    if let Some(device_id) = file_name
+        .strip_prefix("device_")
+        .and_then(|name| name.get(..36))
+        .and_then(|id| Uuid::parse_str(id).ok())
+    {
+        return vec![device_id];
+    }
+
+    let content = match map_file(path) {
+        Ok(content) => content,
+        Err(err) => {
+            warn!("Failed to open {path:?}: {err}");
+            return Vec::new();
+        }
+    };
+    match mcap::Summary::read(&content) {
+        Ok(Some(summary)) => summary
+            .channels
+            .values()
+            .filter_map(|channel| topic_device(&channel.topic))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect(),
+        Ok(None) => Vec::new(),
+        Err(err) => {
+            warn!("Failed to read summary of {path:?}: {err}");
+            Vec::new()
+        }
+    }
+}
+
+fn is_vehicle_topic(topic: &str) -> bool {
+    topic.ends_with("/VehicleData")
+}
+
+fn extend_bounding_box(bounding_box: &mut Option<BoundingBox>, message: &mcap::Message) {
+    if !is_vehicle_topic(&message.channel.topic) {
+        return;
+    }
+    let Ok(pose) = serde_json::from_slice::<VehicleData>(&message.data) else {
+        return;
+    };
+    let (latitude, longitude) = (pose.lat, pose.lon);
+    // The autopilot reports a null position until it gets a GPS fix
+    if latitude == 0.0 && longitude == 0.0 {
+        return;
+    }
+    match bounding_box {
+        Some(bounding_box) => bounding_box.extend(latitude, longitude),
+        None => *bounding_box = Some(BoundingBox::point(latitude, longitude)),
+    }
+}
+
+pub fn summarize(content: &[u8]) -> Result<RecordingSummary, ConversionError> {
+    let summary = mcap::Summary::read(content)?;
+    let statistics = summary
+        .as_ref()
+        .and_then(|summary| Some((summary, summary.stats.as_ref()?)));
+
+    let mut channels: BTreeMap<String, ChannelSummary> = BTreeMap::new();
+    let mut message_count = 0;
+    let mut times: Option<(u64, u64)> = None;
+    if let Some((summary, statistics)) = statistics {
+        for channel in summary.channels.values() {
+            let count = statistics
+                .channel_message_counts
+                .get(&channel.id)
+                .copied()
+                .unwrap_or_default();
+            channels
+                .entry(channel.topic.clone())
+                .or_insert_with(|| ChannelSummary {
+                    topic: channel.topic.clone(),
+                    schema: channel.schema.as_ref().map(|schema| schema.name.clone()),
+                    message_count: 0,
+                })
+                .message_count += count;
+        }
+        message_count = statistics.message_count;
+        if message_count > 0 {
+            times = Some((statistics.message_start_time, statistics.message_end_time));
+        }
+    }
+
+    // Positions are only found on the messages, the rest too when there is no summary
+    let mut bounding_box: Option<BoundingBox> = None;
+    match statistics {
+        Some((summary, _)) if !summary.chunk_indexes.is_empty() => {
+            // Only go through the chunks holding vehicle data
+            let vehicle_channels: BTreeSet<u16> = summary
+                .channels
+                .values()
+                .filter(|channel| is_vehicle_topic(&channel.topic))
+                .map(|channel| channel.id)
+                .collect();
+            for index in summary.chunk_indexes.iter().filter(|index| {
+                index
+                    .message_index_offsets
+                    .keys()
+                    .any(|id| vehicle_channels.contains(id))
+            }) {
+                for message in summary.stream_chunk(content, index)? {
+                    extend_bounding_box(&mut bounding_box, &message?);
+                }
+            }
+        }
+        Some(_) => {
+            for message in mcap::MessageStream::new(content)? {
+                extend_bounding_box(&mut bounding_box, &message?);
+            }
+        }
+        None => {
+            for message in mcap::MessageStream::new(content)? {
+                let message = message?;
+                let topic = &message.channel.topic;
+                channels
+                    .entry(topic.clone())
+                    .or_insert_with(|| ChannelSummary {
+                        topic: topic.clone(),
+                        schema: message
+                            .channel
+                            .schema
+                            .as_ref()
+                            .map(|schema| schema.name.clone()),
+                        message_count: 0,
+                    })
+                    .message_count += 1;
+                message_count += 1;
+                times = Some(match times {
+                    Some((start, end)) => (start.min(message.log_time), end.max(message.log_time)),
+                    None => (message.log_time, message.log_time),
+                });
+                extend_bounding_box(&mut bounding_box, &message);
+            }
+        }
+    }
+
+    let devices = channels
+        .keys()
+        .filter_map(|topic| topic_device(topic))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+
+    Ok(RecordingSummary {
+        file_size: content.len() as u64,
+        has_summary: statistics.is_some(),
+        channels: channels.into_values().collect(),
+        message_count,
+        start_time: times.map(|(start, _)| log_time_to_date(start).to_rfc3339()),
+        end_time: times.map(|(_, end)| log_time_to_date(end).to_rfc3339()),
+        duration_s: times
+            .map(|(start, end)| end.saturating_sub(start) as f64 / 1e9)
+            .unwrap_or_default(),
+        devices,
+        bounding_box,
+        metadata: match &summary {
+            Some(summary) => annotations::indexed_metadata(content, summary)?,
+            None => annotations::read_metadata(content)?,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_names() {
+        let device_id = Uuid::new_v4();
+        assert_eq!(
+            topic_device(&format!("device_{device_id}/Ping360")),
+            Some(device_id)
+        );
+        assert_eq!(topic_device("session/Annotations"), None);
+
+        assert_eq!(
+            file_name_time(&format!("device_{device_id}_20250102_030405.mcap"))
+                .map(|time| time.to_rfc3339()),
+            Some("2025-01-02T03:04:05+00:00".to_string())
+        );
+        assert_eq!(file_name_time("imported_log.mcap"), None);
+    }
+}
@@ -0,0 +1,752 @@
+use bluerobotics_ping::{
+    common::{AckStruct, DeviceInformationStruct, ProtocolVersionStruct},
+    decoder::{Decoder, DecoderResult},
+    message::{MessageInfo, PingMessage, ProtocolMessage},
+    ping1d::ProfileStruct,
+    ping360::{AutoDeviceDataStruct, DeviceDataStruct},
+};
+use std::path::Path;
+
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, DuplexStream, WriteHalf},
+    sync::{mpsc, oneshot},
+    time::{sleep_until, Duration, Instant},
+};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::device::{
+    manager::{DeviceSelection, ManagerError, SourceReplayStruct},
+    recording::summary::map_file,
+};
+
+// Size of the in-memory pipe between the replay task and the virtual device
+const REPLAY_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub enum ReplayCommand {
+    Pause,
+    Resume,
+    /// Position in milliseconds from the beginning of the recording
+    Seek(u64),
+    /// Playback rate, 1.0 is the recorded rate
+    SetSpeed(f32),
+    Status,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayStatus {
+    pub path: String,
+    pub paused: bool,
+    pub finished: bool,
+    pub speed: f32,
+    #[serde(rename = "loop")]
+    pub looping: bool,
+    pub position_ms: u64,
+    pub duration_ms: u64,
+    pub frame_index: usize,
+    pub frame_count: usize,
+}
+
+#[derive(Debug)]
+pub struct ReplayActorRequest {
+    pub request: ReplayCommand,
+    pub respond_to: oneshot::Sender<Result<ReplayStatus, ManagerError>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ReplayHandler {
+    sender: mpsc::Sender<ReplayActorRequest>,
+}
+
+#[derive(Debug, Clone)]
+enum ReplayMessage {
+    Ping1D(ProfileStruct),
+    Ping360(AutoDeviceDataStruct),
+}
+
+#[derive(Debug, Clone)]
+struct ReplayFrame {
+    // Nanoseconds since the first replayed message
+    position: u64,
+    // Chunk of the recording holding the frame, and its rank among the messages of the replayed
+    // channel in it
+    chunk: usize,
+    index: usize,
+}
+
+// Recordings can be several gigabytes long, so only the frames of one chunk are kept decoded
+enum FrameSource {
+    Chunks {
+        content: memmap2::Mmap,
+        summary: Box<mcap::Summary>,
+        channel_id: u16,
+        device_type: DeviceSelection,
+        cache: Option<(usize, Vec<Option<ReplayMessage>>)>,
+    },
+    // Recordings still being written have no summary section to find their chunks
+    Decoded(Vec<ReplayMessage>),
+}
+
+struct ReplayActor {
+    receiver: mpsc::Receiver<ReplayActorRequest>,
+    path: String,
+    device_type: DeviceSelection,
+    frames: Vec<ReplayFrame>,
+    source: FrameSource,
+    index: usize,
+    last_sent: Option<usize>,
+    speed: f32,
+    looping: bool,
+    paused: bool,
+    paused_position: u64,
+    anchor_instant: Instant,
+    anchor_position: u64,
+}
+
+// Open a recording as a virtual device, the returned stream should be used as a regular device port
+pub async fn open(
+    source: &SourceReplayStruct,
+) -> Result<(DuplexStream, ReplayHandler), ManagerError> {
+    if source.speed.is_nan() || source.speed <= 0.0 {
+        return Err(ManagerError::DeviceSourceError(format!(
+            "Invalid replay speed {}, it should be greater than zero",
+            source.speed
+        )));
+    }
+
+    let content = map_file(Path::new(&source.path)).map_err(|err| {
+        ManagerError::DeviceSourceError(format!(
+            "Failed to read replay file {}: {err}",
+            source.path
+        ))
+    })?;
+
+    let (device_type, frames, frame_source) = load_frames(content)?;
+
+    info!(
+        "Replay: loaded {} frames of {device_type:?} from {}",
+        frames.len(),
+        source.path
+    );
+
+    let (device_port, replay_port) = tokio::io::duplex(REPLAY_BUFFER_SIZE);
+    let (sender, receiver) = mpsc::channel(10);
+
+    let actor = ReplayActor {
+        receiver,
+        path: source.path.clone(),
+        device_type,
+        frames,
+        source: frame_source,
+        index: 0,
+        last_sent: None,
+        speed: source.speed,
+        looping: source.looping,
+        paused: false,
+        paused_position: 0,
+        anchor_instant: Instant::now(),
+        anchor_position: 0,
+    };
+    tokio::spawn(actor.run(replay_port));
+
+    Ok((device_port, ReplayHandler { sender }))
+}
+
+fn invalid_file(err: mcap::McapError) -> ManagerError {
+    ManagerError::DeviceSourceError(format!("Invalid MCAP file: {err}"))
+}
+
+fn topic_type(topic: &str) -> Option<DeviceSelection> {
+    if topic.ends_with("/Ping1D") {
+        Some(DeviceSelection::Ping1D)
+    } else if topic.ends_with("/Ping360") {
+        Some(DeviceSelection::Ping360)
+    } else {
+        None
+    }
+}
+
+fn decode_message(device_type: &DeviceSelection, message: &mcap::Message) -> Option<ReplayMessage> {
+    let replay_message = match device_type {
+        DeviceSelection::Ping1D => {
+            serde_json::from_slice::<ProfileStruct>(&message.data).map(ReplayMessage::Ping1D)
+        }
+        _ => serde_json::from_slice::<AutoDeviceDataStruct>(&message.data)
+            .map(ReplayMessage::Ping360),
+    };
+    replay_message
+        .map_err(|err| {
+            warn!(
+                "Replay: skipping undecodable message on {}: {err}",
+                message.channel.topic
+            )
+        })
+        .ok()
+}
+
+// Decoded frames of a chunk, in the order they were written, undecodable ones are kept empty
+fn chunk_messages(
+    content: &[u8],
+    summary: &mcap::Summary,
+    chunk: usize,
+    channel_id: u16,
+    device_type: &DeviceSelection,
+) -> Result<Vec<Option<ReplayMessage>>, mcap::McapError> {
+    let mut messages = Vec::new();
+    for message in summary.stream_chunk(content, &summary.chunk_indexes[chunk])? {
+        let message = message?;
+        if message.channel.id == channel_id {
+            messages.push(decode_message(device_type, &message));
+        }
+    }
+    Ok(messages)
+}
+
+// Log times of the channel messages of a chunk, in the order they were written.
+// Taken from the message indexes when available, so the chunk isn't decompressed.
+fn chunk_log_times(
+    content: &[u8],
+    summary: &mcap::Summary,
+    chunk: usize,
+    channel_id: u16,
+) -> Result<Vec<u64>, mcap::McapError> {
+    let chunk_index = &summary.chunk_indexes[chunk];
+    if chunk_index.message_index_offsets.is_empty() {
+        let mut log_times = Vec::new();
+        for message in summary.stream_chunk(content, chunk_index)? {
+            let message = message?;
+            if message.channel.id == channel_id {
+                log_times.push(message.log_time);
+            }
+        }
+        return Ok(log_times);
+    }
+
+    if !chunk_index.message_index_offsets.contains_key(&channel_id) {
+        return Ok(Vec::new());
+    }
+    let mut entries = summary
+        .read_message_indexes(content, chunk_index)?
+        .into_iter()
+        .find(|(channel, _)| channel.id == channel_id)
+        .map(|(_, entries)| entries)
+        .unwrap_or_default();
+    entries.sort_by_key(|entry| entry.offset);
+    Ok(entries.into_iter().map(|entry| entry.log_time).collect())
+}
+
+// Index the device channels written by RecordingManager, only the first device found is replayed
+fn load_frames(
+    content: memmap2::Mmap,
+) -> Result<(DeviceSelection, Vec<ReplayFrame>, FrameSource), ManagerError> {
+    let summary = mcap::Summary::read(&content).map_err(invalid_file)?;
+    let (device_type, mut timed_frames, source) = match summary {
+        Some(summary) if !summary.chunk_indexes.is_empty() => {
+            let (channel_id, device_type) = summary
+                .channels
+                .values()
+                .filter_map(|channel| Some((channel.id, topic_type(&channel.topic)?)))
+                .min_by_key(|(channel_id, _)| *channel_id)
+                .ok_or_else(|| {
+                    ManagerError::DeviceSourceError(
+                        "Replay file has no Ping1D or Ping360 messages".to_string(),
+                    )
+                })?;
+            debug!(
+                "Replay: using topic {}",
+                summary.channels[&channel_id].topic
+            );
+
+            let mut timed_frames = Vec::new();
+            for chunk in 0..summary.chunk_indexes.len() {
+                let log_times =
+                    chunk_log_times(&content, &summary, chunk, channel_id).map_err(invalid_file)?;
+                timed_frames.extend(
+                    log_times
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, log_time)| (log_time, chunk, index)),
+                );
+            }
+
+            let source = FrameSource::Chunks {
+                content,
+                summary: Box::new(summary),
+                channel_id,
+                device_type: device_type.clone(),
+                cache: None,
+            };
+            (device_type, timed_frames, source)
+        }
+        _ => {
+            warn!("Replay: file has no chunk index, keeping every frame in memory");
+            let mut replay_topic: Option<String> = None;
+            let mut device_type = DeviceSelection::Auto;
+            let mut timed_frames = Vec::new();
+            let mut messages = Vec::new();
+
+            for message in mcap::MessageStream::new(&content).map_err(invalid_file)? {
+                let message = message.map_err(invalid_file)?;
+
+                let topic = &message.channel.topic;
+                let Some(message_type) = topic_type(topic) else {
+                    continue;
+                };
+
+                match &replay_topic {
+                    Some(replay_topic) if replay_topic != topic => continue,
+                    Some(_) => {}
+                    None => {
+                        debug!("Replay: using topic {topic}");
+                        replay_topic = Some(topic.clone());
+                        device_type = message_type;
+                    }
+                }
+
+                if let Some(replay_message) = decode_message(&device_type, &message) {
+                    timed_frames.push((message.log_time, 0, messages.len()));
+                    messages.push(replay_message);
+                }
+            }
+            (device_type, timed_frames, FrameSource::Decoded(messages))
+        }
+    };
+
+    if timed_frames.is_empty() {
+        return Err(ManagerError::DeviceSourceError(
+            "Replay file has no Ping1D or Ping360 messages".to_string(),
+        ));
+    }
+
+    timed_frames.sort_by_key(|(log_time, _, _)| *log_time);
+    let start_time = timed_frames[0].0;
+
+    let frames = timed_frames
+        .into_iter()
+        .map(|(log_time, chunk, index)| ReplayFrame {
+            position: log_time - start_time,
+            chunk,
+            index,
+        })
+        .collect();
+
+    Ok((device_type, frames, source))
+}
+
+pub(crate) fn serialize_message(message: &impl PingMessage) -> Vec<u8> {
+    let mut package = ProtocolMessage::new();
+    package.set_message(message);
+    package.serialized()
+}
+
+impl ReplayMessage {
+    fn serialized(&self) -> Vec<u8> {
+        match self {
+            ReplayMessage::Ping1D(profile) => serialize_message(
+                &bluerobotics_ping::ping1d::Messages::Profile(profile.clone()),
+            ),
+            ReplayMessage::Ping360(auto_device_data) => serialize_message(
+                &bluerobotics_ping::ping360::Messages::AutoDeviceData(auto_device_data.clone()),
+            ),
+        }
+    }
+}
+
+impl FrameSource {
+    fn message(&mut self, frame: &ReplayFrame) -> Option<ReplayMessage> {
+        match self {
+            FrameSource::Decoded(messages) => messages.get(frame.index).cloned(),
+            FrameSource::Chunks {
+                content,
+                summary,
+                channel_id,
+                device_type,
+                cache,
+            } => {
+                if cache.as_ref().map(|(chunk, _)| *chunk) != Some(frame.chunk) {
+                    let messages = match chunk_messages(
+                        content,
+                        summary,
+                        frame.chunk,
+                        *channel_id,
+                        device_type,
+                    ) {
+                        Ok(messages) => messages,
+                        Err(err) => {
+                            error!("Replay: failed to read chunk {}: {err}", frame.chunk);
+                            return None;
+                        }
+                    };
+                    *cache = Some((frame.chunk, messages));
+                }
+                cache.as_ref()?.1.get(frame.index).cloned().flatten()
+            }
+        }
+    }
+}
+
+impl ReplayActor {
+    async fn run(mut self, port: DuplexStream) {
+        let (mut reader, mut writer) = tokio::io::split(port);
+        let mut decoder = Decoder::new();
+        let mut buffer = [0u8; 1024];
+
+        loop {
+            let next_frame_deadline = self.next_frame_deadline();
+
+            tokio::select! {
+                read = reader.read(&mut buffer) => {
+                    let size = match read {
+                        Ok(0) => {
+                            debug!("Replay: virtual device closed, stopping replay of {}", self.path);
+                            break;
+                        }
+                        Ok(size) => size,
+                        Err(err) => {
+                            error!("Replay: failed to read from virtual device: {err}");
+                            break;
+                        }
+                    };
+
+                    for byte in &buffer[..size] {
+                        match decoder.parse_byte(*byte) {
+                            DecoderResult::Success(message) => {
+                                if let Err(err) = self.answer(&message, &mut writer).await {
+                                    error!("Replay: failed to answer request: {err}");
+                                }
+                            }
+                            DecoderResult::InProgress(_) => {}
+                            DecoderResult::Error(err) => {
+                                trace!("Replay: failed to decode request: {err:?}");
+                            }
+                        }
+                    }
+                }
+                Some(request) = self.receiver.recv() => {
+                    let result = self.handle_command(request.request);
+                    if let Err(err) = request.respond_to.send(result) {
+                        error!("Replay: failed to return response: {err:?}");
+                    }
+                }
+                _ = sleep_until(next_frame_deadline.unwrap_or_else(Instant::now)), if next_frame_deadline.is_some() => {
+                    if let Err(err) = self.send_next_frame(&mut writer).await {
+                        error!("Replay: failed to write frame: {err}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    fn next_frame_deadline(&self) -> Option<Instant> {
+        if self.paused {
+            return None;
+        }
+        let frame = self.frames.get(self.index)?;
+        let delta = frame.position.saturating_sub(self.anchor_position);
+        Some(self.anchor_instant + Duration::from_nanos((delta as f64 / self.speed as f64) as u64))
+    }
+
+    async fn send_next_frame(
+        &mut self,
+        writer: &mut WriteHalf<DuplexStream>,
+    ) -> std::io::Result<()> {
+        let Some(frame) = self.frames.get(self.index) else {
+            return Ok(());
+        };
+
+        if let Some(message) = self.source.message(frame) {
+            writer.write_all(&message.serialized()).await?;
+        }
+        self.last_sent = Some(self.index);
+        self.index += 1;
+
+        if self.index >= self.frames.len() && self.looping {
+            trace!("Replay: restarting {}", self.path);
+            self.seek_to(0);
+        }
+        Ok(())
+    }
+
+    // Answer the requests done by the virtual device as a real one would
+    async fn answer(
+        &mut self,
+        message: &ProtocolMessage,
+        writer: &mut WriteHalf<DuplexStream>,
+    ) -> std::io::Result<()> {
+        let answer = match bluerobotics_ping::Messages::try_from(message) {
+            Ok(bluerobotics_ping::Messages::Common(
+                bluerobotics_ping::common::Messages::GeneralRequest(request),
+            )) => self.answer_general_request(request.requested_id),
+            Ok(bluerobotics_ping::Messages::Ping360(
+                bluerobotics_ping::ping360::Messages::Transducer(_),
+            )) => self.last_device_data().map(|device_data| {
+                serialize_message(&bluerobotics_ping::ping360::Messages::DeviceData(
+                    device_data,
+                ))
+            }),
+            _ => Some(serialize_message(
+                &bluerobotics_ping::common::Messages::Ack(AckStruct {
+                    acked_id: message.message_id,
+                }),
+            )),
+        };
+
+        match answer {
+            Some(answer) => writer.write_all(&answer).await,
+            None => {
+                trace!(
+                    "Replay: no answer available for message id {}",
+                    message.message_id
+                );
+                Ok(())
+            }
+        }
+    }
+
+    fn answer_general_request(&mut self, requested_id: u16) -> Option<Vec<u8>> {
+        if requested_id == DeviceInformationStruct::id() {
+            let device_type = match self.device_type {
+                DeviceSelection::Ping1D => 1,
+                DeviceSelection::Ping360 => 2,
+                _ => 0,
+            };
+            // Report a firmware with auto-transmit support, so Ping360 replays use the broadcast path
+            return Some(serialize_message(
+                &bluerobotics_ping::common::Messages::DeviceInformation(DeviceInformationStruct {
+                    device_type,
+                    device_revision: 1,
+                    firmware_version_major: 3,
+                    firmware_version_minor: 3,
+                    firmware_version_patch: 0,
+                    reserved: 0,
+                }),
+            ));
+        }
+
+        if requested_id == ProtocolVersionStruct::id() {
+            return Some(serialize_message(
+                &bluerobotics_ping::common::Messages::ProtocolVersion(ProtocolVersionStruct {
+                    version_major: 1,
+                    version_minor: 0,
+                    version_patch: 0,
+                    reserved: 0,
+                }),
+            ));
+        }
+
+        if requested_id == DeviceDataStruct::id() {
+            return self.last_device_data().map(|device_data| {
+                serialize_message(&bluerobotics_ping::ping360::Messages::DeviceData(
+                    device_data,
+                ))
+            });
+        }
+
+        if requested_id == ProfileStruct::id() {
+            return self
+                .last_or_first_message()
+                .and_then(|message| match message {
+                    ReplayMessage::Ping1D(profile) => Some(serialize_message(
+                        &bluerobotics_ping::ping1d::Messages::Profile(profile),
+                    )),
+                    ReplayMessage::Ping360(_) => None,
+                });
+        }
+
+        None
+    }
+
+    fn last_or_first_message(&mut self) -> Option<ReplayMessage> {
+        let frame = self.frames.get(self.last_sent.unwrap_or(0))?;
+        self.source.message(frame)
+    }
+
+    fn last_device_data(&mut self) -> Option<DeviceDataStruct> {
+        match self.last_or_first_message()? {
+            ReplayMessage::Ping360(data) => Some(DeviceDataStruct {
+                mode: data.mode,
+                gain_setting: data.gain_setting,
+                angle: data.angle,
+                transmit_duration: data.transmit_duration,
+                sample_period: data.sample_period,
+                transmit_frequency: data.transmit_frequency,
+                number_of_samples: data.number_of_samples,
+                data_length: data.data_length,
+                data: data.data,
+            }),
+            ReplayMessage::Ping1D(_) => None,
+        }
+    }
+
+    fn duration(&self) -> u64 {
+        self.frames.last().map(|frame| frame.position).unwrap_or(0)
+    }
+
+    fn position(&self) -> u64 {
+        let position = if self.paused {
+            self.paused_position
+        } else {
+            let elapsed = self.anchor_instant.elapsed().as_nanos() as f64 * self.speed as f64;
+            self.anchor_position + elapsed as u64
+        };
+        position.min(self.duration())
+    }
+
+    fn seek_to(&mut self, position: u64) {
+        let position = position.min(self.duration());
+        self.index = self
+            .frames
+            .partition_point(|frame| frame.position < position);
+        self.anchor_instant = Instant::now();
+        self.anchor_position = position;
+        self.paused_position = position;
+    }
+
+    fn handle_command(&mut self, command: ReplayCommand) -> Result<ReplayStatus, ManagerError> {
+        trace!("Replay: received command {command:?} for {}", self.path);
+        match command {
+            ReplayCommand::Pause => {
+                if !self.paused {
+                    self.paused_position = self.position();
+                    self.paused = true;
+                }
+            }
+            ReplayCommand::Resume => {
+                if self.paused {
+                    self.anchor_instant = Instant::now();
+                    self.anchor_position = self.paused_position;
+                    self.paused = false;
+                }
+            }
+            ReplayCommand::Seek(position_ms) => {
+                self.seek_to(position_ms.saturating_mul(1_000_000));
+            }
+            ReplayCommand::SetSpeed(speed) => {
+                if speed.is_nan() || speed <= 0.0 {
+                    return Err(ManagerError::Other(format!(
+                        "Invalid replay speed {speed}, it should be greater than zero"
+                    )));
+                }
+                let position = self.position();
+                self.anchor_instant = Instant::now();
+                self.anchor_position = position;
+                self.speed = speed;
+            }
+            ReplayCommand::Status => {}
+        }
+        Ok(self.status())
+    }
+
+    fn status(&self) -> ReplayStatus {
+        ReplayStatus {
+            path: self.path.clone(),
+            paused: self.paused,
+            finished: self.index >= self.frames.len(),
+            speed: self.speed,
+            looping: self.looping,
+            position_ms: self.position() / 1_000_000,
+            duration_ms: self.duration() / 1_000_000,
+            frame_index: self.index,
+            frame_count: self.frames.len(),
+        }
+    }
+}
+
+impl ReplayHandler {
+    pub async fn send(&self, request: ReplayCommand) -> Result<ReplayStatus, ManagerError> {
+        let (result_sender, result_receiver) = oneshot::channel();
+
+        trace!("Handling Replay request: {request:?}: Forwarding request.");
+        let replay_request = ReplayActorRequest {
+            request,
+            respond_to: result_sender,
+        };
+
+        self.sender
+            .send(replay_request)
+            .await
+            .map_err(|err| ManagerError::TokioMpsc(err.to_string()))?;
+
+        result_receiver
+            .await
+            .map_err(|err| ManagerError::TokioMpsc(err.to_string()))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn profile(distance: u32) -> Vec<u8> {
+        serde_json::to_vec(&ProfileStruct {
+            distance,
+            confidence: 100,
+            transmit_duration: 0,
+            ping_number: 0,
+            scan_start: 0,
+            scan_length: 0,
+            gain_setting: 0,
+            profile_data_length: 0,
+            profile_data: vec![],
+        })
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_load_frames() {
+        let invalid_speed = SourceReplayStruct {
+            path: "missing.mcap".to_string(),
+            speed: 0.0,
+            looping: false,
+        };
+        let Err(ManagerError::DeviceSourceError(err)) = open(&invalid_speed).await else {
+            panic!("Expected an invalid speed error");
+        };
+        assert!(err.starts_with("Invalid replay speed"));
+
+        let path = std::env::temp_dir().join(format!("replay_{}.mcap", uuid::Uuid::new_v4()));
+        let mut writer = mcap::Writer::new(std::fs::File::create(&path).unwrap()).unwrap();
+        let channel_id = writer
+            .add_channel(0, "device_test/Ping1D", "json", &BTreeMap::new())
+            .unwrap();
+        // Written out of order, with an undecodable message in between
+        for (sequence, (log_time, data)) in [
+            (3_000_000, profile(3)),
+            (2_000_000, b"{}".to_vec()),
+            (1_000_000, profile(1)),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let header = mcap::records::MessageHeader {
+                channel_id,
+                sequence: sequence as u32,
+                log_time,
+                publish_time: log_time,
+            };
+            writer.write_to_known_channel(&header, &data).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let content = map_file(&path).unwrap();
+        let (device_type, frames, mut source) = load_frames(content).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(device_type, DeviceSelection::Ping1D));
+        assert!(matches!(source, FrameSource::Chunks { .. }));
+
+        let positions: Vec<u64> = frames.iter().map(|frame| frame.position).collect();
+        assert_eq!(positions, [0, 1_000_000, 2_000_000]);
+        let distances: Vec<Option<u32>> = frames
+            .iter()
+            .map(|frame| match source.message(frame) {
+                Some(ReplayMessage::Ping1D(profile)) => Some(profile.distance),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(distances, [Some(1), None, Some(3)]);
+    }
+}
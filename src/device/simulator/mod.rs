@@ -0,0 +1,383 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    str::FromStr,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::UdpSocket,
+    task::JoinHandle,
+    time::{sleep_until, Instant},
+};
+use tracing::{debug, error, info, trace, warn};
+
+use crate::device::manager::{
+    CreateStruct, DeviceSelection, ManagerError, SourceSelection, SourceUdpStruct,
+};
+
+mod responder;
+pub mod seabed;
+
+pub use responder::SimulatedDevice;
+pub use seabed::SyntheticSeabed;
+
+/// Port used by Ping360 network discovery broadcasts.
+pub const DISCOVERY_PORT: u16 = 30303;
+/// Default UDP port for simulated Ping1D devices.
+pub const PING1D_DEFAULT_PORT: u16 = 9090;
+/// Default UDP port for simulated Ping360 devices, the same one used by real Ping360 over ethernet.
+pub const PING360_DEFAULT_PORT: u16 = 12345;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulatorTransport {
+    /// Local UDP port, 0 picks any free port
+    Udp(u16),
+    /// Pseudo terminal, behaves like a serial device
+    Pty,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulatorConfig {
+    pub device_type: DeviceSelection,
+    pub transport: SimulatorTransport,
+    pub seabed: SyntheticSeabed,
+    /// Answer network discovery broadcasts on this port, only used by Ping360 over UDP
+    pub discovery_port: Option<u16>,
+}
+
+impl SimulatorConfig {
+    pub fn new(device_type: DeviceSelection) -> Self {
+        let (transport, discovery_port) = match device_type {
+            DeviceSelection::Ping360 => (
+                SimulatorTransport::Udp(PING360_DEFAULT_PORT),
+                Some(DISCOVERY_PORT),
+            ),
+            _ => (SimulatorTransport::Udp(PING1D_DEFAULT_PORT), None),
+        };
+
+        Self {
+            device_type,
+            transport,
+            seabed: SyntheticSeabed::default(),
+            discovery_port,
+        }
+    }
+}
+
+// Parse the command line format: `DEVICE[:udp[:PORT]]` or `DEVICE:pty`
+impl FromStr for SimulatorConfig {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.split(':');
+
+        let device_type = match parts.next().map(str::to_lowercase).as_deref() {
+            Some("ping1d") => DeviceSelection::Ping1D,
+            Some("ping360") => DeviceSelection::Ping360,
+            _ => {
+                return Err(format!(
+                    "Invalid simulator device in \"{value}\", expected \"ping1d\" or \"ping360\""
+                ))
+            }
+        };
+
+        let mut config = SimulatorConfig::new(device_type);
+
+        match parts.next().map(str::to_lowercase).as_deref() {
+            None | Some("udp") => {
+                if let Some(port) = parts.next() {
+                    let port = port
+                        .parse::<u16>()
+                        .map_err(|err| format!("Invalid simulator port in \"{value}\": {err}"))?;
+                    config.transport = SimulatorTransport::Udp(port);
+                }
+            }
+            Some("pty") => {
+                config.transport = SimulatorTransport::Pty;
+                config.discovery_port = None;
+            }
+            Some(transport) => {
+                return Err(format!(
+                    "Invalid simulator transport \"{transport}\" in \"{value}\", expected \"udp\" or \"pty\""
+                ))
+            }
+        }
+
+        if parts.next().is_some() {
+            return Err(format!(
+                "Too many fields in simulator definition \"{value}\""
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+/// A running simulator, the simulated device stops when the handle is dropped.
+#[derive(Debug)]
+pub struct SimulatorHandle {
+    pub source: SourceSelection,
+    pub device_type: DeviceSelection,
+    /// Port answering network discovery broadcasts, if any
+    pub discovery_port: Option<u16>,
+    tasks: Vec<JoinHandle<()>>,
+    // The pty slave should stay open while the simulator runs, otherwise the master side hangs up
+    #[cfg(unix)]
+    _pty: Option<tokio_serial::SerialStream>,
+}
+
+impl SimulatorHandle {
+    pub fn create_struct(&self) -> CreateStruct {
+        CreateStruct {
+            source: self.source.clone(),
+            device_selection: self.device_type.clone(),
+        }
+    }
+}
+
+impl Drop for SimulatorHandle {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+pub async fn start(config: SimulatorConfig) -> Result<SimulatorHandle, ManagerError> {
+    if !matches!(
+        config.device_type,
+        DeviceSelection::Ping1D | DeviceSelection::Ping360
+    ) {
+        return Err(ManagerError::Other(format!(
+            "Simulator: unsupported device type {:?}",
+            config.device_type
+        )));
+    }
+
+    let device = SimulatedDevice::new(config.device_type.clone(), config.seabed.clone());
+    let mut tasks = Vec::new();
+    let mut discovery_port = None;
+
+    let handle = match config.transport {
+        SimulatorTransport::Udp(port) => {
+            let socket = UdpSocket::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+                .await
+                .map_err(|err| {
+                    ManagerError::DeviceSourceError(format!(
+                        "Simulator: failed to bind UDP port {port}: {err}"
+                    ))
+                })?;
+            let port = socket
+                .local_addr()
+                .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?
+                .port();
+
+            if let Some(requested_port) = config.discovery_port {
+                match UdpSocket::bind(SocketAddr::from((Ipv4Addr::UNSPECIFIED, requested_port)))
+                    .await
+                {
+                    Ok(discovery_socket) => {
+                        discovery_port = discovery_socket
+                            .local_addr()
+                            .ok()
+                            .map(|address| address.port());
+                        tasks.push(tokio::spawn(run_discovery(
+                            discovery_socket,
+                            Ipv4Addr::LOCALHOST,
+                        )));
+                    }
+                    Err(err) => {
+                        warn!("Simulator: discovery disabled, failed to bind port {requested_port}: {err}")
+                    }
+                }
+            }
+
+            tasks.push(tokio::spawn(run_udp(socket, device)));
+
+            SimulatorHandle {
+                source: SourceSelection::UdpStream(SourceUdpStruct {
+                    ip: Ipv4Addr::LOCALHOST,
+                    port,
+                }),
+                device_type: config.device_type.clone(),
+                discovery_port,
+                tasks,
+                #[cfg(unix)]
+                _pty: None,
+            }
+        }
+        #[cfg(unix)]
+        SimulatorTransport::Pty => {
+            use tokio_serial::SerialPort;
+
+            let (master, mut slave) = tokio_serial::SerialStream::pair().map_err(|err| {
+                ManagerError::DeviceSourceError(format!("Simulator: failed to open pty: {err}"))
+            })?;
+            slave
+                .set_exclusive(false)
+                .map_err(|err| ManagerError::DeviceSourceError(err.to_string()))?;
+            let path = slave.name().ok_or_else(|| {
+                ManagerError::DeviceSourceError("Simulator: pty has no name".to_string())
+            })?;
+
+            tasks.push(tokio::spawn(run_stream(master, device)));
+
+            SimulatorHandle {
+                source: SourceSelection::SerialStream(crate::device::manager::SourceSerialStruct {
+                    path,
+                    baudrate: 115200,
+                }),
+                device_type: config.device_type.clone(),
+                discovery_port,
+                tasks,
+                _pty: Some(slave),
+            }
+        }
+        #[cfg(not(unix))]
+        SimulatorTransport::Pty => {
+            return Err(ManagerError::DeviceSourceError(
+                "Simulator: pty transport is only available on unix".to_string(),
+            ))
+        }
+    };
+
+    info!(
+        "Simulator: {:?} available on {:?}",
+        handle.device_type, handle.source
+    );
+
+    Ok(handle)
+}
+
+fn next_deadline(device: &SimulatedDevice, last: Instant) -> Option<Instant> {
+    device.stream_interval().map(|interval| last + interval)
+}
+
+async fn run_udp(socket: UdpSocket, mut device: SimulatedDevice) {
+    let mut buffer = [0u8; 2048];
+    let mut peer: Option<SocketAddr> = None;
+    let mut last_stream = Instant::now();
+
+    loop {
+        let deadline = next_deadline(&device, last_stream).filter(|_| peer.is_some());
+
+        tokio::select! {
+            received = socket.recv_from(&mut buffer) => {
+                let (size, address) = match received {
+                    Ok(received) => received,
+                    Err(err) => {
+                        error!("Simulator: failed to receive UDP data: {err}");
+                        continue;
+                    }
+                };
+
+                if peer != Some(address) {
+                    debug!("Simulator: new client {address}");
+                    peer = Some(address);
+                }
+
+                let was_streaming = device.stream_interval().is_some();
+                for answer in device.handle_bytes(&buffer[..size]) {
+                    if let Err(err) = socket.send_to(&answer, address).await {
+                        error!("Simulator: failed to send answer to {address}: {err}");
+                    }
+                }
+                if !was_streaming {
+                    last_stream = Instant::now();
+                }
+            }
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                last_stream = Instant::now();
+                let (Some(address), Some(message)) = (peer, device.next_stream_message()) else {
+                    continue;
+                };
+                if let Err(err) = socket.send_to(&message, address).await {
+                    trace!("Simulator: failed to stream to {address}: {err}");
+                }
+            }
+        }
+    }
+}
+
+async fn run_stream<T>(port: T, mut device: SimulatedDevice)
+where
+    T: AsyncRead + AsyncWrite,
+{
+    let (mut reader, mut writer) = tokio::io::split(port);
+    let mut buffer = [0u8; 1024];
+    let mut last_stream = Instant::now();
+
+    loop {
+        let deadline = next_deadline(&device, last_stream);
+
+        tokio::select! {
+            read = reader.read(&mut buffer) => {
+                let size = match read {
+                    Ok(0) => {
+                        debug!("Simulator: stream closed");
+                        break;
+                    }
+                    Ok(size) => size,
+                    Err(err) => {
+                        error!("Simulator: failed to read from stream: {err}");
+                        break;
+                    }
+                };
+
+                let was_streaming = device.stream_interval().is_some();
+                for answer in device.handle_bytes(&buffer[..size]) {
+                    if let Err(err) = writer.write_all(&answer).await {
+                        error!("Simulator: failed to write answer: {err}");
+                    }
+                }
+                if !was_streaming {
+                    last_stream = Instant::now();
+                }
+            }
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                last_stream = Instant::now();
+                if let Some(message) = device.next_stream_message() {
+                    if let Err(err) = writer.write_all(&message).await {
+                        error!("Simulator: failed to stream message: {err}");
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// ASCII answer sent by Ping360 to a `Discovery` broadcast.
+pub fn discovery_response(ip: Ipv4Addr) -> String {
+    let [a, b, c, d] = ip.octets();
+    format!(
+        "SONAR PING360\r\n\
+        Blue Robotics\r\n\
+        MAC Address:- 54-10-EC-00-00-{d:02X}\r\n\
+        IP Address:- {a:03}.{b:03}.{c:03}.{d:03}\r\n"
+    )
+}
+
+async fn run_discovery(socket: UdpSocket, ip: Ipv4Addr) {
+    let mut buffer = [0u8; 64];
+    let response = discovery_response(ip);
+
+    loop {
+        match socket.recv_from(&mut buffer).await {
+            Ok((size, address)) => {
+                if &buffer[..size] != b"Discovery" {
+                    trace!("Simulator: ignoring discovery message from {address}");
+                    continue;
+                }
+                debug!("Simulator: answering discovery request from {address}");
+                if let Err(err) = socket.send_to(response.as_bytes(), address).await {
+                    error!("Simulator: failed to answer discovery request: {err}");
+                }
+            }
+            Err(err) => {
+                error!("Simulator: discovery socket error: {err}");
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use bluerobotics_ping::{
+    common::{AckStruct, DeviceInformationStruct, ProtocolVersionStruct},
+    decoder::{Decoder, DecoderResult},
+    message::{MessageInfo, PingMessage, ProtocolMessage},
+    ping1d::{
+        DistanceSimpleStruct, DistanceStruct, GainSettingStruct, GeneralInfoStruct, ModeAutoStruct,
+        PingEnableStruct, PingIntervalStruct, ProfileStruct, RangeStruct, SpeedOfSoundStruct,
+        TransmitDurationStruct,
+    },
+    ping360::{AutoDeviceDataStruct, DeviceDataStruct},
+};
+use tokio::time::{Duration, Instant};
+use tracing::trace;
+
+use super::seabed::{NoiseGenerator, SyntheticSeabed, SPEED_OF_SOUND};
+use crate::device::manager::DeviceSelection;
+
+const PING1D_PROFILE_SAMPLES: usize = 200;
+// Ping360 sample period unit, 25 nanoseconds
+const PING360_SAMPLE_PERIOD_TICK: f32 = 25e-9;
+// Time spent by the Ping360 motor moving to the next angle
+const PING360_STEP_TIME: Duration = Duration::from_millis(5);
+
+#[derive(Debug, Clone)]
+struct Ping1DState {
+    continuous_id: Option<u16>,
+    speed_of_sound: u32,
+    scan_start: u32,
+    scan_length: u32,
+    mode_auto: u8,
+    gain_setting: u32,
+    ping_interval: u16,
+    ping_enabled: u8,
+    transmit_duration: u16,
+}
+
+#[derive(Debug, Clone)]
+struct Ping360State {
+    auto_transmit: bool,
+    mode: u8,
+    gain_setting: u8,
+    angle: u16,
+    transmit_duration: u16,
+    sample_period: u16,
+    transmit_frequency: u16,
+    number_of_samples: u16,
+    start_angle: u16,
+    stop_angle: u16,
+    num_steps: u8,
+    delay: u8,
+    // Sweep direction for sector scans
+    clockwise: bool,
+}
+
+// Protocol state machine of a simulated device, it is transport agnostic:
+// requests go in as raw bytes and answers come out as serialized packages.
+pub struct SimulatedDevice {
+    device_type: DeviceSelection,
+    seabed: SyntheticSeabed,
+    decoder: Decoder,
+    noise: NoiseGenerator,
+    started: Instant,
+    ping_number: u32,
+    ping1d: Ping1DState,
+    ping360: Ping360State,
+}
+
+fn serialize_message(message: &impl PingMessage) -> Vec<u8> {
+    let mut package = ProtocolMessage::new();
+    package.set_message(message);
+    package.serialized()
+}
+
+impl SimulatedDevice {
+    pub fn new(device_type: DeviceSelection, seabed: SyntheticSeabed) -> Self {
+        Self {
+            device_type,
+            seabed,
+            decoder: Decoder::new(),
+            noise: NoiseGenerator::new(0x5eed),
+            started: Instant::now(),
+            ping_number: 0,
+            ping1d: Ping1DState {
+                continuous_id: None,
+                speed_of_sound: (SPEED_OF_SOUND * 1000.0) as u32,
+                scan_start: 0,
+                scan_length: 30_000,
+                mode_auto: 1,
+                gain_setting: 0,
+                ping_interval: 100,
+                ping_enabled: 1,
+                transmit_duration: 100,
+            },
+            ping360: Ping360State {
+                auto_transmit: false,
+                mode: 1,
+                gain_setting: 0,
+                angle: 0,
+                transmit_duration: 32,
+                sample_period: 80,
+                transmit_frequency: 740,
+                number_of_samples: 1200,
+                start_angle: 0,
+                stop_angle: 399,
+                num_steps: 1,
+                delay: 0,
+                clockwise: true,
+            },
+        }
+    }
+
+    // Feed incoming bytes, returning every answer that should be written back
+    pub fn handle_bytes(&mut self, bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut answers = Vec::new();
+        for byte in bytes {
+            match self.decoder.parse_byte(*byte) {
+                DecoderResult::Success(message) => {
+                    if let Some(answer) = self.handle_message(&message) {
+                        answers.push(answer);
+                    }
+                }
+                DecoderResult::InProgress(_) => {}
+                DecoderResult::Error(err) => {
+                    trace!("Simulator: failed to decode request: {err:?}");
+                }
+            }
+        }
+        answers
+    }
+
+    // Interval between unsolicited messages, `None` while the device is not streaming
+    pub fn stream_interval(&self) -> Option<Duration> {
+        match self.device_type {
+            DeviceSelection::Ping1D if self.ping1d.continuous_id.is_some() => Some(
+                Duration::from_millis(self.ping1d.ping_interval.max(10) as u64),
+            ),
+            DeviceSelection::Ping360 if self.ping360.auto_transmit => {
+                let round_trip = self.ping360.number_of_samples as f32
+                    * self.ping360.sample_period as f32
+                    * PING360_SAMPLE_PERIOD_TICK;
+                Some(
+                    PING360_STEP_TIME
+                        + Duration::from_secs_f32(round_trip)
+                        + Duration::from_millis(self.ping360.delay as u64),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    pub fn next_stream_message(&mut self) -> Option<Vec<u8>> {
+        match self.device_type {
+            DeviceSelection::Ping1D => {
+                let id = self.ping1d.continuous_id?;
+                if id == DistanceStruct::id() {
+                    Some(serialize_message(
+                        &bluerobotics_ping::ping1d::Messages::Distance(self.distance()),
+                    ))
+                } else {
+                    Some(serialize_message(
+                        &bluerobotics_ping::ping1d::Messages::Profile(self.profile()),
+                    ))
+                }
+            }
+            DeviceSelection::Ping360 if self.ping360.auto_transmit => {
+                self.step_angle();
+                let device_data = self.device_data();
+                let state = &self.ping360;
+                Some(serialize_message(
+                    &bluerobotics_ping::ping360::Messages::AutoDeviceData(AutoDeviceDataStruct {
+                        mode: device_data.mode,
+                        gain_setting: device_data.gain_setting,
+                        angle: device_data.angle,
+                        transmit_duration: device_data.transmit_duration,
+                        sample_period: device_data.sample_period,
+                        transmit_frequency: device_data.transmit_frequency,
+                        start_angle: state.start_angle,
+                        stop_angle: state.stop_angle,
+                        num_steps: state.num_steps,
+                        delay: state.delay,
+                        number_of_samples: device_data.number_of_samples,
+                        data_length: device_data.data_length,
+                        data: device_data.data,
+                    }),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn handle_message(&mut self, message: &ProtocolMessage) -> Option<Vec<u8>> {
+        let ack = || {
+            Some(serialize_message(
+                &bluerobotics_ping::common::Messages::Ack(AckStruct {
+                    acked_id: message.message_id,
+                }),
+            ))
+        };
+
+        match bluerobotics_ping::Messages::try_from(message) {
+            Ok(bluerobotics_ping::Messages::Common(
+                bluerobotics_ping::common::Messages::GeneralRequest(request),
+            )) => self.answer_general_request(request.requested_id),
+            Ok(bluerobotics_ping::Messages::Ping1D(request)) => {
+                use bluerobotics_ping::ping1d::Messages;
+                match request {
+                    Messages::ContinuousStart(start) => {
+                        self.ping1d.continuous_id = Some(start.id);
+                        ack()
+                    }
+                    Messages::ContinuousStop(_) => {
+                        self.ping1d.continuous_id = None;
+                        ack()
+                    }
+                    Messages::SetSpeedOfSound(request) => {
+                        self.ping1d.speed_of_sound = request.speed_of_sound;
+                        ack()
+                    }
+                    Messages::SetRange(request) => {
+                        self.ping1d.scan_start = request.scan_start;
+                        self.ping1d.scan_length = request.scan_length.max(1);
+                        self.ping1d.mode_auto = 0;
+                        ack()
+                    }
+                    Messages::SetModeAuto(request) => {
+                        self.ping1d.mode_auto = request.mode_auto;
+                        ack()
+                    }
+                    Messages::SetGainSetting(request) => {
+                        self.ping1d.gain_setting = request.gain_setting as u32;
+                        ack()
+                    }
+                    Messages::SetPingInterval(request) => {
+                        self.ping1d.ping_interval = request.ping_interval;
+                        ack()
+                    }
+                    Messages::SetPingEnable(request) => {
+                        self.ping1d.ping_enabled = request.ping_enabled;
+                        ack()
+                    }
+                    _ => ack(),
+                }
+            }
+            Ok(bluerobotics_ping::Messages::Ping360(request)) => {
+                use bluerobotics_ping::ping360::Messages;
+                match request {
+                    Messages::Transducer(request) => {
+                        let state = &mut self.ping360;
+                        state.auto_transmit = false;
+                        state.mode = request.mode;
+                        state.gain_setting = request.gain_setting;
+                        state.angle = request.angle % 400;
+                        state.transmit_duration = request.transmit_duration;
+                        state.sample_period = request.sample_period;
+                        state.transmit_frequency = request.transmit_frequency;
+                        state.number_of_samples = request.number_of_samples;
+                        Some(serialize_message(&Messages::DeviceData(self.device_data())))
+                    }
+                    Messages::AutoTransmit(request) => {
+                        let state = &mut self.ping360;
+                        state.mode = request.mode;
+                        state.gain_setting = request.gain_setting;
+                        state.transmit_duration = request.transmit_duration;
+                        state.sample_period = request.sample_period;
+                        state.transmit_frequency = request.transmit_frequency;
+                        state.number_of_samples = request.number_of_samples;
+                        state.start_angle = request.start_angle % 400;
+                        state.stop_angle = request.stop_angle % 400;
+                        state.num_steps = request.num_steps.max(1);
+                        state.delay = request.delay;
+                        state.angle = state.start_angle;
+                        state.clockwise = true;
+                        state.auto_transmit = true;
+                        ack()
+                    }
+                    Messages::MotorOff(_) => {
+                        self.ping360.auto_transmit = false;
+                        ack()
+                    }
+                    _ => ack(),
+                }
+            }
+            _ => ack(),
+        }
+    }
+
+    fn answer_general_request(&mut self, requested_id: u16) -> Option<Vec<u8>> {
+        use bluerobotics_ping::ping1d::Messages as Ping1DMessages;
+
+        if requested_id == DeviceInformationStruct::id() {
+            let device_type = match self.device_type {
+                DeviceSelection::Ping1D => 1,
+                DeviceSelection::Ping360 => 2,
+                _ => 0,
+            };
+            // Report a firmware with auto-transmit support, as the current Ping360 releases do
+            return Some(serialize_message(
+                &bluerobotics_ping::common::Messages::DeviceInformation(DeviceInformationStruct {
+                    device_type,
+                    device_revision: 1,
+                    firmware_version_major: 3,
+                    firmware_version_minor: 3,
+                    firmware_version_patch: 0,
+                    reserved: 0,
+                }),
+            ));
+        }
+
+        if requested_id == ProtocolVersionStruct::id() {
+            return Some(serialize_message(
+                &bluerobotics_ping::common::Messages::ProtocolVersion(ProtocolVersionStruct {
+                    version_major: 1,
+                    version_minor: 0,
+                    version_patch: 0,
+                    reserved: 0,
+                }),
+            ));
+        }
+
+        match self.device_type {
+            DeviceSelection::Ping1D => {
+                let state = self.ping1d.clone();
+                let message = if requested_id == ProfileStruct::id() {
+                    Ping1DMessages::Profile(self.profile())
+                } else if requested_id == DistanceStruct::id() {
+                    Ping1DMessages::Distance(self.distance())
+                } else if requested_id == DistanceSimpleStruct::id() {
+                    let distance = self.distance();
+                    Ping1DMessages::DistanceSimple(DistanceSimpleStruct {
+                        distance: distance.distance,
+                        confidence: distance.confidence as u8,
+                    })
+                } else if requested_id == GeneralInfoStruct::id() {
+                    Ping1DMessages::GeneralInfo(GeneralInfoStruct {
+                        firmware_version_major: 3,
+                        firmware_version_minor: 3,
+                        voltage_5: 5000,
+                        ping_interval: state.ping_interval,
+                        gain_setting: state.gain_setting as u8,
+                        mode_auto: state.mode_auto,
+                    })
+                } else if requested_id == SpeedOfSoundStruct::id() {
+                    Ping1DMessages::SpeedOfSound(SpeedOfSoundStruct {
+                        speed_of_sound: state.speed_of_sound,
+                    })
+                } else if requested_id == RangeStruct::id() {
+                    Ping1DMessages::Range(RangeStruct {
+                        scan_start: state.scan_start,
+                        scan_length: state.scan_length,
+                    })
+                } else if requested_id == ModeAutoStruct::id() {
+                    Ping1DMessages::ModeAuto(ModeAutoStruct {
+                        mode_auto: state.mode_auto,
+                    })
+                } else if requested_id == GainSettingStruct::id() {
+                    Ping1DMessages::GainSetting(GainSettingStruct {
+                        gain_setting: state.gain_setting,
+                    })
+                } else if requested_id == PingIntervalStruct::id() {
+                    Ping1DMessages::PingInterval(PingIntervalStruct {
+                        ping_interval: state.ping_interval,
+                    })
+                } else if requested_id == PingEnableStruct::id() {
+                    Ping1DMessages::PingEnable(PingEnableStruct {
+                        ping_enabled: state.ping_enabled,
+                    })
+                } else if requested_id == TransmitDurationStruct::id() {
+                    Ping1DMessages::TransmitDuration(TransmitDurationStruct {
+                        transmit_duration: state.transmit_duration,
+                    })
+                } else {
+                    trace!("Simulator: no answer for Ping1D request id {requested_id}");
+                    return None;
+                };
+                Some(serialize_message(&message))
+            }
+            DeviceSelection::Ping360 if requested_id == DeviceDataStruct::id() => {
+                Some(serialize_message(
+                    &bluerobotics_ping::ping360::Messages::DeviceData(self.device_data()),
+                ))
+            }
+            _ => {
+                trace!("Simulator: no answer for request id {requested_id}");
+                None
+            }
+        }
+    }
+
+    fn elapsed(&self) -> f32 {
+        self.started.elapsed().as_secs_f32()
+    }
+
+    // Keep the seabed around the middle of the scan when the device is in auto mode
+    fn update_auto_range(&mut self, distance: f32) {
+        if self.ping1d.mode_auto == 0 {
+            return;
+        }
+        let scan_length = ((distance * 1.5).ceil() as u32).max(2) * 1000;
+        self.ping1d.scan_start = 0;
+        self.ping1d.scan_length = scan_length;
+    }
+
+    fn distance(&mut self) -> DistanceStruct {
+        let profile = self.profile();
+        DistanceStruct {
+            distance: profile.distance,
+            confidence: profile.confidence,
+            transmit_duration: profile.transmit_duration,
+            ping_number: profile.ping_number,
+            scan_start: profile.scan_start,
+            scan_length: profile.scan_length,
+            gain_setting: profile.gain_setting,
+        }
+    }
+
+    fn profile(&mut self) -> ProfileStruct {
+        let time = self.elapsed();
+        // The seabed distance scales with the configured speed of sound, as a real device would
+        let distance = self.seabed.distance(time) * self.ping1d.speed_of_sound as f32
+            / (SPEED_OF_SOUND * 1000.0);
+        self.update_auto_range(distance);
+        self.ping_number = self.ping_number.wrapping_add(1);
+
+        let state = &self.ping1d;
+        let start = state.scan_start as f32 / 1000.0;
+        let length = state.scan_length as f32 / 1000.0;
+        let step = length / PING1D_PROFILE_SAMPLES as f32;
+        let profile_data = if state.ping_enabled == 0 {
+            vec![0; PING1D_PROFILE_SAMPLES]
+        } else {
+            self.seabed.samples(
+                distance,
+                start,
+                step,
+                PING1D_PROFILE_SAMPLES,
+                &mut self.noise,
+            )
+        };
+
+        ProfileStruct {
+            distance: (distance * 1000.0) as u32,
+            confidence: self.seabed.confidence(distance, start + length),
+            transmit_duration: state.transmit_duration,
+            ping_number: self.ping_number,
+            scan_start: state.scan_start,
+            scan_length: state.scan_length,
+            gain_setting: state.gain_setting,
+            profile_data_length: profile_data.len() as u16,
+            profile_data,
+        }
+    }
+
+    fn device_data(&mut self) -> DeviceDataStruct {
+        let time = self.elapsed();
+        let state = &self.ping360;
+        let distance = self.seabed.wall_distance(time, state.angle);
+        let step = state.sample_period as f32 * PING360_SAMPLE_PERIOD_TICK * SPEED_OF_SOUND / 2.0;
+        let data = self.seabed.samples(
+            distance,
+            0.0,
+            step,
+            state.number_of_samples as usize,
+            &mut self.noise,
+        );
+
+        DeviceDataStruct {
+            mode: state.mode,
+            gain_setting: state.gain_setting,
+            angle: state.angle,
+            transmit_duration: state.transmit_duration,
+            sample_period: state.sample_period,
+            transmit_frequency: state.transmit_frequency,
+            number_of_samples: state.number_of_samples,
+            data_length: data.len() as u16,
+            data,
+        }
+    }
+
+    // Move the head like the firmware does, full turns wrap around and sectors sweep back and forth
+    fn step_angle(&mut self) {
+        let state = &mut self.ping360;
+        let steps = state.num_steps as u16;
+        let full_turn = (state.start_angle == 0 && state.stop_angle == 399)
+            || state.start_angle == state.stop_angle;
+
+        if full_turn {
+            state.angle = (state.angle + steps) % 400;
+            return;
+        }
+
+        let sector = (state.stop_angle + 400 - state.start_angle) % 400;
+        let position = (state.angle + 400 - state.start_angle) % 400;
+        let position = if state.clockwise {
+            if position + steps >= sector {
+                state.clockwise = false;
+                sector
+            } else {
+                position + steps
+            }
+        } else if position <= steps {
+            state.clockwise = true;
+            0
+        } else {
+            position - steps
+        };
+        state.angle = (state.start_angle + position) % 400;
+    }
+}
@@ -0,0 +1,106 @@
+use std::f32::consts::PI;
+
+// Speed of sound used to convert sample periods into distances, in meters per second
+pub const SPEED_OF_SOUND: f32 = 1500.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntheticSeabed {
+    /// Mean distance to the seabed, in meters
+    pub depth: f32,
+    /// Amplitude of the slow seabed undulation, in meters
+    pub amplitude: f32,
+    /// Period of the seabed undulation, in seconds
+    pub period: f32,
+    /// Amount of random noise added to every sample, from 0 to 1
+    pub noise: f32,
+}
+
+impl Default for SyntheticSeabed {
+    fn default() -> Self {
+        Self {
+            depth: 10.0,
+            amplitude: 1.5,
+            period: 20.0,
+            noise: 0.05,
+        }
+    }
+}
+
+impl SyntheticSeabed {
+    // Distance to the seabed below the vehicle at the given time, in meters
+    pub fn distance(&self, time: f32) -> f32 {
+        let undulation = if self.period > 0.0 {
+            self.amplitude * (2.0 * PI * time / self.period).sin()
+        } else {
+            0.0
+        };
+        (self.depth + undulation).max(0.1)
+    }
+
+    // Distance to the closest echo seen by a scanning sonar, angle in gradians
+    pub fn wall_distance(&self, time: f32, angle: u16) -> f32 {
+        let angle = angle as f32 * 2.0 * PI / 400.0;
+        self.distance(time) * (1.0 + 0.25 * (2.0 * angle).sin() + 0.1 * (5.0 * angle).cos())
+    }
+
+    // Echo confidence from 0 to 100, lower when the echo is weak or too noisy
+    pub fn confidence(&self, distance: f32, range: f32) -> u16 {
+        if distance > range {
+            return 0;
+        }
+        let attenuation = 1.0 - (distance / range.max(0.1)) * 0.3;
+        ((100.0 * attenuation * (1.0 - self.noise.clamp(0.0, 1.0))).round() as u16).min(100)
+    }
+
+    // Generate the echo intensity for `count` samples starting at `start` meters, `step` meters apart
+    pub fn samples(
+        &self,
+        distance: f32,
+        start: f32,
+        step: f32,
+        count: usize,
+        noise: &mut NoiseGenerator,
+    ) -> Vec<u8> {
+        let echo_width = 0.1 + 0.02 * distance;
+        let noise_level = self.noise.clamp(0.0, 1.0);
+
+        (0..count)
+            .map(|index| {
+                let range = start + step * index as f32;
+
+                // Transducer ring down, strong at the face and gone after a few centimeters
+                let ring_down = (-range / 0.15).exp();
+                let echo = (-((range - distance) / echo_width).powi(2)).exp();
+                // Sediment keeps returning some energy after the main echo
+                let tail = if range > distance {
+                    0.35 * (-(range - distance) / (2.0 * echo_width + 0.5)).exp()
+                } else {
+                    0.0
+                };
+                let random = noise_level * noise.next_f32();
+
+                let intensity = (ring_down + echo + tail) * (1.0 - noise_level) + random;
+                (intensity.clamp(0.0, 1.0) * 255.0) as u8
+            })
+            .collect()
+    }
+}
+
+// Small xorshift generator, good enough to make synthetic data look alive
+#[derive(Debug, Clone)]
+pub struct NoiseGenerator {
+    state: u32,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: u32) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32
+    }
+}
@@ -0,0 +1,333 @@
+use std::{collections::HashMap, time::Duration};
+
+use bluerobotics_ping::{
+    ping1d::ProfileStruct,
+    ping360::{AutoDeviceDataStruct, DeviceDataStruct},
+};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::broadcast::error::RecvError, task::JoinHandle};
+use tracing::{debug, error, info, trace, warn};
+use uuid::Uuid;
+use zenoh::bytes::Encoding;
+
+use crate::{
+    device::{
+        devices::{PingAnswer, PingRequest},
+        manager::{
+            distance_filter::{self, FilteredDistance},
+            Answer, DeviceInfo, DeviceSelection, DeviceStatus, ManagerActorHandler, Request,
+            UuidWrapper,
+        },
+    },
+    vehicle::ZenohSession,
+};
+
+/// Root of every key published, data goes to `ping/<device_id>/<topic>`.
+pub const KEY_PREFIX: &str = "ping";
+/// JSON schemas of the published messages can be queried on `ping/schema/<schema>`.
+pub const SCHEMA_KEY_PREFIX: &str = "ping/schema";
+
+const DEVICES_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct DeviceStatusMessage {
+    #[schemars(description = "Device type")]
+    pub device_type: DeviceSelection,
+    #[schemars(description = "Current device status")]
+    pub status: DeviceStatus,
+}
+
+// Every message published, with the key suffix and the schema name used on its encoding
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Topic {
+    Profile,
+    AutoDeviceData,
+    DeviceData,
+    FilteredDistance,
+    Status,
+}
+
+impl Topic {
+    const ALL: [Topic; 5] = [
+        Topic::Profile,
+        Topic::AutoDeviceData,
+        Topic::DeviceData,
+        Topic::FilteredDistance,
+        Topic::Status,
+    ];
+
+    fn key(&self) -> &'static str {
+        match self {
+            Topic::Profile => "profile",
+            Topic::AutoDeviceData => "auto_device_data",
+            Topic::DeviceData => "device_data",
+            Topic::FilteredDistance => "filtered_distance",
+            Topic::Status => "status",
+        }
+    }
+
+    fn schema_name(&self) -> &'static str {
+        match self {
+            Topic::Profile => "ping1d.Profile",
+            Topic::AutoDeviceData => "ping360.AutoDeviceData",
+            Topic::DeviceData => "ping360.DeviceData",
+            Topic::FilteredDistance => "ping1d.FilteredDistance",
+            Topic::Status => "ping.DeviceStatus",
+        }
+    }
+
+    fn schema(&self) -> serde_json::Value {
+        let schema = match self {
+            Topic::Profile => schemars::schema_for!(ProfileStruct),
+            Topic::AutoDeviceData => schemars::schema_for!(AutoDeviceDataStruct),
+            Topic::DeviceData => schemars::schema_for!(DeviceDataStruct),
+            Topic::FilteredDistance => schemars::schema_for!(FilteredDistance),
+            Topic::Status => schemars::schema_for!(DeviceStatusMessage),
+        };
+        serde_json::to_value(schema).unwrap_or_default()
+    }
+
+    fn encoding(&self) -> Encoding {
+        Encoding::APPLICATION_JSON.with_schema(self.schema_name())
+    }
+}
+
+async fn publish<T: Serialize>(session: &ZenohSession, device_id: Uuid, topic: Topic, value: &T) {
+    let Some(session) = session.read().await.clone() else {
+        return;
+    };
+
+    let payload = match serde_json::to_vec(value) {
+        Ok(payload) => payload,
+        Err(err) => {
+            error!("Zenoh publisher: failed to serialize {topic:?}: {err}");
+            return;
+        }
+    };
+
+    let key = format!("{KEY_PREFIX}/{device_id}/{}", topic.key());
+    if let Err(err) = session.put(&key, payload).encoding(topic.encoding()).await {
+        trace!("Zenoh publisher: failed to put on {key}: {err}");
+    }
+}
+
+/// Publish sonar data and status of every device on the Zenoh session shared with the vehicle bridge.
+pub struct ZenohPublisher {
+    session: ZenohSession,
+    manager_handler: ManagerActorHandler,
+    devices: HashMap<Uuid, JoinHandle<()>>,
+    statuses: HashMap<Uuid, DeviceStatus>,
+    schema_queryable: Option<(zenoh::Session, JoinHandle<()>)>,
+}
+
+impl ZenohPublisher {
+    pub fn new(session: ZenohSession, manager_handler: ManagerActorHandler) -> Self {
+        Self {
+            session,
+            manager_handler,
+            devices: HashMap::new(),
+            statuses: HashMap::new(),
+            schema_queryable: None,
+        }
+    }
+
+    pub async fn run(mut self) {
+        info!("Zenoh publisher is running");
+
+        let mut interval = tokio::time::interval(DEVICES_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.update_schema_queryable().await;
+            self.update_devices().await;
+        }
+    }
+
+    // Follow the bridge session, schemas are served by the current one
+    async fn update_schema_queryable(&mut self) {
+        let session = self.session.read().await.clone();
+
+        let is_current = match (&session, &self.schema_queryable) {
+            (Some(session), Some((queryable_session, task))) => {
+                session.zid() == queryable_session.zid() && !task.is_finished()
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if is_current {
+            return;
+        }
+
+        if let Some((_, task)) = self.schema_queryable.take() {
+            task.abort();
+        }
+        // Statuses are published on changes, so the new session should receive all of them again
+        self.statuses.clear();
+        if let Some(session) = session {
+            self.schema_queryable = Some((session.clone(), tokio::spawn(serve_schemas(session))));
+        }
+    }
+
+    async fn update_devices(&mut self) {
+        let devices = match self.manager_handler.send(Request::List).await {
+            Ok(Answer::DeviceInfo(devices)) => devices,
+            Ok(answer) => {
+                warn!("Zenoh publisher: unexpected answer while listing devices: {answer:?}");
+                return;
+            }
+            Err(err) => {
+                trace!("Zenoh publisher: failed to list devices: {err:?}");
+                return;
+            }
+        };
+
+        self.devices.retain(|device_id, task| {
+            let exists = devices.iter().any(|device| device.id == *device_id);
+            if !exists {
+                task.abort();
+            }
+            exists
+        });
+        self.statuses
+            .retain(|device_id, _| devices.iter().any(|device| device.id == *device_id));
+
+        for device in devices {
+            if self.statuses.get(&device.id) != Some(&device.status) {
+                self.statuses.insert(device.id, device.status.clone());
+                publish(
+                    &self.session,
+                    device.id,
+                    Topic::Status,
+                    &DeviceStatusMessage {
+                        device_type: device.device_type.clone(),
+                        status: device.status.clone(),
+                    },
+                )
+                .await;
+            }
+
+            let is_alive = self
+                .devices
+                .get(&device.id)
+                .is_some_and(|task| !task.is_finished());
+            if is_alive
+                || !matches!(
+                    device.status,
+                    DeviceStatus::Running | DeviceStatus::ContinuousMode
+                )
+            {
+                continue;
+            }
+
+            if let Some(task) = self.forward_device(&device).await {
+                self.devices.insert(device.id, task);
+            }
+        }
+    }
+
+    // A new task is needed every time the device actor is rebuilt, like after a reconnection
+    async fn forward_device(&self, device: &DeviceInfo) -> Option<JoinHandle<()>> {
+        let handler = match self
+            .manager_handler
+            .send(Request::GetDeviceHandler(UuidWrapper { uuid: device.id }))
+            .await
+        {
+            Ok(Answer::InnerDeviceHandler(handler)) => handler,
+            answer => {
+                trace!(
+                    "Zenoh publisher: failed to get device handler: {answer:?}, device: {}",
+                    device.id
+                );
+                return None;
+            }
+        };
+
+        let mut subscriber = match handler.send(PingRequest::GetSubscriber).await {
+            Ok(PingAnswer::Subscriber(subscriber)) => subscriber,
+            answer => {
+                trace!(
+                    "Zenoh publisher: failed to get subscriber: {answer:?}, device: {}",
+                    device.id
+                );
+                return None;
+            }
+        };
+        let mut filtered_distances = distance_filter::subscribe(device.id);
+
+        let session = self.session.clone();
+        let device_id = device.id;
+        debug!("Zenoh publisher: publishing data from device {device_id}");
+
+        Some(tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    message = subscriber.recv() => match message {
+                        Ok(message) => match bluerobotics_ping::Messages::try_from(&message) {
+                            Ok(bluerobotics_ping::Messages::Ping1D(
+                                bluerobotics_ping::ping1d::Messages::Profile(profile),
+                            )) => publish(&session, device_id, Topic::Profile, &profile).await,
+                            Ok(bluerobotics_ping::Messages::Ping360(
+                                bluerobotics_ping::ping360::Messages::AutoDeviceData(data),
+                            )) => publish(&session, device_id, Topic::AutoDeviceData, &data).await,
+                            Ok(bluerobotics_ping::Messages::Ping360(
+                                bluerobotics_ping::ping360::Messages::DeviceData(data),
+                            )) => publish(&session, device_id, Topic::DeviceData, &data).await,
+                            _ => {}
+                        },
+                        Err(RecvError::Lagged(skipped)) => {
+                            warn!("Zenoh publisher: skipped {skipped} messages, device: {device_id}");
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    Ok(filtered_distance) = filtered_distances.recv() => {
+                        publish(&session, device_id, Topic::FilteredDistance, &filtered_distance).await;
+                    }
+                }
+            }
+
+            debug!("Zenoh publisher: device {device_id} subscriber closed");
+        }))
+    }
+}
+
+impl Drop for ZenohPublisher {
+    fn drop(&mut self) {
+        for task in self.devices.values() {
+            task.abort();
+        }
+        if let Some((_, task)) = &self.schema_queryable {
+            task.abort();
+        }
+    }
+}
+
+async fn serve_schemas(session: zenoh::Session) {
+    let key = format!("{SCHEMA_KEY_PREFIX}/*");
+    let queryable = match session.declare_queryable(&key).await {
+        Ok(queryable) => queryable,
+        Err(err) => {
+            error!("Zenoh publisher: failed to declare schema queryable on {key}: {err}");
+            return;
+        }
+    };
+
+    while let Ok(query) = queryable.recv_async().await {
+        for topic in Topic::ALL {
+            let schema_key = format!("{SCHEMA_KEY_PREFIX}/{}", topic.schema_name());
+            let Ok(schema_key_expr) = zenoh::key_expr::KeyExpr::try_from(schema_key.clone()) else {
+                continue;
+            };
+            if !query.key_expr().intersects(&schema_key_expr) {
+                continue;
+            }
+
+            let payload = serde_json::to_vec(&topic.schema()).unwrap_or_default();
+            if let Err(err) = query
+                .reply(&schema_key, payload)
+                .encoding(Encoding::APPLICATION_JSON)
+                .await
+            {
+                trace!("Zenoh publisher: failed to reply schema query on {schema_key}: {err}");
+            }
+        }
+    }
+}
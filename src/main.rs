@@ -1,8 +1,14 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::info;
+use tracing::{error, info, warn};
 
-use ping_viewer_next::{cli, device, logger, server, vehicle::zenoh_client_bridge};
+use ping_viewer_next::{
+    cli, device, logger, server,
+    vehicle::{
+        pose_history::{PoseHistory, SharedPoseHistory},
+        zenoh_client_bridge, ZenohSession,
+    },
+};
 
 #[tokio::main]
 async fn main() {
@@ -11,14 +17,52 @@ async fn main() {
     // Logger should start before everything else to register any log information
     logger::manager::init();
 
-    let vehicle_data = Arc::new(RwLock::new(None));
+    let pose_history: SharedPoseHistory = Arc::new(RwLock::new(PoseHistory::default()));
+    let zenoh_session: ZenohSession = Arc::new(RwLock::new(None));
+    let zenoh_bridge_config = cli::manager::zenoh_bridge_config();
 
-    // Start the Zenoh-client with shared data
-    tokio::spawn(zenoh_client_bridge(vehicle_data.clone()));
+    // Start the Zenoh-client with shared data, it only reports its state when disabled
+    tokio::spawn(zenoh_client_bridge(
+        pose_history.clone(),
+        zenoh_session.clone(),
+        zenoh_bridge_config.clone(),
+    ));
 
-    let (mut manager, handler) = device::manager::DeviceManager::new(10);
+    let settings_path = cli::manager::settings_path();
+    if cli::manager::is_reset() {
+        if let Err(err) = device::manager::settings::reset(settings_path.as_ref()) {
+            error!("Unable to reset settings file, details {err:?}");
+        }
+    }
+
+    let (mut manager, handler) =
+        device::manager::DeviceManager::new_with_settings(10, &settings_path);
+
+    // Simulators should live for the whole application, dropping the handle stops the device
+    let mut simulators = Vec::new();
+    for config in cli::manager::simulators() {
+        match device::simulator::start(config).await {
+            Ok(simulator) => simulators.push(simulator),
+            Err(err) => error!("Unable to start simulator, details {err:?}"),
+        }
+    }
+
+    match manager.restore_settings().await {
+        Ok(answer) => info!("DeviceManager restored previous devices: {answer:?}"),
+        Err(err) => info!("DeviceManager unable to restore previous devices, details {err:?}"),
+    }
+
+    for simulator in &simulators {
+        let create = simulator.create_struct();
+        match manager.create(create.source, create.device_selection).await {
+            Ok(answer) => info!("DeviceManager created simulated device: {answer:?}"),
+            Err(device::manager::ManagerError::DeviceAlreadyExist(id)) => {
+                info!("DeviceManager simulated device already restored: {id}")
+            }
+            Err(err) => error!("DeviceManager unable to create simulated device, details {err:?}"),
+        }
+    }
 
-    //Todo: Load previous devices
     if cli::manager::is_enable_auto_create() {
         match manager.auto_create().await {
             Ok(answer) => info!("DeviceManager initialized with following devices: {answer:?}"),
@@ -26,21 +70,48 @@ async fn main() {
         }
     }
 
-    let (recordings_manager, recordings_manager_handler) =
+    let (mut recordings_manager, recordings_manager_handler) =
         device::recording::RecordingManager::new_with_pose(
             10,
-            "recordings",
+            cli::manager::recordings_path(),
             handler.clone(),
-            vehicle_data,
+            pose_history,
         );
+    if let Err(err) = recordings_manager.set_policy(cli::manager::recording_policy()) {
+        error!("Invalid recording policy, using the default one, details {err:?}");
+    }
     tokio::spawn(async move { recordings_manager.run().await });
 
     tokio::spawn(async move { manager.run().await });
 
+    // Ping1D distances are sent to the autopilot, the Zenoh bridge is used unless a MAVLink address is given
+    let rangefinder_transport = match cli::manager::rangefinder_output() {
+        device::rangefinder::RangefinderTransportSelection::Zenoh => {
+            if !zenoh_bridge_config.enabled {
+                warn!("Zenoh bridge is disabled, rangefinder data will not reach the autopilot");
+            }
+            device::rangefinder::RangefinderTransport::Zenoh {
+                session: zenoh_session.clone(),
+                key: zenoh_bridge_config.outgoing_key(),
+            }
+        }
+        device::rangefinder::RangefinderTransportSelection::Mavlink(address) => {
+            device::rangefinder::RangefinderTransport::Mavlink(address)
+        }
+    };
+    device::rangefinder::start(rangefinder_transport, zenoh_bridge_config.system_id);
+
+    // Sonar data goes to the same Zenoh session used to receive the vehicle pose
+    let zenoh_publisher =
+        device::zenoh_publisher::ZenohPublisher::new(zenoh_session, handler.clone());
+    tokio::spawn(async move { zenoh_publisher.run().await });
+
     server::manager::run(
         &cli::manager::server_address(),
         handler,
         recordings_manager_handler,
+        cli::manager::auth_config(),
+        cli::manager::cors_origins(),
     )
     .await
     .unwrap();
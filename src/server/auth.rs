@@ -0,0 +1,482 @@
+use std::{collections::HashMap, str::FromStr};
+
+use actix_cors::Cors;
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header,
+    middleware::Next,
+    web::{Data, Query},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use base64::Engine;
+use paperclip::actix::Apiv2Schema;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::device::{
+    devices::{Ping1DRequest, Ping360Request, PingCommonRequest, PingRequest},
+    manager::{ModifyDeviceCommand, Request},
+    recording::RecordingManagerCommand,
+};
+use crate::server::protocols::v1::errors::Error;
+
+// Paths reachable without credentials, used by BlueOS to discover the extension
+const PUBLIC_PATHS: [&str; 3] = [
+    "/register_service",
+    "/cockpit_extras.json",
+    "/v1/cockpit_extras.json",
+];
+// The frontend is served to everyone so browsers can load the login page: its index, the routes
+// of its router and the embedded files. Checked on the raw path, anything percent-encoded
+// doesn't match an embedded file and needs credentials.
+fn is_frontend(method: &actix_web::http::Method, path: &str) -> bool {
+    if method != actix_web::http::Method::GET {
+        return false;
+    }
+    let path = path.strip_prefix("/v1").unwrap_or(path);
+    path.is_empty()
+        || path == "/"
+        || path.starts_with("/addons/")
+        || crate::server::protocols::v1::rest::is_embedded_file(path.trim_start_matches('/'))
+}
+
+/// Access levels, each one includes the previous ones
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Apiv2Schema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Read devices, recordings and data streams
+    Viewer,
+    /// Configure devices and control recordings
+    Operator,
+    /// Create and delete devices, change their network and firmware
+    Admin,
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "viewer" => Ok(Role::Viewer),
+            "operator" => Ok(Role::Operator),
+            "admin" => Ok(Role::Admin),
+            _ => Err(format!(
+                "Unknown role: {s}, expected viewer, operator or admin"
+            )),
+        }
+    }
+}
+
+/// Bearer token accepted by the server, in the "ROLE:TOKEN" format
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthToken {
+    pub role: Role,
+    pub token: String,
+}
+
+impl FromStr for AuthToken {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (role, token) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid token: {s}, expected ROLE:TOKEN"))?;
+        if token.is_empty() {
+            return Err("Token can't be empty".to_string());
+        }
+        Ok(Self {
+            role: role.parse()?,
+            token: token.to_string(),
+        })
+    }
+}
+
+/// Basic authentication user, in the "ROLE:USER:PASSWORD" format
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthUser {
+    pub role: Role,
+    pub user: String,
+    pub password: String,
+}
+
+impl FromStr for AuthUser {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(role), Some(user), Some(password)) if !user.is_empty() => Ok(Self {
+                role: role.parse()?,
+                user: user.to_string(),
+                password: password.to_string(),
+            }),
+            _ => Err(format!("Invalid user: {s}, expected ROLE:USER:PASSWORD")),
+        }
+    }
+}
+
+/// Credentials accepted by the server, everyone is an admin when there are none
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    pub tokens: Vec<AuthToken>,
+    pub users: Vec<AuthUser>,
+}
+
+/// Authenticated client, available on the request extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub role: Role,
+    /// User name, empty for tokens and when authentication is disabled
+    pub user: Option<String>,
+}
+
+// Avoid leaking how much of a secret matched through the comparison time
+fn secret_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+impl AuthConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || !self.users.is_empty()
+    }
+
+    fn token_identity(&self, token: &str) -> Option<Identity> {
+        self.tokens
+            .iter()
+            .find(|candidate| secret_eq(&candidate.token, token))
+            .map(|candidate| Identity {
+                role: candidate.role,
+                user: None,
+            })
+    }
+
+    fn basic_identity(&self, credentials: &str) -> Option<Identity> {
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials)
+            .ok()?;
+        let decoded = String::from_utf8(decoded).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+        self.users
+            .iter()
+            .find(|candidate| candidate.user == user && secret_eq(&candidate.password, password))
+            .map(|candidate| Identity {
+                role: candidate.role,
+                user: Some(candidate.user.clone()),
+            })
+    }
+
+    // Browsers can't set headers on websockets, so the token can also be sent as a query parameter
+    pub fn identify(&self, authorization: Option<&str>, query: &str) -> Option<Identity> {
+        if !self.is_enabled() {
+            return Some(Identity {
+                role: Role::Admin,
+                user: None,
+            });
+        }
+
+        if let Some(authorization) = authorization {
+            let (scheme, credentials) = authorization.split_once(' ')?;
+            return match scheme.to_lowercase().as_str() {
+                "bearer" => self.token_identity(credentials.trim()),
+                "basic" => self.basic_identity(credentials.trim()),
+                _ => None,
+            };
+        }
+
+        // Tokens are percent-encoded by the clients, like any query parameter
+        let query = Query::<HashMap<String, String>>::from_query(query).ok()?;
+        query
+            .get("token")
+            .and_then(|token| self.token_identity(token))
+    }
+
+    fn challenge(&self) -> &'static str {
+        if self.users.is_empty() {
+            "Bearer"
+        } else {
+            "Basic realm=\"ping-viewer-next\""
+        }
+    }
+}
+
+/// Middleware rejecting requests without valid credentials, access to each operation is checked
+/// later by the handlers with the identity left on the request extensions.
+pub async fn authenticate<B: MessageBody + 'static>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let config = req
+        .app_data::<Data<AuthConfig>>()
+        .map(|config| config.get_ref().clone())
+        .unwrap_or_default();
+
+    let authorization = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok());
+    let identity = config.identify(authorization, req.query_string());
+
+    match identity {
+        Some(identity) => {
+            req.extensions_mut().insert(identity);
+        }
+        None if PUBLIC_PATHS.contains(&req.path())
+            || is_frontend(req.method(), req.path())
+            || req.method() == actix_web::http::Method::OPTIONS => {}
+        None => {
+            warn!(
+                "Unauthorized request to {} from {:?}",
+                req.path(),
+                req.peer_addr()
+            );
+            let response = HttpResponse::Unauthorized()
+                .insert_header((header::WWW_AUTHENTICATE, config.challenge()))
+                .body("Unauthorized: Missing or invalid credentials");
+            return Ok(req.into_response(response));
+        }
+    }
+
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_boxed_body)
+}
+
+pub fn identity(req: &HttpRequest) -> Option<Identity> {
+    req.extensions().get::<Identity>().cloned()
+}
+
+pub fn is_enabled(req: &HttpRequest) -> bool {
+    req.app_data::<Data<AuthConfig>>()
+        .is_some_and(|config| config.is_enabled())
+}
+
+/// Owner of the device locks taken by a client, scoped to its user so users never share one
+pub fn client_owner(identity: Option<&Identity>, client: &str) -> String {
+    match identity.and_then(|identity| identity.user.as_deref()) {
+        Some(user) => format!("{user}/{client}"),
+        None => client.to_string(),
+    }
+}
+
+pub fn require(req: &HttpRequest, role: Role) -> Result<(), Error> {
+    let current = identity(req).map(|identity| identity.role);
+    if current.is_some_and(|current| current >= role) {
+        return Ok(());
+    }
+    Err(Error::Forbidden(format!(
+        "{role:?} role needed, current role is {current:?}"
+    )))
+}
+
+pub fn ping_request_role(request: &PingRequest) -> Role {
+    if !request.is_mutating() {
+        return Role::Viewer;
+    }
+    match request {
+        PingRequest::Ping1D(Ping1DRequest::SetDeviceId(_) | Ping1DRequest::GotoBootloader)
+        | PingRequest::Ping360(Ping360Request::SetDeviceId(_) | Ping360Request::Reset(_))
+        | PingRequest::Common(PingCommonRequest::SetDeviceId(_))
+        | PingRequest::Upgrade => Role::Admin,
+        _ => Role::Operator,
+    }
+}
+
+pub fn request_role(request: &Request) -> Role {
+    match request {
+        Request::List | Request::Info(_) | Request::GetSpeedOfSound => Role::Viewer,
+        Request::ModifyDevice(modify) if modify.modify.is_read_only() => Role::Viewer,
+        Request::ModifyDevice(modify) if matches!(modify.modify, ModifyDeviceCommand::SetIp(_)) => {
+            Role::Admin
+        }
+        Request::Ping(request) => ping_request_role(&request.device_request),
+        Request::AutoCreate
+        | Request::Create(_)
+        | Request::Delete(_)
+        | Request::ForceReleaseLock(_)
+        | Request::GetDeviceHandler(_)
+        | Request::SpecialTurnOffContinuousMode(_) => Role::Admin,
+        Request::Search
+        | Request::ModifyDevice(_)
+        | Request::EnableContinuousMode(_)
+        | Request::DisableContinuousMode(_)
+        | Request::ReplayControl(_)
+        | Request::SetSpeedOfSoundConfig(_)
+        | Request::AcquireLock(_)
+        | Request::ReleaseLock(_) => Role::Operator,
+    }
+}
+
+pub fn recording_command_role(command: &RecordingManagerCommand) -> Role {
+    match command {
+        RecordingManagerCommand::StartRecording(_)
+        | RecordingManagerCommand::StopRecording(_)
+        | RecordingManagerCommand::StartSession(_)
+        | RecordingManagerCommand::StopSession(_)
+        | RecordingManagerCommand::SetSessionTags(_)
+        | RecordingManagerCommand::AddMarker(_) => Role::Operator,
+        // The retention policy deletes recordings
+        RecordingManagerCommand::SetRecordingPolicy(_) => Role::Admin,
+        RecordingManagerCommand::GetRecordingStatus(_)
+        | RecordingManagerCommand::GetAllRecordingStatus
+        | RecordingManagerCommand::GetSubscriber
+        | RecordingManagerCommand::GetRecordingPolicy
+        | RecordingManagerCommand::GetSessionStatus(_) => Role::Viewer,
+    }
+}
+
+pub fn authorize(role: Option<Role>, request: &Request) -> Result<(), Error> {
+    let required = request_role(request);
+    if role.is_some_and(|role| role >= required) {
+        return Ok(());
+    }
+    Err(Error::Forbidden(format!(
+        "{required:?} role needed, current role is {role:?}"
+    )))
+}
+
+/// CORS policy, any origin is allowed when the list is empty
+pub fn cors(origins: &[String]) -> Cors {
+    if origins.is_empty() {
+        return Cors::permissive();
+    }
+    origins
+        .iter()
+        .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+        .allow_any_method()
+        .allow_any_header()
+        .supports_credentials()
+        .max_age(3600)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::manager::{ModifyDevice, UuidWrapper};
+    use uuid::Uuid;
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            tokens: vec![
+                "viewer:abc".parse().unwrap(),
+                "operator:a+b/c=".parse().unwrap(),
+            ],
+            users: vec!["admin:pilot:secret:123".parse().unwrap()],
+        }
+    }
+
+    #[test]
+    fn test_identify() {
+        let disabled = AuthConfig::default();
+        assert_eq!(disabled.identify(None, "").unwrap().role, Role::Admin);
+
+        let config = config();
+        assert_eq!(config.identify(None, ""), None);
+        assert_eq!(
+            config.identify(Some("Bearer abc"), "").unwrap().role,
+            Role::Viewer
+        );
+        assert_eq!(config.identify(Some("Bearer abd"), ""), None);
+        assert_eq!(
+            config.identify(None, "filter=.*&token=abc").unwrap().role,
+            Role::Viewer
+        );
+        assert_eq!(
+            config.identify(None, "token=a%2Bb%2Fc%3D").unwrap().role,
+            Role::Operator
+        );
+
+        let basic = format!(
+            "Basic {}",
+            base64::engine::general_purpose::STANDARD.encode("pilot:secret:123")
+        );
+        let identity = config.identify(Some(&basic), "").unwrap();
+        assert_eq!(identity.role, Role::Admin);
+        assert_eq!(identity.user.as_deref(), Some("pilot"));
+
+        assert!("guest:abc".parse::<AuthToken>().is_err());
+        assert!("viewer:pilot".parse::<AuthUser>().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_authenticate_paths() {
+        use actix_web::{
+            test::{call_service, init_service, TestRequest},
+            web, App,
+        };
+
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(config()))
+                .wrap(actix_web::middleware::from_fn(authenticate))
+                .route(
+                    "/ws",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                )
+                .route(
+                    "/{file_path:.*}",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+
+        for (path, status) in [
+            ("/", 200),
+            ("/index.html", 200),
+            ("/v1/index.html", 200),
+            ("/ws", 401),
+            // Routes match the decoded path, classifying on it would let these in
+            ("/%77s", 401),
+            ("/%72ecordings/list", 401),
+        ] {
+            let response = call_service(&app, TestRequest::get().uri(path).to_request()).await;
+            assert_eq!(response.status().as_u16(), status, "{path}");
+        }
+
+        let response = call_service(&app, TestRequest::post().uri("/").to_request()).await;
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    #[test]
+    fn test_request_roles() {
+        let uuid = Uuid::new_v4();
+        let modify = |modify| Request::ModifyDevice(ModifyDevice { uuid, modify });
+
+        assert_eq!(request_role(&Request::List), Role::Viewer);
+        assert_eq!(
+            request_role(&modify(ModifyDeviceCommand::GetPing360Config)),
+            Role::Viewer
+        );
+        assert_eq!(
+            request_role(&modify(ModifyDeviceCommand::StopPing360ScanPlan)),
+            Role::Operator
+        );
+        assert_eq!(
+            request_role(&modify(ModifyDeviceCommand::SetIp(
+                std::net::Ipv4Addr::LOCALHOST
+            ))),
+            Role::Admin
+        );
+        assert_eq!(
+            request_role(&Request::Delete(UuidWrapper { uuid })),
+            Role::Admin
+        );
+
+        assert_eq!(
+            ping_request_role(&PingRequest::Ping1D(Ping1DRequest::Distance)),
+            Role::Viewer
+        );
+        assert_eq!(
+            ping_request_role(&PingRequest::Ping1D(Ping1DRequest::GotoBootloader)),
+            Role::Admin
+        );
+        assert!(authorize(Some(Role::Operator), &Request::List).is_ok());
+        assert!(authorize(None, &Request::List).is_err());
+    }
+}
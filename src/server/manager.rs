@@ -1,9 +1,8 @@
 use crate::device::{manager::ManagerActorHandler, recording::RecordingsManagerHandler};
 
-use super::protocols;
-use actix_cors::Cors;
+use super::{auth, protocols};
 use actix_web::{middleware, web::Data, App, HttpServer};
-use tracing::info;
+use tracing::{info, warn};
 
 use paperclip::actix::{
     web::{self, Scope},
@@ -18,12 +17,17 @@ pub async fn run(
     server_address: &str,
     devices_manager_handler: ManagerActorHandler,
     recordings_handler: RecordingsManagerHandler,
+    auth_config: auth::AuthConfig,
+    cors_origins: Vec<String>,
 ) -> std::io::Result<()> {
     let server_address = server_address.to_string();
     info!("ServerManager: Service starting");
+    if !auth_config.is_enabled() {
+        warn!("ServerManager: Authentication is disabled, every client has admin access");
+    }
 
     let server = HttpServer::new(move || {
-        let cors = Cors::permissive();
+        let cors = auth::cors(&cors_origins);
 
         let v1 = add_v1_paths(web::scope("/v1"));
         let default = add_v1_paths(web::scope(""));
@@ -31,6 +35,9 @@ pub async fn run(
         App::new()
             .app_data(Data::new(devices_manager_handler.clone()))
             .app_data(Data::new(recordings_handler.clone()))
+            .app_data(Data::new(auth_config.clone()))
+            // Authentication runs inside CORS, so rejected requests still carry its headers
+            .wrap(middleware::from_fn(auth::authenticate))
             .wrap(cors)
             .wrap(middleware::Logger::default())
             .wrap_api()
@@ -40,6 +47,7 @@ pub async fn run(
             .service(protocols::v1::rest::server_metadata)
             .service(protocols::v1::websocket::websocket)
             .service(protocols::v1::websocket::recording_websocket)
+            .service(protocols::v1::websocket::vehicle_websocket)
             .service(default)
             .build()
     });
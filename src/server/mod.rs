@@ -1,3 +1,4 @@
+pub mod auth;
 pub mod manager;
 pub mod protocols;
 
@@ -23,3 +24,8 @@ pub mod protocols;
 // Otherwise, if they are not defined, the WebSocket channel will receive all available messages.
 // All operations made through REST API and WebSocket routes will be broadcast to all clients subscribed to device-number=null (default),
 // except for errors, which are forwarded directly to the requester.
+//
+// Authentication:
+// Disabled by default, turned on by passing tokens (--auth-token) or users (--auth-user) on the command line.
+// Clients authenticate with "Authorization: Bearer <token>", basic authentication, or ?token=<token> for websockets.
+// Each token or user has a role: viewer (read only), operator (configure devices and recordings) or admin (everything).
@@ -1,6 +1,12 @@
-use crate::device::manager::{ManagerActorHandler, Request, UuidWrapper};
-use crate::server::protocols::v1::errors::Error;
-use actix_web::Responder;
+use crate::device::manager::{
+    sonar_image::{self, Colormap, SonarImageSettings},
+    Answer, DeviceSelection, ManagerActorHandler, Request, UuidWrapper,
+};
+use crate::server::{
+    auth::{self, Role},
+    protocols::v1::errors::Error,
+};
+use actix_web::{HttpRequest, Responder};
 use mime_guess::from_path;
 use paperclip::actix::{
     api_v2_operation, get, post,
@@ -23,6 +29,10 @@ struct Asset;
 #[folder = "ping-viewer-next-frontend/dist"]
 struct Asset;
 
+pub fn is_embedded_file(path: &str) -> bool {
+    !path.is_empty() && Asset::get(path).is_some()
+}
+
 fn handle_embedded_file(path: &str) -> HttpResponse {
     match Asset::get(path) {
         Some(content) => HttpResponse::Ok()
@@ -65,25 +75,72 @@ pub fn register_services(cfg: &mut web::ServiceConfig) {
         .service(device_manager_get)
         .service(device_manager_post)
         .service(recording::recording_manager_get)
+        .service(recording::recording_policy_get)
+        .service(recording::recording_session_post)
+        .service(recording::recording_session_get)
+        .service(recording::recording_session_stop_post)
+        .service(recording::recording_session_tags_post)
+        .service(recording::recording_session_marker_post)
+        .service(recording::recording_policy_post)
         .service(recording::recording_manager_post)
         .service(recording::recordings_manager_post_request)
         .service(post_create)
+        .service(post_speed_of_sound)
+        .service(device_manager_replay_post)
+        .service(device_manager_ping360_image_get)
+        .service(device_manager_ping360_image_settings_get)
+        .service(device_manager_ping360_image_settings_post)
         .service(device_manager_device_get)
         .service(device_manager_device_ping1d_get)
         .service(device_manager_device_ping360_get)
         .service(device_manager_device_common_get)
         .service(addons_handler)
         .service(cockpit_extras)
+        .service(vehicle_bridge_get)
+        .service(vehicle_pose_get)
         .service(recording::list_mcap_recordings)
         .service(recording::download_mcap_file)
+        .service(recording::export_mcap_file)
+        .service(recording::extract_mcap_file)
+        .service(recording::recording_annotations_get)
+        .service(recording::recording_summary_get)
+        .service(recording::import_ping_viewer_log)
         .service(recording::delete_mcap_file)
         .service(index_files);
 }
 
+// Clients identify themselves with the X-Client-Id header, otherwise by their address.
+// With authentication the header is required to control a device, clients behind the same
+// proxy would share their address.
+fn client_id(req: &HttpRequest, request: &Request) -> Result<Option<String>, Error> {
+    let client = req
+        .headers()
+        .get("X-Client-Id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    if !auth::is_enabled(req) {
+        return Ok(client.or_else(|| req.peer_addr().map(|addr| addr.ip().to_string())));
+    }
+
+    match client {
+        Some(client) => Ok(Some(auth::client_owner(
+            auth::identity(req).as_ref(),
+            &client,
+        ))),
+        None if request.controlled_device().is_some() => Err(Error::BadRequest(
+            "The X-Client-Id header is needed to control a device".to_string(),
+        )),
+        None => Ok(None),
+    }
+}
+
 async fn send_request_and_broadcast(
+    req: &HttpRequest,
     manager_handler: &web::Data<ManagerActorHandler>,
     request: Request,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
+    auth::authorize(auth::identity(req).map(|identity| identity.role), &request)?;
+
     let request_has_id = match &request {
         Request::ModifyDevice(modify) => Some(modify.uuid),
         Request::Ping(device_request) => Some(device_request.uuid),
@@ -91,10 +148,15 @@ async fn send_request_and_broadcast(
         Request::Info(uuid_wrapper) => Some(uuid_wrapper.uuid),
         Request::EnableContinuousMode(uuid_wrapper) => Some(uuid_wrapper.uuid),
         Request::DisableContinuousMode(uuid_wrapper) => Some(uuid_wrapper.uuid),
+        Request::ReplayControl(replay_control) => Some(replay_control.uuid),
+        Request::AcquireLock(lock_request) => Some(lock_request.uuid),
+        Request::ReleaseLock(uuid_wrapper) => Some(uuid_wrapper.uuid),
+        Request::ForceReleaseLock(uuid_wrapper) => Some(uuid_wrapper.uuid),
         _ => None,
     };
 
-    let answer = manager_handler.send(request).await?;
+    let client = client_id(req, &request)?;
+    let answer = manager_handler.send_from(client, request).await?;
     crate::server::protocols::v1::websocket::send_to_websockets(json!(answer), request_has_id);
     Ok(Json(answer))
 }
@@ -102,12 +164,13 @@ async fn send_request_and_broadcast(
 #[api_v2_operation(tags("Device Manager"))]
 #[post("device_manager/request")]
 async fn post_request(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     json: web::Json<crate::device::manager::Request>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
     let request = json.into_inner();
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
@@ -115,6 +178,7 @@ pub enum DeviceManagerGetOptionsV1 {
     AutoCreate,
     List,
     Search,
+    SpeedOfSound,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
@@ -123,11 +187,15 @@ pub enum DeviceManagerPostOptionsV1 {
     Info,
     EnableContinuousMode,
     DisableContinuousMode,
+    AcquireLock,
+    ReleaseLock,
+    ForceReleaseLock,
 }
 
 #[api_v2_operation(tags("Device Manager"))]
 #[get("device_manager/{selection}")]
 async fn device_manager_get(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     selection: web::Path<DeviceManagerGetOptionsV1>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
@@ -135,14 +203,16 @@ async fn device_manager_get(
         DeviceManagerGetOptionsV1::AutoCreate => crate::device::manager::Request::AutoCreate,
         DeviceManagerGetOptionsV1::List => crate::device::manager::Request::List,
         DeviceManagerGetOptionsV1::Search => crate::device::manager::Request::Search,
+        DeviceManagerGetOptionsV1::SpeedOfSound => crate::device::manager::Request::GetSpeedOfSound,
     };
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
 }
 
 #[api_v2_operation(tags("Device Manager"))]
 #[post("device_manager/create")]
 async fn post_create(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     info: web::Json<crate::device::manager::CreateStruct>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
@@ -150,12 +220,121 @@ async fn post_create(
 
     let request = crate::device::manager::Request::Create(create_struct);
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
+}
+
+#[api_v2_operation(tags("Device Manager"))]
+#[post("device_manager/speed_of_sound")]
+async fn post_speed_of_sound(
+    req: HttpRequest,
+    manager_handler: web::Data<ManagerActorHandler>,
+    config: web::Json<crate::device::manager::speed_of_sound::SpeedOfSoundConfig>,
+) -> Result<Json<crate::device::manager::Answer>, Error> {
+    let request = crate::device::manager::Request::SetSpeedOfSoundConfig(config.into_inner());
+
+    send_request_and_broadcast(&req, &manager_handler, request).await
+}
+
+#[api_v2_operation(tags("Device Manager : Device"))]
+#[post("device_manager/{device}/replay")]
+async fn device_manager_replay_post(
+    req: HttpRequest,
+    manager_handler: web::Data<ManagerActorHandler>,
+    device: web::Path<Uuid>,
+    command: web::Json<crate::device::replay::ReplayCommand>,
+) -> Result<Json<crate::device::manager::Answer>, Error> {
+    let request =
+        crate::device::manager::Request::ReplayControl(crate::device::manager::ReplayControl {
+            uuid: device.into_inner(),
+            command: command.into_inner(),
+        });
+
+    send_request_and_broadcast(&req, &manager_handler, request).await
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct SonarImageQuery {
+    pub size: Option<u32>,
+    pub colormap: Option<Colormap>,
+}
+
+#[api_v2_operation(tags("Device Manager : Device"))]
+#[get("device_manager/{device}/ping360/image")]
+async fn device_manager_ping360_image_get(
+    req: HttpRequest,
+    device: web::Path<Uuid>,
+    query: web::Query<SonarImageQuery>,
+) -> Result<HttpResponse, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let device_id = device.into_inner();
+    let Some((scan, mut settings)) = sonar_image::snapshot(device_id) else {
+        return Ok(HttpResponse::NotFound()
+            .body(format!("No Ping360 scan available for device {device_id}")));
+    };
+
+    if let Some(size) = query.size {
+        settings.size = size;
+    }
+    if let Some(colormap) = query.colormap {
+        settings.colormap = colormap;
+    }
+    settings.validate().map_err(Error::BadRequest)?;
+
+    let png = actix_web::web::block(move || scan.render_png(&settings))
+        .await
+        .map_err(|err| Error::Internal(err.to_string()))?
+        .map_err(Error::Internal)?;
+    Ok(HttpResponse::Ok()
+        .content_type("image/png")
+        .append_header(("Cache-Control", "no-cache, no-store, must-revalidate"))
+        .body(png))
+}
+
+#[api_v2_operation(tags("Device Manager : Device"))]
+#[get("device_manager/{device}/ping360/image/settings")]
+async fn device_manager_ping360_image_settings_get(
+    req: HttpRequest,
+    device: web::Path<Uuid>,
+) -> Result<Json<SonarImageSettings>, Error> {
+    auth::require(&req, Role::Viewer)?;
+    Ok(Json(
+        sonar_image::get_settings(device.into_inner()).unwrap_or_default(),
+    ))
+}
+
+#[api_v2_operation(tags("Device Manager : Device"))]
+#[post("device_manager/{device}/ping360/image/settings")]
+async fn device_manager_ping360_image_settings_post(
+    req: HttpRequest,
+    manager_handler: web::Data<ManagerActorHandler>,
+    device: web::Path<Uuid>,
+    settings: web::Json<SonarImageSettings>,
+) -> Result<Json<SonarImageSettings>, Error> {
+    auth::require(&req, Role::Operator)?;
+    let uuid = device.into_inner();
+    let settings = settings.into_inner();
+
+    let is_ping360 = match manager_handler
+        .send(Request::Info(UuidWrapper { uuid }))
+        .await?
+    {
+        Answer::DeviceInfo(info) => info
+            .first()
+            .is_some_and(|info| info.device_type == DeviceSelection::Ping360),
+        _ => false,
+    };
+    if !is_ping360 {
+        return Err(Error::BadRequest(format!("Device {uuid} is not a Ping360")));
+    }
+
+    sonar_image::set_settings(uuid, settings).map_err(Error::BadRequest)?;
+    Ok(Json(settings))
 }
 
 #[api_v2_operation(tags("Device Manager : Device"))]
 #[post("device_manager/{device}/{selection}")]
 async fn device_manager_post(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     info: web::Path<(Uuid, DeviceManagerPostOptionsV1)>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
@@ -176,14 +355,27 @@ async fn device_manager_post(
         DeviceManagerPostOptionsV1::DisableContinuousMode => {
             crate::device::manager::Request::DisableContinuousMode(UuidWrapper { uuid })
         }
+        DeviceManagerPostOptionsV1::AcquireLock => crate::device::manager::Request::AcquireLock(
+            crate::device::manager::device_lock::LockRequest {
+                uuid,
+                timeout_s: None,
+            },
+        ),
+        DeviceManagerPostOptionsV1::ReleaseLock => {
+            crate::device::manager::Request::ReleaseLock(UuidWrapper { uuid })
+        }
+        DeviceManagerPostOptionsV1::ForceReleaseLock => {
+            crate::device::manager::Request::ForceReleaseLock(UuidWrapper { uuid })
+        }
     };
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
 }
 
 #[api_v2_operation(tags("Device Manager : Device"))]
 #[get("device_manager/{device}/{request}")]
 async fn device_manager_device_get(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     info: web::Path<(Uuid, crate::device::devices::PingRequest)>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
@@ -197,12 +389,13 @@ async fn device_manager_device_get(
             device_request: request,
         });
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
 }
 
 #[api_v2_operation(tags("Device Manager : Device"))]
 #[get("device_manager/{device}/ping1d/{request}")]
 async fn device_manager_device_ping1d_get(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     info: web::Path<(Uuid, crate::device::devices::Ping1DRequest)>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
@@ -218,12 +411,13 @@ async fn device_manager_device_ping1d_get(
             device_request: request,
         });
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
 }
 
 #[api_v2_operation(tags("Device Manager : Device"))]
 #[get("device_manager/{device}/ping360/{request}")]
 async fn device_manager_device_ping360_get(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     info: web::Path<(Uuid, crate::device::devices::Ping360Request)>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
@@ -239,12 +433,13 @@ async fn device_manager_device_ping360_get(
             device_request: request,
         });
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
 }
 
 #[api_v2_operation(tags("Device Manager : Device"))]
 #[get("device_manager/{device}/common/{request}")]
 async fn device_manager_device_common_get(
+    req: HttpRequest,
     manager_handler: web::Data<ManagerActorHandler>,
     info: web::Path<(Uuid, crate::device::devices::PingCommonRequest)>,
 ) -> Result<Json<crate::device::manager::Answer>, Error> {
@@ -260,7 +455,7 @@ async fn device_manager_device_common_get(
             device_request: request,
         });
 
-    send_request_and_broadcast(&manager_handler, request).await
+    send_request_and_broadcast(&req, &manager_handler, request).await
 }
 
 #[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
@@ -315,6 +510,27 @@ impl Default for ServerMetadata {
     }
 }
 
+/// Connection state of the Zenoh bridge and time of the last vehicle pose received
+#[api_v2_operation(tags("Vehicle"))]
+#[get("vehicle/bridge")]
+async fn vehicle_bridge_get(
+    req: HttpRequest,
+) -> Result<Json<crate::vehicle::ZenohBridgeStatus>, Error> {
+    auth::require(&req, Role::Viewer)?;
+    Ok(Json(crate::vehicle::bridge_status()))
+}
+
+/// Latest vehicle pose received by the Zenoh bridge, empty before the first one,
+/// updates are also streamed by the `ws/vehicle` websocket
+#[api_v2_operation(tags("Vehicle"))]
+#[get("vehicle/pose")]
+async fn vehicle_pose_get(
+    req: HttpRequest,
+) -> Result<Json<Option<crate::vehicle::VehiclePose>>, Error> {
+    auth::require(&req, Role::Viewer)?;
+    Ok(Json(crate::vehicle::latest_pose()))
+}
+
 #[api_v2_operation]
 #[get("/cockpit_extras.json")]
 async fn cockpit_extras(
@@ -1,7 +1,18 @@
 use crate::device::manager::UuidWrapper;
-use crate::device::recording::{RecordingManagerCommand, RecordingsManagerHandler};
-use crate::server::protocols::v1::errors::Error;
-use actix_web::Responder;
+use crate::device::recording::{
+    annotations::{self, RecordingAnnotations},
+    export::{self, ConversionError, ExportFormat, ImportedLog, RecordingExport, TimeRange},
+    ping_viewer, summary, RecordingManagerCommand, RecordingsManagerHandler,
+};
+use crate::server::{
+    auth::{self, Role},
+    protocols::v1::errors::Error,
+};
+use actix_web::{
+    body::{BodySize, MessageBody},
+    web::Bytes,
+    HttpRequest, Responder, ResponseError,
+};
 use chrono::{DateTime, Utc};
 use mime_guess::from_path;
 use paperclip::actix::{
@@ -10,11 +21,20 @@ use paperclip::actix::{
     Apiv2Schema,
 };
 use serde::{Deserialize, Serialize};
-use std::fs;
-use std::path::Path;
-use tracing::debug;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, warn};
 use uuid::Uuid;
 
+// Converted files are sent by chunks of this size while the conversion goes on
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+const STREAM_CHANNEL_SIZE: usize = 16;
+const MAX_IMPORT_SIZE: usize = 256 * 1024 * 1024;
+
 #[derive(Debug, Serialize, Deserialize, Apiv2Schema)]
 pub struct McapFileInfo {
     pub file_name: String,
@@ -29,10 +49,71 @@ pub enum RecordingsManagerPostOptionsV1 {
     GetRecordingStatus,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSort {
+    Name,
+    Size,
+    Modified,
+    /// Start of the recording, from its file name or its modification time
+    Start,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Default, Deserialize, Apiv2Schema)]
+pub struct ListQuery {
+    /// Only recordings of this device
+    pub device: Option<Uuid>,
+    /// Only recordings started at or after this time, RFC 3339
+    pub from: Option<String>,
+    /// Only recordings started before this time, RFC 3339
+    pub to: Option<String>,
+    /// Start when empty and a page is requested, directory order otherwise
+    pub sort: Option<ListSort>,
+    /// Descending when empty
+    pub order: Option<SortOrder>,
+    /// Page to return, starting at 1, all recordings are returned when empty
+    pub page: Option<usize>,
+    /// Recordings per page, 50 when empty
+    pub per_page: Option<usize>,
+}
+
+const DEFAULT_PER_PAGE: usize = 50;
+
+fn parse_query_time(time: &Option<String>) -> Result<Option<DateTime<Utc>>, Error> {
+    time.as_deref()
+        .map(|time| {
+            DateTime::parse_from_rfc3339(time)
+                .map(|time| time.to_utc())
+                .map_err(|err| Error::BadRequest(format!("Invalid time {time}: {err}")))
+        })
+        .transpose()
+}
+
+struct ListedFile {
+    info: McapFileInfo,
+    path: std::path::PathBuf,
+    modified: std::time::SystemTime,
+    start: DateTime<Utc>,
+}
+
+/// Recordings, filtered and sorted by the query, the total before pagination is on the
+/// X-Total-Count header
 #[api_v2_operation(tags("Recordings Server"))]
 #[get("/recordings/list")]
-async fn list_mcap_recordings(req: web::HttpRequest) -> Result<Json<Vec<McapFileInfo>>, Error> {
-    let recordings_dir = Path::new("recordings");
+async fn list_mcap_recordings(
+    req: web::HttpRequest,
+    query: web::Query<ListQuery>,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
+) -> Result<HttpResponse, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let recordings_dir = recordings_handler.base_path();
     debug!("Listing MCAP files in directory: {:?}", recordings_dir);
 
     let show_detailed_listing = req
@@ -41,6 +122,9 @@ async fn list_mcap_recordings(req: web::HttpRequest) -> Result<Json<Vec<McapFile
         .and_then(|h| h.to_str().ok())
         .map(|v| v == "?1")
         .unwrap_or(false);
+    let query = query.into_inner();
+    let from = parse_query_time(&query.from)?;
+    let to = parse_query_time(&query.to)?;
 
     let mut files = Vec::new();
 
@@ -48,7 +132,6 @@ async fn list_mcap_recordings(req: web::HttpRequest) -> Result<Json<Vec<McapFile
         debug!("Creating recordings directory: {:?}", recordings_dir);
         if let Err(e) = fs::create_dir_all(recordings_dir) {
             debug!("Failed to create recordings directory: {:?}", e);
-            return Ok(Json(files));
         }
     }
 
@@ -67,6 +150,9 @@ async fn list_mcap_recordings(req: web::HttpRequest) -> Result<Json<Vec<McapFile
                         if should_include {
                             match entry.metadata() {
                                 Ok(metadata) => {
+                                    let mtime = metadata
+                                        .modified()
+                                        .unwrap_or(std::time::SystemTime::UNIX_EPOCH);
                                     let modified = metadata
                                         .modified()
                                         .ok()
@@ -74,16 +160,20 @@ async fn list_mcap_recordings(req: web::HttpRequest) -> Result<Json<Vec<McapFile
                                             DateTime::<Utc>::from(mtime).to_rfc3339().into()
                                         })
                                         .unwrap_or_else(|| "unknown".to_string());
+                                    let file_name =
+                                        path.file_name().unwrap().to_string_lossy().to_string();
 
                                     debug!("Adding file: {:?}", path.file_name());
-                                    files.push(McapFileInfo {
-                                        file_name: path
-                                            .file_name()
-                                            .unwrap()
-                                            .to_string_lossy()
-                                            .to_string(),
-                                        file_size: metadata.len(),
-                                        modified,
+                                    files.push(ListedFile {
+                                        start: summary::file_name_time(&file_name)
+                                            .unwrap_or_else(|| DateTime::<Utc>::from(mtime)),
+                                        info: McapFileInfo {
+                                            file_name,
+                                            file_size: metadata.len(),
+                                            modified,
+                                        },
+                                        path,
+                                        modified: mtime,
                                     });
                                 }
                                 Err(e) => debug!("Failed to get metadata for {:?}: {:?}", path, e),
@@ -103,17 +193,85 @@ async fn list_mcap_recordings(req: web::HttpRequest) -> Result<Json<Vec<McapFile
         }
     }
 
-    // Sort files by modification time (newest first) when detailed listing is enabled
-    if show_detailed_listing {
-        files.sort_by(|a, b| b.modified.cmp(&a.modified));
+    files.retain(|file| {
+        from.is_none_or(|from| file.start >= from) && to.is_none_or(|to| file.start < to)
+    });
+    if let Some(device_id) = query.device {
+        // Session files are only known by their summary, read after the cheaper filters
+        files = tokio::task::spawn_blocking(move || {
+            files
+                .into_iter()
+                .filter(|file| summary::file_devices(&file.path).contains(&device_id))
+                .collect()
+        })
+        .await
+        .map_err(|err| Error::Internal(err.to_string()))?;
     }
 
+    // Sort files by modification time (newest first) when detailed listing is enabled, pages
+    // need a stable order so they default to the newest recordings first
+    let sort = query
+        .sort
+        .or(show_detailed_listing.then_some(ListSort::Modified))
+        .or(query.page.map(|_| ListSort::Start));
+    if let Some(sort) = sort {
+        match sort {
+            ListSort::Name => files.sort_by(|a, b| a.info.file_name.cmp(&b.info.file_name)),
+            ListSort::Size => files.sort_by_key(|file| file.info.file_size),
+            ListSort::Modified => files.sort_by_key(|file| file.modified),
+            ListSort::Start => files.sort_by_key(|file| file.start),
+        }
+        if query.order != Some(SortOrder::Asc) {
+            files.reverse();
+        }
+    }
+
+    let total = files.len();
+    let files: Vec<McapFileInfo> = match query.page {
+        Some(page) => {
+            let per_page = query.per_page.unwrap_or(DEFAULT_PER_PAGE).max(1);
+            files
+                .into_iter()
+                .skip(page.saturating_sub(1).saturating_mul(per_page))
+                .take(per_page)
+                .map(|file| file.info)
+                .collect()
+        }
+        None => files.into_iter().map(|file| file.info).collect(),
+    };
+
     debug!(
-        "Total files found: {} (MCAP filter: {})",
-        files.len(),
-        !show_detailed_listing
+        "Total files found: {} (MCAP filter: {}), returning {}",
+        total,
+        !show_detailed_listing,
+        files.len()
     );
-    Ok(Json(files))
+    Ok(HttpResponse::Ok()
+        .append_header(("X-Total-Count", total.to_string()))
+        .json(files))
+}
+
+/// Channels, message counts, time span, devices, GPS area and metadata of a recording
+#[api_v2_operation(tags("Recordings Server"))]
+#[get("/recordings/summary/{file_name}")]
+async fn recording_summary_get(
+    req: HttpRequest,
+    file_name: web::Path<String>,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
+) -> Result<Json<summary::RecordingSummary>, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let recordings_dir = recordings_handler.base_path();
+    let canonical_file = secure_file_path(recordings_dir, &file_name)
+        .map_err(|_| Error::BadRequest(format!("No recording named {file_name}")))?;
+
+    let summary = tokio::task::spawn_blocking(move || {
+        let content = summary::map_file(&canonical_file)?;
+        summary::summarize(&content)
+    })
+    .await
+    .map_err(|err| Error::Internal(err.to_string()))?
+    .map_err(conversion_error)?;
+    Ok(Json(summary))
 }
 
 // Helper function to securely resolve a file path
@@ -140,8 +298,12 @@ async fn download_mcap_file(
     file_name: web::Path<String>,
     req: web::HttpRequest,
     query: web::Query<std::collections::HashMap<String, String>>,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
 ) -> impl Responder {
-    let recordings_dir = Path::new("recordings");
+    if let Err(err) = auth::require(&req, Role::Viewer) {
+        return err.error_response();
+    }
+    let recordings_dir = recordings_handler.base_path();
     let canonical_file = match secure_file_path(recordings_dir, &file_name) {
         Ok(path) => path,
         Err(resp) => return resp,
@@ -193,10 +355,359 @@ async fn download_mcap_file(
     }
 }
 
+// Response body fed by a blocking task, through a ChannelWriter
+struct ChannelBody(mpsc::Receiver<io::Result<Bytes>>);
+
+impl MessageBody for ChannelBody {
+    type Error = io::Error;
+
+    fn size(&self) -> BodySize {
+        BodySize::Stream
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        self.0.poll_recv(cx)
+    }
+}
+
+// Fails once the client is gone, which stops the conversion
+struct ChannelWriter(mpsc::Sender<io::Result<Bytes>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(Ok(Bytes::copy_from_slice(buf)))
+            .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Stream what the blocking closure writes, an error after the start aborts the response
+fn stream_response(
+    content_type: &str,
+    file_name: &str,
+    write: impl FnOnce(BufWriter<ChannelWriter>) -> Result<(), ConversionError> + Send + 'static,
+) -> actix_web::HttpResponse {
+    let (sender, receiver) = mpsc::channel(STREAM_CHANNEL_SIZE);
+    let writer = BufWriter::with_capacity(STREAM_CHUNK_SIZE, ChannelWriter(sender.clone()));
+    tokio::task::spawn_blocking(move || {
+        if let Err(err) = write(writer) {
+            warn!("Failed to stream converted recording: {err}");
+            let _ = sender.blocking_send(Err(io::Error::other(err.to_string())));
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .append_header((
+            "Content-Disposition",
+            format!("attachment; filename=\"{file_name}\""),
+        ))
+        .append_header(("Cache-Control", "no-cache, no-store, must-revalidate"))
+        .body(ChannelBody(receiver))
+}
+
+fn conversion_error(err: ConversionError) -> Error {
+    match err {
+        ConversionError::Invalid(message) => Error::BadRequest(message),
+        ConversionError::Io(err) => Error::Internal(err.to_string()),
+    }
+}
+
+// Stream a device of a recording, errors found before converting anything are returned as such
+async fn stream_export(
+    path: PathBuf,
+    device: Option<Uuid>,
+    time_range: TimeRange,
+    format: ExportFormat,
+    file_name: &str,
+) -> Result<HttpResponse, Error> {
+    let (ready_sender, ready_receiver) = oneshot::channel();
+    let response = stream_response(format.content_type(), file_name, move |writer| {
+        let content = match summary::map_file(&path) {
+            Ok(content) => content,
+            Err(err) => {
+                let _ = ready_sender.send(Err(err.into()));
+                return Ok(());
+            }
+        };
+        let export = match RecordingExport::new(&content, device) {
+            Ok(export) => export.with_time_range(time_range),
+            Err(err) => {
+                let _ = ready_sender.send(Err(err));
+                return Ok(());
+            }
+        };
+        let _ = ready_sender.send(Ok(()));
+        export.write(format, writer)
+    });
+
+    ready_receiver
+        .await
+        .map_err(|err| Error::Internal(err.to_string()))?
+        .map_err(conversion_error)?;
+    Ok(response)
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+    /// Device to export, the first one of the recording when empty
+    pub device: Option<Uuid>,
+}
+
+/// Convert a recording to a Ping Viewer sensor log or to CSV, streamed while being converted
+#[api_v2_operation(tags("Recordings Server"))]
+#[get("/recordings/export/{file_name}")]
+async fn export_mcap_file(
+    req: HttpRequest,
+    file_name: web::Path<String>,
+    query: web::Query<ExportQuery>,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
+) -> Result<HttpResponse, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let recordings_dir = recordings_handler.base_path();
+    let canonical_file = match secure_file_path(recordings_dir, &file_name) {
+        Ok(path) => path,
+        Err(resp) => return Ok(resp),
+    };
+    let ExportQuery { format, device } = query.into_inner();
+
+    let stem = Path::new(file_name.as_str())
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let export_name = format!("{stem}.{}", format.extension());
+    debug!("Exporting {file_name} as {export_name}");
+
+    stream_export(canonical_file, device, TimeRange::ALL, format, &export_name).await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractFormat {
+    Mcap,
+    /// Sonar data of a single device, like the export
+    Csv,
+}
+
+#[derive(Debug, Deserialize, Apiv2Schema)]
+pub struct ExtractQuery {
+    /// Start of the extract, RFC 3339, the beginning of the recording when empty
+    pub start: Option<String>,
+    /// End of the extract, RFC 3339, the end of the recording when empty
+    pub end: Option<String>,
+    /// Comma separated topics to keep, all of them when empty, only used by MCAP extracts
+    pub topics: Option<String>,
+    /// MCAP when empty
+    pub format: Option<ExtractFormat>,
+    /// Device of a CSV extract, the first one of the recording when empty
+    pub device: Option<Uuid>,
+}
+
+// Extracts are written to the system temporary directory before being sent, and deleted once
+// sent, so they never show up among the recordings
+struct TemporaryFile(PathBuf);
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.0) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                warn!("Failed to delete temporary file {:?}: {err}", self.0);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn time_range(query: &ExtractQuery) -> Result<TimeRange, Error> {
+    let nanoseconds = |time: DateTime<Utc>| {
+        time.timestamp_nanos_opt()
+            .and_then(|time| u64::try_from(time).ok())
+            .ok_or_else(|| Error::BadRequest(format!("Time out of range: {time}")))
+    };
+    let time_range = TimeRange {
+        start: parse_query_time(&query.start)?
+            .map(nanoseconds)
+            .transpose()?
+            .unwrap_or(TimeRange::ALL.start),
+        end: parse_query_time(&query.end)?
+            .map(nanoseconds)
+            .transpose()?
+            .unwrap_or(TimeRange::ALL.end),
+    };
+    if time_range.start >= time_range.end {
+        return Err(Error::BadRequest(
+            "The start of the extract should be before its end".to_string(),
+        ));
+    }
+    Ok(time_range)
+}
+
+/// Part of a recording between two times, optionally limited to some topics, streamed as a
+/// smaller MCAP file or as CSV
+#[api_v2_operation(tags("Recordings Server"))]
+#[get("/recordings/extract/{file_name}")]
+async fn extract_mcap_file(
+    req: HttpRequest,
+    file_name: web::Path<String>,
+    query: web::Query<ExtractQuery>,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
+) -> Result<HttpResponse, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let recordings_dir = recordings_handler.base_path();
+    let canonical_file = match secure_file_path(recordings_dir, &file_name) {
+        Ok(path) => path,
+        Err(resp) => return Ok(resp),
+    };
+    let query = query.into_inner();
+    let time_range = time_range(&query)?;
+    let stem = Path::new(file_name.as_str())
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    match query.format.unwrap_or(ExtractFormat::Mcap) {
+        ExtractFormat::Mcap => {
+            let topics: Vec<String> = query
+                .topics
+                .iter()
+                .flat_map(|topics| topics.split(','))
+                .map(|topic| topic.trim().to_string())
+                .filter(|topic| !topic.is_empty())
+                .collect();
+            let temporary_path =
+                std::env::temp_dir().join(format!("ping_viewer_extract_{}.mcap", Uuid::new_v4()));
+
+            let temporary_file = tokio::task::spawn_blocking(move || {
+                let content = summary::map_file(&canonical_file)?;
+                let temporary_file = TemporaryFile(temporary_path);
+                let output = BufWriter::new(File::create(&temporary_file.0)?);
+                let message_count = export::extract_mcap(&content, time_range, &topics, output)?;
+                debug!("Extracted {message_count} messages from {canonical_file:?}");
+                Ok::<_, ConversionError>(temporary_file)
+            })
+            .await
+            .map_err(|err| Error::Internal(err.to_string()))?
+            .map_err(conversion_error)?;
+
+            let extract_name = format!("{stem}_extract.mcap");
+            Ok(stream_response(
+                "application/octet-stream",
+                &extract_name,
+                move |mut writer| {
+                    let mut file = File::open(&temporary_file.0)?;
+                    io::copy(&mut file, &mut writer)?;
+                    writer.flush()?;
+                    Ok(())
+                },
+            ))
+        }
+        ExtractFormat::Csv => {
+            let extract_name = format!("{stem}_extract.csv");
+            stream_export(
+                canonical_file,
+                query.device,
+                time_range,
+                ExportFormat::Csv,
+                &extract_name,
+            )
+            .await
+        }
+    }
+}
+
+/// Convert a Ping Viewer sensor log, sent as the request body, to a new recording
+#[api_v2_operation(tags("Recordings Server"))]
+#[post("/recordings/import/{file_name}")]
+async fn import_ping_viewer_log(
+    req: HttpRequest,
+    file_name: web::Path<String>,
+    body: web::Payload,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
+) -> Result<Json<ImportedLog>, Error> {
+    auth::require(&req, Role::Operator)?;
+
+    let file_name = file_name.into_inner();
+    let stem = Path::new(&file_name)
+        .file_stem()
+        .filter(|_| Path::new(&file_name).file_name() == Some(file_name.as_ref()))
+        .map(|stem| stem.to_string_lossy().to_string())
+        .ok_or_else(|| Error::BadRequest(format!("Invalid file name: {file_name}")))?;
+
+    let recordings_dir = recordings_handler.base_path();
+    fs::create_dir_all(recordings_dir)
+        .map_err(|err| Error::Internal(format!("Failed to create recordings directory: {err}")))?;
+    let output = recordings_dir.join(format!("imported_{stem}.mcap"));
+    if output.exists() {
+        return Err(Error::Conflict(format!(
+            "Log {file_name} was already imported as {output:?}"
+        )));
+    }
+
+    let content = body
+        .to_bytes_limited(MAX_IMPORT_SIZE)
+        .await
+        .map_err(|_| {
+            Error::BadRequest(format!(
+                "Log larger than {} MB",
+                MAX_IMPORT_SIZE / (1024 * 1024)
+            ))
+        })?
+        .map_err(|err| Error::BadRequest(format!("Failed to receive log: {err}")))?;
+    let start_time = ping_viewer::file_name_time(&file_name).unwrap_or_else(Utc::now);
+
+    let imported = tokio::task::spawn_blocking(move || {
+        export::import_ping_viewer_log(&content, start_time, &output)
+    })
+    .await
+    .map_err(|err| Error::Internal(err.to_string()))?
+    .map_err(conversion_error)?;
+    debug!("Imported {file_name} as {}", imported.file_name);
+    Ok(Json(imported))
+}
+
+/// Metadata records and markers of a recording
+#[api_v2_operation(tags("Recordings Server"))]
+#[get("/recordings/annotations/{file_name}")]
+async fn recording_annotations_get(
+    req: HttpRequest,
+    file_name: web::Path<String>,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
+) -> Result<Json<RecordingAnnotations>, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let recordings_dir = recordings_handler.base_path();
+    let canonical_file = secure_file_path(recordings_dir, &file_name)
+        .map_err(|_| Error::BadRequest(format!("No recording named {file_name}")))?;
+
+    let annotations = tokio::task::spawn_blocking(move || {
+        let content = summary::map_file(&canonical_file)?;
+        annotations::read_annotations(&content)
+    })
+    .await
+    .map_err(|err| Error::Internal(err.to_string()))?
+    .map_err(conversion_error)?;
+    Ok(Json(annotations))
+}
+
 #[api_v2_operation(tags("Recordings Server"))]
 #[delete("/recordings/delete/{file_name}")]
-async fn delete_mcap_file(file_name: web::Path<String>) -> impl Responder {
-    let recordings_dir = Path::new("recordings");
+async fn delete_mcap_file(
+    req: HttpRequest,
+    file_name: web::Path<String>,
+    recordings_handler: web::Data<RecordingsManagerHandler>,
+) -> impl Responder {
+    if let Err(err) = auth::require(&req, Role::Operator) {
+        return err.error_response();
+    }
+    let recordings_dir = recordings_handler.base_path();
     let canonical_file = match secure_file_path(recordings_dir, &file_name) {
         Ok(path) => path,
         Err(resp) => return resp,
@@ -222,8 +733,10 @@ async fn delete_mcap_file(file_name: web::Path<String>) -> impl Responder {
 #[api_v2_operation(tags("Recordings Manager"))]
 #[get("recordings_manager/list")]
 async fn recording_manager_get(
+    req: HttpRequest,
     recording_tx: web::Data<RecordingsManagerHandler>,
 ) -> Result<Json<crate::device::recording::Answer>, Error> {
+    auth::require(&req, Role::Viewer)?;
     let request = RecordingManagerCommand::GetAllRecordingStatus;
     let answer = recording_tx.send(request).await?;
     Ok(Json(answer))
@@ -232,6 +745,7 @@ async fn recording_manager_get(
 #[api_v2_operation(tags("Recordings Manager : Device"))]
 #[post("recordings_manager/{device}/{selection}")]
 async fn recording_manager_post(
+    req: HttpRequest,
     recording_tx: web::Data<RecordingsManagerHandler>,
     info: web::Path<(Uuid, RecordingsManagerPostOptionsV1)>,
 ) -> Result<Json<crate::device::recording::Answer>, Error> {
@@ -251,6 +765,114 @@ async fn recording_manager_post(
         }
     };
 
+    auth::require(&req, auth::recording_command_role(&request))?;
+    let answer = recording_tx.send(request).await?;
+    Ok(Json(answer))
+}
+
+/// Record several devices on a single file, started and stopped together
+#[api_v2_operation(tags("Recordings Manager : Session"))]
+#[post("recordings_manager/sessions")]
+async fn recording_session_post(
+    req: HttpRequest,
+    recording_tx: web::Data<RecordingsManagerHandler>,
+    session: web::Json<crate::device::recording::StartSession>,
+) -> Result<Json<crate::device::recording::Answer>, Error> {
+    let request = RecordingManagerCommand::StartSession(session.into_inner());
+    auth::require(&req, auth::recording_command_role(&request))?;
+    let answer = recording_tx.send(request).await?;
+    Ok(Json(answer))
+}
+
+#[api_v2_operation(tags("Recordings Manager : Session"))]
+#[get("recordings_manager/sessions/{session}")]
+async fn recording_session_get(
+    req: HttpRequest,
+    recording_tx: web::Data<RecordingsManagerHandler>,
+    session: web::Path<Uuid>,
+) -> Result<Json<crate::device::recording::Answer>, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let request = RecordingManagerCommand::GetSessionStatus(UuidWrapper {
+        uuid: session.into_inner(),
+    });
+    let answer = recording_tx.send(request).await?;
+    Ok(Json(answer))
+}
+
+#[api_v2_operation(tags("Recordings Manager : Session"))]
+#[post("recordings_manager/sessions/{session}/stop")]
+async fn recording_session_stop_post(
+    req: HttpRequest,
+    recording_tx: web::Data<RecordingsManagerHandler>,
+    session: web::Path<Uuid>,
+) -> Result<Json<crate::device::recording::Answer>, Error> {
+    let request = RecordingManagerCommand::StopSession(UuidWrapper {
+        uuid: session.into_inner(),
+    });
+    auth::require(&req, auth::recording_command_role(&request))?;
+    let answer = recording_tx.send(request).await?;
+    Ok(Json(answer))
+}
+
+/// Dive name, operator and site of the session, written as metadata on its files
+#[api_v2_operation(tags("Recordings Manager : Session"))]
+#[post("recordings_manager/sessions/{session}/tags")]
+async fn recording_session_tags_post(
+    req: HttpRequest,
+    recording_tx: web::Data<RecordingsManagerHandler>,
+    session: web::Path<Uuid>,
+    tags: web::Json<annotations::SessionTags>,
+) -> Result<Json<crate::device::recording::Answer>, Error> {
+    let request = RecordingManagerCommand::SetSessionTags(annotations::SetSessionTags {
+        session_id: session.into_inner(),
+        tags: tags.into_inner(),
+    });
+    auth::require(&req, auth::recording_command_role(&request))?;
+    let answer = recording_tx.send(request).await?;
+    Ok(Json(answer))
+}
+
+/// Free text marker written on the annotations channel of the session
+#[api_v2_operation(tags("Recordings Manager : Session"))]
+#[post("recordings_manager/sessions/{session}/markers")]
+async fn recording_session_marker_post(
+    req: HttpRequest,
+    recording_tx: web::Data<RecordingsManagerHandler>,
+    session: web::Path<Uuid>,
+    marker: web::Json<annotations::NewMarker>,
+) -> Result<Json<crate::device::recording::Answer>, Error> {
+    let request = RecordingManagerCommand::AddMarker(annotations::AddMarker {
+        session_id: session.into_inner(),
+        marker: marker.into_inner(),
+    });
+    auth::require(&req, auth::recording_command_role(&request))?;
+    let answer = recording_tx.send(request).await?;
+    Ok(Json(answer))
+}
+
+#[api_v2_operation(tags("Recordings Manager"))]
+#[get("recordings_manager/policy")]
+async fn recording_policy_get(
+    req: HttpRequest,
+    recording_tx: web::Data<RecordingsManagerHandler>,
+) -> Result<Json<crate::device::recording::Answer>, Error> {
+    auth::require(&req, Role::Viewer)?;
+    let answer = recording_tx
+        .send(RecordingManagerCommand::GetRecordingPolicy)
+        .await?;
+    Ok(Json(answer))
+}
+
+/// Split, retention and free disk space policy applied to all recordings
+#[api_v2_operation(tags("Recordings Manager"))]
+#[post("recordings_manager/policy")]
+async fn recording_policy_post(
+    req: HttpRequest,
+    recording_tx: web::Data<RecordingsManagerHandler>,
+    policy: web::Json<crate::device::recording::rolling::RecordingPolicy>,
+) -> Result<Json<crate::device::recording::Answer>, Error> {
+    let request = RecordingManagerCommand::SetRecordingPolicy(policy.into_inner());
+    auth::require(&req, auth::recording_command_role(&request))?;
     let answer = recording_tx.send(request).await?;
     Ok(Json(answer))
 }
@@ -258,10 +880,12 @@ async fn recording_manager_post(
 #[api_v2_operation(tags("Recording Manager: Request"))]
 #[post("recordings_manager/request")]
 async fn recordings_manager_post_request(
+    req: HttpRequest,
     manager_handler: web::Data<RecordingsManagerHandler>,
     json: web::Json<crate::device::recording::RecordingManagerCommand>,
 ) -> Result<Json<crate::device::recording::Answer>, Error> {
     let request = json.into_inner();
+    auth::require(&req, auth::recording_command_role(&request))?;
     let answer = manager_handler.send(request).await?;
     Ok(Json(answer))
 }
@@ -15,13 +15,14 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::sync::{Arc, Mutex};
 use tokio::sync::broadcast;
-use tracing::info;
+use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::device::{
     manager::{ManagerActorHandler, Request},
     recording::{RecordingManagerCommand, RecordingsManagerHandler},
 };
+use crate::server::auth::{self, Role};
 
 pub struct StringMessage(String);
 
@@ -70,6 +71,13 @@ lazy_static! {
         Arc::new(Mutex::new(WebsocketManager::default()));
 }
 
+pub fn has_clients() -> bool {
+    MANAGER
+        .lock()
+        .map(|manager| !manager.clients.is_empty())
+        .unwrap_or(false)
+}
+
 pub fn send_to_websockets(message: Value, device: Option<Uuid>) {
     MANAGER
         .lock()
@@ -81,6 +89,9 @@ pub struct WebsocketActor {
     server: Arc<Mutex<WebsocketManager>>,
     pub filter: String,
     pub device_number: Option<Uuid>,
+    pub client_id: String,
+    /// Role of the authenticated client, checked on every request sent through the socket
+    pub role: Option<Role>,
     pub manager_handler: web::Data<ManagerActorHandler>,
 }
 
@@ -88,12 +99,16 @@ impl WebsocketActor {
     pub fn new(
         message_filter: String,
         device_number: Option<Uuid>,
+        client_id: String,
+        role: Option<Role>,
         manager_handler: web::Data<ManagerActorHandler>,
     ) -> Self {
         Self {
             server: MANAGER.clone(),
             filter: message_filter,
             device_number,
+            client_id,
+            role,
             manager_handler,
         }
     }
@@ -153,6 +168,14 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebsocketActor {
                 for request in manager_requests {
                     match request {
                         crate::ModuleType::DeviceManager(request) => {
+                            if let Err(err) = auth::authorize(self.role, &request) {
+                                let error = WebsocketError {
+                                    error: err.to_string(),
+                                };
+                                ctx.text(serde_json::to_string_pretty(&error).unwrap());
+                                continue;
+                            }
+
                             let manager_handler = self.manager_handler.clone();
 
                             let request_has_id = match &request {
@@ -166,11 +189,19 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebsocketActor {
                                 Request::DisableContinuousMode(uuid_wrapper) => {
                                     Some(uuid_wrapper.uuid)
                                 }
+                                Request::ReplayControl(replay_control) => Some(replay_control.uuid),
+                                Request::AcquireLock(lock_request) => Some(lock_request.uuid),
+                                Request::ReleaseLock(uuid_wrapper)
+                                | Request::ForceReleaseLock(uuid_wrapper) => {
+                                    Some(uuid_wrapper.uuid)
+                                }
                                 _ => None,
                             };
 
+                            let client_id = Some(self.client_id.clone());
                             let future =
-                                async move { manager_handler.send(request).await }.into_actor(self);
+                                async move { manager_handler.send_from(client_id, request).await }
+                                    .into_actor(self);
 
                             future
                                 .then(move |res, actor, ctx| {
@@ -210,6 +241,7 @@ pub async fn websocket(
     stream: web::Payload,
     manager_handler: web::Data<ManagerActorHandler>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    auth::require(&req, Role::Viewer)?;
     let query_inner = query.into_inner();
 
     let filter = match query_inner.filter {
@@ -217,6 +249,10 @@ pub async fn websocket(
         _ => ".*".to_owned(),
     };
     let device_number = query_inner.device_number;
+    let client_id = query_inner
+        .client_id
+        .unwrap_or_else(|| format!("websocket-{}", Uuid::new_v4()));
+    let client_id = auth::client_owner(auth::identity(&req).as_ref(), &client_id);
 
     if let Some(device_number) = device_number {
         let request = crate::device::manager::Request::Info(crate::device::manager::UuidWrapper {
@@ -235,7 +271,13 @@ pub async fn websocket(
     }
 
     ws::start(
-        WebsocketActor::new(filter, device_number, manager_handler.clone()),
+        WebsocketActor::new(
+            filter,
+            device_number,
+            client_id,
+            auth::identity(&req).map(|identity| identity.role),
+            manager_handler.clone(),
+        ),
         &req,
         stream,
     )
@@ -297,6 +339,7 @@ pub async fn recording_websocket(
     stream: web::Payload,
     recorder_handler: web::Data<RecordingsManagerHandler>,
 ) -> Result<HttpResponse, actix_web::Error> {
+    auth::require(&req, Role::Viewer)?;
     let recording_manager = match recorder_handler
         .send(RecordingManagerCommand::GetSubscriber)
         .await
@@ -318,4 +361,74 @@ pub struct WebsocketQuery {
     /// Regex filter to select the desired incoming messages
     filter: Option<String>,
     device_number: Option<Uuid>,
+    /// Identifies the client on device locks, a new one is generated for each connection without it
+    client_id: Option<String>,
+}
+
+pub struct VehiclePoseActor {
+    pose_subscriber: broadcast::Receiver<crate::vehicle::VehiclePose>,
+}
+
+impl Actor for VehiclePoseActor {
+    type Context = ws::WebsocketContext<Self>;
+}
+
+impl Handler<StringMessage> for VehiclePoseActor {
+    type Result = ();
+
+    fn handle(&mut self, message: StringMessage, ctx: &mut Self::Context) {
+        ctx.text(message.0);
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for VehiclePoseActor {
+    fn started(&mut self, ctx: &mut Self::Context) {
+        info!("VehiclePoseActor: Starting websocket client");
+
+        let addr = ctx.address();
+        let mut subscriber = self.pose_subscriber.resubscribe();
+
+        tokio::spawn(async move {
+            loop {
+                match subscriber.recv().await {
+                    Ok(pose) => {
+                        if !addr.connected() {
+                            break;
+                        }
+                        match serde_json::to_string(&pose) {
+                            Ok(pose) => addr.do_send(StringMessage(pose)),
+                            Err(err) => error!("VehiclePoseActor: Failed to serialize pose: {err}"),
+                        }
+                    }
+                    // Only the latest pose matters to a slow client
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(msg)) => ctx.close(msg),
+            _ => (),
+        }
+    }
+}
+
+#[api_v2_operation(skip)]
+#[get("ws/vehicle")]
+pub async fn vehicle_websocket(
+    req: HttpRequest,
+    stream: web::Payload,
+) -> Result<HttpResponse, actix_web::Error> {
+    auth::require(&req, Role::Viewer)?;
+    ws::start(
+        VehiclePoseActor {
+            pose_subscriber: crate::vehicle::subscribe_pose(),
+        },
+        &req,
+        stream,
+    )
 }
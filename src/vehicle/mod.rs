@@ -1,14 +1,21 @@
-use std::sync::Arc;
+use std::{str::FromStr, sync::Arc};
 
+use lazy_static::lazy_static;
 use mavlink::ardupilotmega::ATTITUDE_DATA;
 use mavlink::ardupilotmega::GLOBAL_POSITION_INT_DATA;
+use mavlink::ardupilotmega::SCALED_PRESSURE2_DATA;
+use mavlink::ardupilotmega::VFR_HUD_DATA;
 
+use paperclip::actix::Apiv2Schema;
 use serde::Deserialize;
 use serde::Serialize;
-use tokio::sync::RwLock;
-use tracing::{error, info};
+use tokio::sync::{broadcast, RwLock};
+use tracing::{error, info, warn};
 
-#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+/// Timestamped poses received by the bridge, used to find the pose at a given time.
+pub mod pose_history;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, schemars::JsonSchema, Apiv2Schema)]
 pub struct VehicleData {
     #[schemars(description = "Roll angle in radians")]
     pub roll: f32,
@@ -16,48 +23,335 @@ pub struct VehicleData {
     pub pitch: f32,
     #[schemars(description = "Yaw angle in radians")]
     pub yaw: f32,
+    #[schemars(description = "Roll angular speed in radians per second")]
+    pub rollspeed: f32,
+    #[schemars(description = "Pitch angular speed in radians per second")]
+    pub pitchspeed: f32,
+    #[schemars(description = "Yaw angular speed in radians per second")]
+    pub yawspeed: f32,
     #[schemars(description = "Altitude in meters above sea level")]
     pub alt: f64,
+    #[schemars(description = "Altitude in meters above home")]
+    pub relative_alt: f64,
     #[schemars(description = "Latitude in decimal degrees")]
     pub lat: f64,
     #[schemars(description = "Longitude in decimal degrees")]
     pub lon: f64,
+    #[schemars(description = "Ground speed to the north in meters per second")]
+    pub vx: f32,
+    #[schemars(description = "Ground speed to the east in meters per second")]
+    pub vy: f32,
+    #[schemars(description = "Ground speed downwards in meters per second")]
+    pub vz: f32,
+    #[schemars(description = "Heading in degrees, from 0 to 360, empty when unknown")]
+    pub hdg: Option<f32>,
+    #[schemars(
+        description = "Depth in meters below the surface, from SCALED_PRESSURE2 or VFR_HUD, empty when unknown"
+    )]
+    pub depth: Option<f32>,
+    #[schemars(
+        description = "Water temperature in degrees Celsius, from SCALED_PRESSURE2, empty when unknown"
+    )]
+    pub water_temperature: Option<f32>,
+    #[schemars(description = "Autopilot time since boot in milliseconds, from ATTITUDE")]
+    pub time_boot_ms: u32,
+}
+
+// Surface pressure and water density used to turn the external pressure sensor reading into depth
+const SURFACE_PRESSURE_HPA: f32 = 1013.25;
+const WATER_DENSITY: f32 = 1025.0;
+const GRAVITY: f32 = 9.80665;
+
+fn pressure_depth(pressure: &SCALED_PRESSURE2_DATA) -> f32 {
+    (pressure.press_abs - SURFACE_PRESSURE_HPA) * 100.0 / (WATER_DENSITY * GRAVITY)
+}
+
+impl VehicleData {
+    // External pressure sensor is preferred for depth, VFR_HUD altitude is the depth reported by ArduSub
+    pub fn from_messages(
+        attitude: &ATTITUDE_DATA,
+        position: &GLOBAL_POSITION_INT_DATA,
+        pressure: Option<&SCALED_PRESSURE2_DATA>,
+        vfr_hud: Option<&VFR_HUD_DATA>,
+    ) -> Self {
+        let depth = pressure
+            .map(pressure_depth)
+            .or_else(|| vfr_hud.map(|vfr_hud| -vfr_hud.alt));
+
+        Self {
+            roll: attitude.roll,
+            pitch: attitude.pitch,
+            yaw: attitude.yaw,
+            rollspeed: attitude.rollspeed,
+            pitchspeed: attitude.pitchspeed,
+            yawspeed: attitude.yawspeed,
+            alt: position.alt as f64 / 1000.0,
+            relative_alt: position.relative_alt as f64 / 1000.0,
+            lat: position.lat as f64 / 1e7,
+            lon: position.lon as f64 / 1e7,
+            vx: position.vx as f32 / 100.0,
+            vy: position.vy as f32 / 100.0,
+            vz: position.vz as f32 / 100.0,
+            hdg: (position.hdg != u16::MAX).then_some(position.hdg as f32 / 100.0),
+            depth,
+            water_temperature: pressure.map(|pressure| pressure.temperature as f32 / 100.0),
+            time_boot_ms: attitude.time_boot_ms,
+        }
+    }
+}
+
+/// Latest pose received by the bridge, as served by the REST API and the pose websocket.
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct VehiclePose {
+    /// Time the pose was received, in milliseconds since the Unix epoch
+    pub timestamp: i64,
+    pub pose: VehicleData,
 }
 
+/// Zenoh session opened by the bridge, shared with other services publishing on the same bus.
+/// It's empty while the bridge is disconnected.
+pub type ZenohSession = Arc<RwLock<Option<zenoh::Session>>>;
+
 #[derive(Deserialize)]
 struct Envelope<T> {
     message: T,
 }
 
-fn make_default_config(node_name: &str) -> zenoh::Config {
-    let mut config = zenoh::Config::default();
+pub const DEFAULT_ZENOH_ENDPOINT: &str = "tcp/127.0.0.1:7447";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+#[serde(rename_all = "lowercase")]
+pub enum ZenohMode {
+    Client,
+    Peer,
+    Router,
+}
+
+impl ZenohMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ZenohMode::Client => "client",
+            ZenohMode::Peer => "peer",
+            ZenohMode::Router => "router",
+        }
+    }
+}
+
+impl FromStr for ZenohMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "client" => Ok(ZenohMode::Client),
+            "peer" => Ok(ZenohMode::Peer),
+            "router" => Ok(ZenohMode::Router),
+            _ => Err(format!(
+                "Invalid Zenoh mode \"{value}\", expected \"client\", \"peer\" or \"router\""
+            )),
+        }
+    }
+}
+
+/// How the bridge connects to the Zenoh network and which vehicle it follows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub struct ZenohBridgeConfig {
+    pub enabled: bool,
+    /// Overrides the mode of the config file, client when there is no file
+    pub mode: Option<ZenohMode>,
+    /// Overrides the connect endpoints of the config file, `tcp/127.0.0.1:7447` when there is no file
+    pub endpoints: Vec<String>,
+    /// Zenoh configuration file used as base for the session
+    pub config_file: Option<String>,
+    /// MAVLink system id of the vehicle
+    pub system_id: u8,
+    /// Root of the MAVLink keys, messages are received from `<prefix>/**/<system_id>/<MESSAGE>`
+    pub key_prefix: String,
+}
+
+impl Default for ZenohBridgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            mode: None,
+            endpoints: vec![],
+            config_file: None,
+            system_id: 1,
+            key_prefix: "mavlink".to_string(),
+        }
+    }
+}
+
+impl ZenohBridgeConfig {
+    pub fn message_key(&self, message: &str) -> String {
+        format!("{}/**/{}/{message}", self.key_prefix, self.system_id)
+    }
+
+    // Key where messages going to the vehicle are accepted by the MAVLink bridge
+    pub fn outgoing_key(&self) -> String {
+        format!("{}/in", self.key_prefix)
+    }
+
+    pub fn effective_mode(&self) -> Option<ZenohMode> {
+        match (self.mode, &self.config_file) {
+            (Some(mode), _) => Some(mode),
+            (None, None) => Some(ZenohMode::Client),
+            // Defined by the file
+            (None, Some(_)) => None,
+        }
+    }
+
+    pub fn effective_endpoints(&self) -> Vec<String> {
+        match (self.endpoints.is_empty(), &self.config_file) {
+            (false, _) => self.endpoints.clone(),
+            (true, None) => vec![DEFAULT_ZENOH_ENDPOINT.to_string()],
+            (true, Some(_)) => vec![],
+        }
+    }
+
+    pub fn zenoh_config(&self, node_name: &str) -> Result<zenoh::Config, String> {
+        let mut config = match &self.config_file {
+            Some(path) => zenoh::Config::from_file(path)
+                .map_err(|err| format!("Failed to load Zenoh config file {path}: {err}"))?,
+            None => {
+                let mut config = zenoh::Config::default();
+                config
+                    .insert_json5("metadata", &format!(r#"{{"name": "{}"}}"#, node_name))
+                    .map_err(|err| format!("Failed to insert metadata: {err}"))?;
+                config
+                    .insert_json5("adminspace/enabled", r#"true"#)
+                    .map_err(|err| format!("Failed to insert adminspace/enabled: {err}"))?;
+                config
+            }
+        };
+
+        if let Some(mode) = self.effective_mode() {
+            config
+                .insert_json5("mode", &format!(r#""{}""#, mode.as_str()))
+                .map_err(|err| format!("Failed to insert mode: {err}"))?;
+        }
+
+        let endpoints = self.effective_endpoints();
+        if !endpoints.is_empty() {
+            let endpoints = serde_json::to_string(&endpoints).map_err(|err| err.to_string())?;
+            config
+                .insert_json5("connect/endpoints", &endpoints)
+                .map_err(|err| format!("Failed to insert endpoints: {err}"))?;
+        }
+
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Apiv2Schema)]
+pub enum ZenohBridgeState {
+    Disabled,
+    Connecting,
+    Connected,
+    Disconnected,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Apiv2Schema)]
+pub struct ZenohBridgeStatus {
+    pub state: ZenohBridgeState,
+    pub config: ZenohBridgeConfig,
+    /// Zenoh id of the current session
+    pub session_id: Option<String>,
+    /// Keys subscribed for the vehicle pose
+    pub subscriptions: Vec<String>,
+    pub last_error: Option<String>,
+    /// Time of the last pose received, in milliseconds since the Unix epoch
+    pub last_pose_timestamp: Option<i64>,
+    /// Time since the last pose received, in milliseconds
+    pub last_pose_age_ms: Option<i64>,
+}
+
+lazy_static! {
+    // Updated by the bridge task and read by the REST API
+    static ref BRIDGE_STATUS: std::sync::RwLock<ZenohBridgeStatus> =
+        std::sync::RwLock::new(ZenohBridgeStatus {
+            state: ZenohBridgeState::Disabled,
+            config: ZenohBridgeConfig::default(),
+            session_id: None,
+            subscriptions: vec![],
+            last_error: None,
+            last_pose_timestamp: None,
+            last_pose_age_ms: None,
+        });
+    static ref LATEST_POSE: std::sync::RwLock<Option<VehiclePose>> = std::sync::RwLock::new(None);
+    static ref POSE_BROADCAST: broadcast::Sender<VehiclePose> = broadcast::channel(100).0;
+}
 
-    // Set client mode (common to both)
-    config
-        .insert_json5("mode", r#""client""#)
-        .expect("Failed to insert client mode");
-    config
-        .insert_json5("metadata", &format!(r#"{{"name": "{}"}}"#, node_name))
-        .expect("Failed to insert metadata");
-    config
-        .insert_json5("adminspace/enabled", r#"true"#)
-        .expect("Failed to insert adminspace/enabled");
-    config
-        .insert_json5("connect/endpoints", r#"["tcp/127.0.0.1:7447"]"#)
-        .expect("Failed to insert endpoints");
-    info!("Generated zenoh config with default settings");
-    config
+pub fn latest_pose() -> Option<VehiclePose> {
+    LATEST_POSE.read().ok()?.clone()
 }
 
-pub async fn zenoh_client_bridge(latest_pose: Arc<RwLock<Option<VehicleData>>>) {
+pub fn subscribe_pose() -> broadcast::Receiver<VehiclePose> {
+    POSE_BROADCAST.subscribe()
+}
+
+fn publish_pose(pose: VehiclePose) {
+    if let Ok(mut latest) = LATEST_POSE.write() {
+        *latest = Some(pose.clone());
+    }
+    let _ = POSE_BROADCAST.send(pose);
+}
+
+fn update_status(update: impl FnOnce(&mut ZenohBridgeStatus)) {
+    match BRIDGE_STATUS.write() {
+        Ok(mut status) => update(&mut status),
+        Err(err) => error!("Failed to update Zenoh bridge status: {err}"),
+    }
+}
+
+pub fn bridge_status() -> ZenohBridgeStatus {
+    let mut status = BRIDGE_STATUS
+        .read()
+        .map(|status| status.clone())
+        .unwrap_or_else(|err| err.into_inner().clone());
+    status.last_pose_age_ms = status
+        .last_pose_timestamp
+        .map(|timestamp| chrono::Utc::now().timestamp_millis() - timestamp);
+    status
+}
+
+pub async fn zenoh_client_bridge(
+    pose_history: pose_history::SharedPoseHistory,
+    shared_session: ZenohSession,
+    bridge_config: ZenohBridgeConfig,
+) {
     use tokio::time::{sleep, Duration};
     let node_name = env!("CARGO_PKG_NAME");
 
+    update_status(|status| status.config = bridge_config.clone());
+    if !bridge_config.enabled {
+        info!("Zenoh client bridge disabled");
+        return;
+    }
+
     let reconnect_delay_secs = 5;
     let reconnect_delay = Duration::from_secs(reconnect_delay_secs);
 
+    let attitude_key = bridge_config.message_key("ATTITUDE");
+    let position_key = bridge_config.message_key("GLOBAL_POSITION_INT");
+    let pressure_key = bridge_config.message_key("SCALED_PRESSURE2");
+    let vfr_hud_key = bridge_config.message_key("VFR_HUD");
+
     loop {
-        let config = make_default_config(node_name);
+        update_status(|status| status.state = ZenohBridgeState::Connecting);
+
+        let config = match bridge_config.zenoh_config(node_name) {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Zenoh config error: {e}, retrying in {reconnect_delay_secs}s");
+                update_status(|status| {
+                    status.state = ZenohBridgeState::Error;
+                    status.last_error = Some(e);
+                });
+                sleep(reconnect_delay).await;
+                continue;
+            }
+        };
 
         sleep(reconnect_delay).await;
 
@@ -65,32 +359,71 @@ pub async fn zenoh_client_bridge(latest_pose: Arc<RwLock<Option<VehicleData>>>)
             Ok(s) => s,
             Err(e) => {
                 error!("Zenoh session error: {e}, retrying in {reconnect_delay_secs}s");
+                update_status(|status| {
+                    status.state = ZenohBridgeState::Error;
+                    status.last_error = Some(e.to_string());
+                });
                 continue;
             }
         };
-        let attitude_sub = match session.declare_subscriber("mavlink/**/1/ATTITUDE").await {
-            Ok(s) => s,
-            Err(e) => {
-                error!(
-                    "Zenoh subscribe error for ATTITUDE: {e}, retrying in {reconnect_delay_secs}s"
-                );
+        *shared_session.write().await = Some(session.clone());
+        let subscribers = (
+            session.declare_subscriber(&attitude_key).await,
+            session.declare_subscriber(&position_key).await,
+        );
+        let (attitude_sub, position_sub) = match subscribers {
+            (Ok(attitude), Ok(position)) => (attitude, position),
+            (attitude, position) => {
+                let e = [attitude.err(), position.err()]
+                    .into_iter()
+                    .flatten()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                error!("Zenoh subscribe error: {e}, retrying in {reconnect_delay_secs}s");
+                *shared_session.write().await = None;
+                update_status(|status| {
+                    status.state = ZenohBridgeState::Error;
+                    status.last_error = Some(e);
+                });
                 continue;
             }
         };
-        let position_sub = match session
-            .declare_subscriber("mavlink/**/1/GLOBAL_POSITION_INT")
-            .await
-        {
-            Ok(s) => s,
+        let mut subscriptions = vec![attitude_key.clone(), position_key.clone()];
+
+        // Depth and temperature are optional, the pose is published without them
+        let mut pressure_sub = match session.declare_subscriber(&pressure_key).await {
+            Ok(subscriber) => {
+                subscriptions.push(pressure_key.clone());
+                Some(subscriber)
+            }
             Err(e) => {
-                error!("Zenoh subscribe error for GLOBAL_POSITION_INT: {e}, retrying in {reconnect_delay_secs}s");
-                continue;
+                warn!("Zenoh subscribe error: {e}, continuing without {pressure_key}");
+                None
+            }
+        };
+        let mut vfr_hud_sub = match session.declare_subscriber(&vfr_hud_key).await {
+            Ok(subscriber) => {
+                subscriptions.push(vfr_hud_key.clone());
+                Some(subscriber)
+            }
+            Err(e) => {
+                warn!("Zenoh subscribe error: {e}, continuing without {vfr_hud_key}");
+                None
             }
         };
-        info!("Subscribed to mavlink/**/1/ATTITUDE and mavlink/**/1/GLOBAL_POSITION_INT");
+        info!("Subscribed to {}", subscriptions.join(", "));
+        update_status(|status| {
+            status.state = ZenohBridgeState::Connected;
+            status.session_id = Some(session.zid().to_string());
+            status.subscriptions = subscriptions;
+            status.last_error = None;
+        });
 
         let mut latest_attitude: Option<ATTITUDE_DATA> = None;
         let mut latest_position: Option<GLOBAL_POSITION_INT_DATA> = None;
+        let mut latest_pressure: Option<SCALED_PRESSURE2_DATA> = None;
+        let mut latest_vfr_hud: Option<VFR_HUD_DATA> = None;
 
         loop {
             tokio::select! {
@@ -120,22 +453,159 @@ pub async fn zenoh_client_bridge(latest_pose: Arc<RwLock<Option<VehicleData>>>)
                         }
                     }
                 }
+                res = async {
+                    match &pressure_sub {
+                        Some(subscriber) => subscriber.recv_async().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    match res {
+                        Ok(sample) => {
+                            if let Ok(env) = serde_json5::from_slice::<Envelope<SCALED_PRESSURE2_DATA>>(&sample.payload().to_bytes()) {
+                                latest_pressure = Some(env.message);
+                            }
+                        },
+                        Err(e) => {
+                            warn!("Zenoh SCALED_PRESSURE2 recv error: {e}, continuing without it");
+                            pressure_sub = None;
+                            latest_pressure = None;
+                        }
+                    }
+                }
+                res = async {
+                    match &vfr_hud_sub {
+                        Some(subscriber) => subscriber.recv_async().await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    match res {
+                        Ok(sample) => {
+                            if let Ok(env) = serde_json5::from_slice::<Envelope<VFR_HUD_DATA>>(&sample.payload().to_bytes()) {
+                                latest_vfr_hud = Some(env.message);
+                            }
+                        },
+                        Err(e) => {
+                            warn!("Zenoh VFR_HUD recv error: {e}, continuing without it");
+                            vfr_hud_sub = None;
+                            latest_vfr_hud = None;
+                        }
+                    }
+                }
             }
 
             if let (Some(att), Some(pos)) = (&latest_attitude, &latest_position) {
-                let pose = VehicleData {
-                    roll: att.roll,
-                    pitch: att.pitch,
-                    yaw: att.yaw,
-                    alt: pos.alt as f64 / 1000.0,
-                    lat: pos.lat as f64 / 1e7,
-                    lon: pos.lon as f64 / 1e7,
-                };
-                let mut pose_guard = latest_pose.write().await;
-                *pose_guard = Some(pose);
+                let pose = VehicleData::from_messages(
+                    att,
+                    pos,
+                    latest_pressure.as_ref(),
+                    latest_vfr_hud.as_ref(),
+                );
+                // Poses are timestamped on arrival, sonar samples are matched against this time
+                let timestamp = chrono::Utc::now();
+                pose_history.write().await.push(timestamp, pose.clone());
+                publish_pose(VehiclePose {
+                    timestamp: timestamp.timestamp_millis(),
+                    pose,
+                });
+                update_status(|status| {
+                    status.last_pose_timestamp = Some(timestamp.timestamp_millis())
+                });
             }
         }
 
+        *shared_session.write().await = None;
+        update_status(|status| {
+            status.state = ZenohBridgeState::Disconnected;
+            status.session_id = None;
+        });
         error!("Zenoh client bridge disconnected, retrying in {reconnect_delay_secs}s");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zenoh_bridge_config() {
+        let config = ZenohBridgeConfig::default();
+        assert_eq!(config.message_key("ATTITUDE"), "mavlink/**/1/ATTITUDE");
+        assert_eq!(config.effective_mode(), Some(ZenohMode::Client));
+        assert_eq!(config.effective_endpoints(), vec![DEFAULT_ZENOH_ENDPOINT]);
+
+        let config = ZenohBridgeConfig {
+            mode: Some(ZenohMode::Peer),
+            endpoints: vec!["tcp/192.168.2.2:7447".to_string()],
+            system_id: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            config.message_key("GLOBAL_POSITION_INT"),
+            "mavlink/**/2/GLOBAL_POSITION_INT"
+        );
+        let zenoh_config = config.zenoh_config("test").unwrap();
+        assert_eq!(zenoh_config.get_json("mode").unwrap(), r#""peer""#);
+
+        // Without overrides, the config file decides
+        let config = ZenohBridgeConfig {
+            config_file: Some("zenoh.json5".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.effective_mode(), None);
+        assert!(config.effective_endpoints().is_empty());
+
+        assert_eq!("Router".parse::<ZenohMode>().unwrap(), ZenohMode::Router);
+        assert!("bridge".parse::<ZenohMode>().is_err());
+    }
+
+    #[test]
+    fn test_vehicle_data_from_messages() {
+        let attitude = ATTITUDE_DATA {
+            time_boot_ms: 1234,
+            yawspeed: 0.5,
+            ..Default::default()
+        };
+        let position = GLOBAL_POSITION_INT_DATA {
+            relative_alt: -2500,
+            vx: 150,
+            hdg: 9000,
+            ..Default::default()
+        };
+        let pressure = SCALED_PRESSURE2_DATA {
+            press_abs: SURFACE_PRESSURE_HPA + WATER_DENSITY * GRAVITY / 100.0,
+            temperature: 1250,
+            ..Default::default()
+        };
+        let vfr_hud = VFR_HUD_DATA {
+            alt: -3.0,
+            ..Default::default()
+        };
+
+        let data = VehicleData::from_messages(&attitude, &position, None, None);
+        assert_eq!(data.time_boot_ms, 1234);
+        assert_eq!(data.yawspeed, 0.5);
+        assert_eq!(data.relative_alt, -2.5);
+        assert_eq!(data.vx, 1.5);
+        assert_eq!(data.hdg, Some(90.0));
+        assert_eq!(data.depth, None);
+        assert_eq!(data.water_temperature, None);
+
+        let data = VehicleData::from_messages(&attitude, &position, None, Some(&vfr_hud));
+        assert_eq!(data.depth, Some(3.0));
+
+        // The pressure sensor takes precedence, one meter of water column
+        let data =
+            VehicleData::from_messages(&attitude, &position, Some(&pressure), Some(&vfr_hud));
+        assert!((data.depth.unwrap() - 1.0).abs() < 1e-3);
+        assert_eq!(data.water_temperature, Some(12.5));
+
+        let position = GLOBAL_POSITION_INT_DATA {
+            hdg: u16::MAX,
+            ..Default::default()
+        };
+        assert_eq!(
+            VehicleData::from_messages(&attitude, &position, None, None).hdg,
+            None
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::{collections::VecDeque, f32::consts::PI, sync::Arc};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use super::VehicleData;
+
+/// Poses older than this, compared to the newest one, are dropped from the history.
+pub const POSE_HISTORY_DURATION: Duration = Duration::seconds(10);
+pub const POSE_HISTORY_CAPACITY: usize = 1000;
+/// A pose further than this from the requested time is flagged as stale.
+pub const POSE_STALE_AFTER: Duration = Duration::milliseconds(500);
+
+/// Poses received by the bridge, shared with recordings.
+pub type SharedPoseHistory = Arc<RwLock<PoseHistory>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoseSample {
+    /// Time the pose was received by the bridge
+    pub timestamp: DateTime<Utc>,
+    pub pose: VehicleData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
+pub struct InterpolatedPose {
+    #[schemars(description = "Pose at the time of the sonar sample")]
+    pub pose: VehicleData,
+    #[schemars(description = "Time of the sonar sample, in microseconds since the Unix epoch")]
+    pub timestamp_us: i64,
+    #[schemars(description = "True when the pose was interpolated between two received poses")]
+    pub interpolated: bool,
+    #[schemars(
+        description = "Time between the sonar sample and the closest received pose, in milliseconds"
+    )]
+    pub age_ms: f64,
+    #[schemars(description = "True when no recent pose was available for the sonar sample")]
+    pub stale: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PoseHistory {
+    samples: VecDeque<PoseSample>,
+}
+
+fn lerp(from: f64, to: f64, fraction: f64) -> f64 {
+    from + (to - from) * fraction
+}
+
+// Follow the shortest way around the circle, so interpolating from 179° to -179° goes through 180°
+fn lerp_angle(from: f32, to: f32, fraction: f32) -> f32 {
+    let delta = (to - from + PI).rem_euclid(2.0 * PI) - PI;
+    let angle = from + delta * fraction;
+    (angle + PI).rem_euclid(2.0 * PI) - PI
+}
+
+// Optional values are only interpolated when known on both sides, otherwise the known one is kept
+fn lerp_option(
+    from: Option<f32>,
+    to: Option<f32>,
+    fraction: f64,
+    lerp: impl Fn(f32, f32, f32) -> f32,
+) -> Option<f32> {
+    match (from, to) {
+        (Some(from), Some(to)) => Some(lerp(from, to, fraction as f32)),
+        (from, to) => to.or(from),
+    }
+}
+
+fn interpolate(from: &VehicleData, to: &VehicleData, fraction: f64) -> VehicleData {
+    let lerp32 = |from: f32, to: f32| lerp(from as f64, to as f64, fraction) as f32;
+
+    VehicleData {
+        roll: lerp_angle(from.roll, to.roll, fraction as f32),
+        pitch: lerp_angle(from.pitch, to.pitch, fraction as f32),
+        yaw: lerp_angle(from.yaw, to.yaw, fraction as f32),
+        rollspeed: lerp32(from.rollspeed, to.rollspeed),
+        pitchspeed: lerp32(from.pitchspeed, to.pitchspeed),
+        yawspeed: lerp32(from.yawspeed, to.yawspeed),
+        alt: lerp(from.alt, to.alt, fraction),
+        relative_alt: lerp(from.relative_alt, to.relative_alt, fraction),
+        lat: lerp(from.lat, to.lat, fraction),
+        lon: lerp(from.lon, to.lon, fraction),
+        vx: lerp32(from.vx, to.vx),
+        vy: lerp32(from.vy, to.vy),
+        vz: lerp32(from.vz, to.vz),
+        hdg: lerp_option(from.hdg, to.hdg, fraction, |from, to, fraction| {
+            lerp_angle(from.to_radians(), to.to_radians(), fraction)
+                .to_degrees()
+                .rem_euclid(360.0)
+        }),
+        depth: lerp_option(from.depth, to.depth, fraction, |from, to, fraction| {
+            lerp(from as f64, to as f64, fraction as f64) as f32
+        }),
+        water_temperature: lerp_option(
+            from.water_temperature,
+            to.water_temperature,
+            fraction,
+            |from, to, fraction| lerp(from as f64, to as f64, fraction as f64) as f32,
+        ),
+        time_boot_ms: lerp(from.time_boot_ms as f64, to.time_boot_ms as f64, fraction).round()
+            as u32,
+    }
+}
+
+impl PoseHistory {
+    // Samples are expected in arrival order, older ones out of the history window are dropped
+    pub fn push(&mut self, timestamp: DateTime<Utc>, pose: VehicleData) {
+        if self
+            .samples
+            .back()
+            .is_some_and(|last| last.timestamp > timestamp)
+        {
+            return;
+        }
+
+        self.samples.push_back(PoseSample { timestamp, pose });
+        while self.samples.len() > POSE_HISTORY_CAPACITY
+            || self
+                .samples
+                .front()
+                .is_some_and(|first| timestamp - first.timestamp > POSE_HISTORY_DURATION)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn latest(&self) -> Option<&PoseSample> {
+        self.samples.back()
+    }
+
+    /// Samples received after a given time, oldest first
+    pub fn since(&self, timestamp: DateTime<Utc>) -> impl Iterator<Item = &PoseSample> {
+        let start = self
+            .samples
+            .partition_point(|sample| sample.timestamp <= timestamp);
+        self.samples.range(start..)
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    // Pose at a given time, interpolated between the surrounding samples, or the closest sample
+    // when the time is outside of the history
+    pub fn at(&self, timestamp: DateTime<Utc>) -> Option<InterpolatedPose> {
+        let next_index = self
+            .samples
+            .partition_point(|sample| sample.timestamp <= timestamp);
+
+        let previous = next_index
+            .checked_sub(1)
+            .and_then(|index| self.samples.get(index));
+        let next = self.samples.get(next_index);
+
+        let (pose, interpolated, age) = match (previous, next) {
+            (Some(previous), Some(next)) => {
+                let span = (next.timestamp - previous.timestamp).num_microseconds()? as f64;
+                let elapsed = (timestamp - previous.timestamp).num_microseconds()? as f64;
+                let fraction = if span > 0.0 { elapsed / span } else { 0.0 };
+                let age = (timestamp - previous.timestamp).min(next.timestamp - timestamp);
+                // Interpolating over a long gap is as unreliable as a stale pose
+                let gap = next.timestamp - previous.timestamp;
+                (
+                    interpolate(&previous.pose, &next.pose, fraction),
+                    true,
+                    if gap > POSE_STALE_AFTER { gap } else { age },
+                )
+            }
+            (Some(previous), None) => {
+                (previous.pose.clone(), false, timestamp - previous.timestamp)
+            }
+            (None, Some(next)) => (next.pose.clone(), false, next.timestamp - timestamp),
+            (None, None) => return None,
+        };
+
+        Some(InterpolatedPose {
+            pose,
+            timestamp_us: timestamp.timestamp_micros(),
+            interpolated,
+            age_ms: age.num_microseconds().unwrap_or(i64::MAX) as f64 / 1000.0,
+            stale: age > POSE_STALE_AFTER,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pose(yaw: f32, lat: f64) -> VehicleData {
+        VehicleData {
+            yaw,
+            lat,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_pose_interpolation() {
+        let start = Utc::now();
+        let mut history = PoseHistory::default();
+        assert!(history.at(start).is_none());
+
+        history.push(start, pose(3.0, 10.0));
+        history.push(start + Duration::milliseconds(100), pose(-3.0, 20.0));
+
+        let middle = history.at(start + Duration::milliseconds(25)).unwrap();
+        assert!(middle.interpolated);
+        assert!(!middle.stale);
+        assert!((middle.pose.lat - 12.5).abs() < 1e-9);
+        // Yaw goes through PI instead of zero
+        assert!(middle.pose.yaw > 3.0 || middle.pose.yaw < -3.0);
+        assert_eq!(middle.pose.hdg, None);
+        assert!((middle.age_ms - 25.0).abs() < 1e-6);
+
+        // After the newest pose the last one is held, until it gets stale
+        let held = history.at(start + Duration::milliseconds(300)).unwrap();
+        assert!(!held.interpolated);
+        assert!(!held.stale);
+        assert_eq!(held.pose.lat, 20.0);
+        assert!(history.at(start + Duration::seconds(2)).unwrap().stale);
+    }
+
+    #[test]
+    fn test_pose_history_window() {
+        let start = Utc::now();
+        let mut history = PoseHistory::default();
+        history.push(start, pose(0.0, 0.0));
+        history.push(start + Duration::seconds(5), pose(0.0, 1.0));
+        // Out of order samples are ignored
+        history.push(start + Duration::seconds(1), pose(0.0, 2.0));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.since(start).count(), 1);
+
+        history.push(start + Duration::seconds(11), pose(0.0, 3.0));
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.latest().unwrap().pose.lat, 3.0);
+
+        // A long gap between poses is flagged, even when interpolating
+        let gap = history.at(start + Duration::seconds(8)).unwrap();
+        assert!(gap.interpolated);
+        assert!(gap.stale);
+    }
+}
@@ -0,0 +1,210 @@
+use std::{net::Ipv4Addr, time::Duration};
+
+use tokio::{net::UdpSocket, time::timeout};
+use uuid::Uuid;
+
+use ping_viewer_next::device::{
+    devices::{Ping1DRequest, PingAnswer, PingRequest},
+    manager::{
+        Answer, DeviceAnswer, DeviceInfo, DeviceManager, DeviceRequestStruct, DeviceSelection,
+        ManagerActorHandler, Request, UuidWrapper,
+    },
+    simulator::{self, SimulatorConfig, SimulatorHandle, SimulatorTransport},
+};
+
+fn udp_config(device_type: DeviceSelection) -> SimulatorConfig {
+    let mut config = SimulatorConfig::new(device_type);
+    config.transport = SimulatorTransport::Udp(0);
+    config.discovery_port = None;
+    config
+}
+
+// The simulator handle should be kept alive while the device is used
+async fn create(
+    handler: &ManagerActorHandler,
+    config: SimulatorConfig,
+) -> (SimulatorHandle, DeviceInfo) {
+    let simulator = simulator::start(config).await.unwrap();
+    let mut create = simulator.create_struct();
+    create.device_selection = DeviceSelection::Auto;
+
+    match handler.send(Request::Create(create)).await.unwrap() {
+        Answer::DeviceInfo(mut info) => (simulator, info.pop().unwrap()),
+        answer => panic!("Unexpected answer: {answer:?}"),
+    }
+}
+
+async fn subscribe(
+    handler: &ManagerActorHandler,
+    uuid: Uuid,
+) -> tokio::sync::broadcast::Receiver<bluerobotics_ping::message::ProtocolMessage> {
+    let device_handler = match handler
+        .send(Request::GetDeviceHandler(UuidWrapper { uuid }))
+        .await
+        .unwrap()
+    {
+        Answer::InnerDeviceHandler(device_handler) => device_handler,
+        answer => panic!("Unexpected answer: {answer:?}"),
+    };
+
+    match device_handler
+        .send(PingRequest::GetSubscriber)
+        .await
+        .unwrap()
+    {
+        PingAnswer::Subscriber(subscriber) => subscriber,
+        answer => panic!("Unexpected answer: {answer:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_simulated_ping1d() {
+    let (manager, handler) = DeviceManager::new(10);
+    tokio::spawn(async move { manager.run().await });
+
+    let (_simulator, info) = create(&handler, udp_config(DeviceSelection::Ping1D)).await;
+    assert_eq!(info.device_type, DeviceSelection::Ping1D);
+
+    let request = Request::Ping(DeviceRequestStruct {
+        uuid: info.id,
+        device_request: PingRequest::Ping1D(Ping1DRequest::Profile),
+    });
+    let profile = match handler.send(request).await.unwrap() {
+        Answer::DeviceMessage(DeviceAnswer {
+            answer:
+                PingAnswer::PingMessage(bluerobotics_ping::Messages::Ping1D(
+                    bluerobotics_ping::ping1d::Messages::Profile(profile),
+                )),
+            ..
+        }) => profile,
+        answer => panic!("Unexpected answer: {answer:?}"),
+    };
+
+    // Default seabed is 10 meters deep with 1.5 meters of undulation
+    assert!((8_500..=11_500).contains(&profile.distance));
+    assert_eq!(
+        profile.profile_data.len(),
+        profile.profile_data_length as usize
+    );
+
+    // Devices are created in continuous mode, so profiles should keep arriving
+    let mut subscriber = subscribe(&handler, info.id).await;
+    let streamed = timeout(Duration::from_secs(2), async {
+        loop {
+            let message = subscriber.recv().await.unwrap();
+            if let Ok(bluerobotics_ping::Messages::Ping1D(
+                bluerobotics_ping::ping1d::Messages::Profile(profile),
+            )) = bluerobotics_ping::Messages::try_from(&message)
+            {
+                return profile;
+            }
+        }
+    })
+    .await
+    .expect("No profile streamed by the simulator");
+    assert!(streamed.ping_number > profile.ping_number);
+}
+
+#[tokio::test]
+async fn test_simulated_ping360() {
+    let (manager, handler) = DeviceManager::new(10);
+    tokio::spawn(async move { manager.run().await });
+
+    let (_simulator, info) = create(&handler, udp_config(DeviceSelection::Ping360)).await;
+    assert_eq!(info.device_type, DeviceSelection::Ping360);
+
+    // Over UDP the firmware auto-transmit mode is used, so the head should be moving by itself
+    let mut subscriber = subscribe(&handler, info.id).await;
+    let angles = timeout(Duration::from_secs(5), async {
+        let mut angles = Vec::new();
+        while angles.len() < 3 {
+            let message = subscriber.recv().await.unwrap();
+            if let Ok(bluerobotics_ping::Messages::Ping360(
+                bluerobotics_ping::ping360::Messages::AutoDeviceData(data),
+            )) = bluerobotics_ping::Messages::try_from(&message)
+            {
+                assert_eq!(data.data.len(), data.number_of_samples as usize);
+                angles.push(data.angle);
+            }
+        }
+        angles
+    })
+    .await
+    .expect("No data streamed by the simulator");
+    assert_ne!(angles[0], angles[2]);
+}
+
+// Some containers have a /dev/ptmx that doesn't belong to the devpts mounted on /dev/pts,
+// the pty slaves can't be opened by their name there
+#[cfg(unix)]
+fn pty_available() -> bool {
+    use tokio_serial::{SerialPort, SerialPortBuilderExt};
+
+    let Ok((_master, mut slave)) = tokio_serial::SerialStream::pair() else {
+        return false;
+    };
+    let _ = slave.set_exclusive(false);
+    slave
+        .name()
+        .is_some_and(|path| tokio_serial::new(path, 115200).open_native_async().is_ok())
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_simulated_ping1d_pty() {
+    if !pty_available() {
+        eprintln!("Skipping test_simulated_ping1d_pty: pty slaves can't be opened");
+        return;
+    }
+
+    let (manager, handler) = DeviceManager::new(10);
+    tokio::spawn(async move { manager.run().await });
+
+    let mut config = SimulatorConfig::new(DeviceSelection::Ping1D);
+    config.transport = SimulatorTransport::Pty;
+
+    let (_simulator, info) = create(&handler, config).await;
+    assert_eq!(info.device_type, DeviceSelection::Ping1D);
+}
+
+#[tokio::test]
+async fn test_simulated_discovery() {
+    let mut config = SimulatorConfig::new(DeviceSelection::Ping360);
+    config.transport = SimulatorTransport::Udp(0);
+    config.discovery_port = Some(0);
+
+    let simulator = simulator::start(config).await.unwrap();
+    let discovery_port = simulator.discovery_port.unwrap();
+
+    let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+    socket
+        .send_to(b"Discovery", (Ipv4Addr::LOCALHOST, discovery_port))
+        .await
+        .unwrap();
+
+    let mut buffer = [0u8; 1024];
+    let (size, _) = timeout(Duration::from_secs(2), socket.recv_from(&mut buffer))
+        .await
+        .expect("No discovery answer")
+        .unwrap();
+
+    assert_eq!(
+        std::str::from_utf8(&buffer[..size]).unwrap(),
+        simulator::discovery_response(Ipv4Addr::LOCALHOST)
+    );
+}
+
+#[test]
+fn test_simulator_config_parsing() {
+    let config: SimulatorConfig = "ping360:udp:4000".parse().unwrap();
+    assert_eq!(config.device_type, DeviceSelection::Ping360);
+    assert_eq!(config.transport, SimulatorTransport::Udp(4000));
+
+    let config: SimulatorConfig = "Ping1D:pty".parse().unwrap();
+    assert_eq!(config.device_type, DeviceSelection::Ping1D);
+    assert_eq!(config.transport, SimulatorTransport::Pty);
+
+    assert!("ping2d".parse::<SimulatorConfig>().is_err());
+    assert!("ping1d:tcp".parse::<SimulatorConfig>().is_err());
+    assert!("ping1d:udp:1:2".parse::<SimulatorConfig>().is_err());
+}